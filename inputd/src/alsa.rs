@@ -0,0 +1,196 @@
+use std::{
+    fs,
+    process::{Command, Stdio},
+    sync::Mutex,
+};
+
+use crate::{config::AlsaCard, volume::VolumeSink, Execute};
+
+/// An ALSA mixer control, controlled with `amixer`.
+pub struct Mixer {
+    /// The card and its mixer control. They change when a preferred card is
+    /// plugged in or out.
+    card: Mutex<AlsaCard>,
+}
+
+impl Mixer {
+    /// Open the mixer control of the specified card.
+    ///
+    /// If the card doesn't have the control, the error lists the available
+    /// controls.
+    pub fn open(card: &AlsaCard) -> Result<Self, String> {
+        check_control(card)?;
+        Ok(Self {
+            card: Mutex::new(card.clone()),
+        })
+    }
+
+    /// Return the card and the mixer control that are in use.
+    pub fn card(&self) -> AlsaCard {
+        self.card.lock().unwrap().clone()
+    }
+
+    /// Switch to the mixer control of another card.
+    pub fn switch(&self, card: &AlsaCard) -> Result<(), String> {
+        check_control(card)?;
+        *self.card.lock().unwrap() = card.clone();
+        Ok(())
+    }
+
+    fn amixer(&self, value: &str) -> Result<(), String> {
+        let card = self.card();
+        let status = Command::new("amixer")
+            .args(["-q", "-M", "-c", &card.card, "sset", &card.mixer, value])
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .execute()
+            .map_err(|e| format!("Could not run amixer: {}", e))?;
+        if status.success() {
+            Ok(())
+        } else {
+            Err(format!("Exit status {} from amixer", status))
+        }
+    }
+}
+
+impl VolumeSink for Mixer {
+    /// Set the volume in percent.
+    ///
+    /// The volume is mapped to the control's range so that it's perceived as
+    /// linear (`amixer -M`).
+    fn set_volume(&self, volume: u8) -> bool {
+        let control = self.card().mixer;
+        match self.amixer(&format!("{}%", volume.min(100))) {
+            Ok(()) => {
+                info!("alsa", { volume: volume }, "Set {} volume to {}%", control, volume);
+                true
+            },
+            Err(e) => {
+                error!("alsa", "Could not set {} volume: {}", control, e);
+                false
+            },
+        }
+    }
+
+    /// Set the volumes of the left and the right channel in percent.
+    fn set_channel_volumes(&self, left: u8, right: u8) -> bool {
+        let control = self.card().mixer;
+        match self.amixer(&format!("{}%,{}%", left.min(100), right.min(100))) {
+            Ok(()) => {
+                info!("alsa", "Set {} volume to {}% (left), {}% (right)", control, left, right);
+                true
+            },
+            Err(e) => {
+                error!("alsa", "Could not set {} volume: {}", control, e);
+                false
+            },
+        }
+    }
+
+    /// Muting sets the volume to 0. On unmute, the volume control restores
+    /// the volume.
+    fn set_mute(&self, muted: bool) {
+        if muted {
+            self.set_volume(0);
+        }
+    }
+}
+
+/// Check that a card has the mixer control, listing the available controls
+/// otherwise.
+fn check_control(card: &AlsaCard) -> Result<(), String> {
+    let controls = controls(&card.card)?;
+    if controls.contains(&card.mixer) {
+        Ok(())
+    } else {
+        Err(format!(
+            "Card {} has no mixer control \"{}\", available controls: {}",
+            card.card,
+            card.mixer,
+            controls.join(", ")
+        ))
+    }
+}
+
+/// Return the simple mixer controls of a card.
+pub fn controls(card: &str) -> Result<Vec<String>, String> {
+    let output = Command::new("amixer")
+        .args(["-c", card, "scontrols"])
+        .stderr(Stdio::null())
+        .output()
+        .map_err(|e| format!("Could not run amixer: {}", e))?;
+    if !output.status.success() {
+        return Err(format!("Could not list mixer controls of card {}", card));
+    }
+    Ok(parse_controls(&String::from_utf8_lossy(&output.stdout)))
+}
+
+/// Parse the output of `amixer scontrols`, e.g. "Simple mixer control
+/// 'Digital',0".
+pub fn parse_controls(output: &str) -> Vec<String> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let start = line.find('\'')? + 1;
+            let end = line.rfind('\'')?;
+            line.get(start..end).map(str::to_string)
+        })
+        .collect()
+}
+
+/// Play a sound file on a card to check that the audio output works.
+///
+/// A missing card usually means that the device tree overlay of the DAC
+/// isn't loaded, which is pointed out in the error.
+pub fn self_test(card: &str, path: &str) -> Result<(), String> {
+    let cards = cards();
+    if !has_card(&cards, card) {
+        let found: Vec<String> = cards.iter().map(|(index, id)| format!("{} ({})", id, index)).collect();
+        return Err(format!(
+            "Sound card {} not found (found: {}), check the dtoverlay of the DAC in /boot/config.txt",
+            card,
+            if found.is_empty() { "none".into() } else { found.join(", ") }
+        ));
+    }
+    let status = Command::new("aplay")
+        .args(["-q", "-D", &format!("plughw:{}", card), path])
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .execute()
+        .map_err(|e| format!("Could not run aplay: {}", e))?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(format!("Exit status {} when playing {} on card {}", status, path, card))
+    }
+}
+
+/// Return the index and the id of every sound card.
+pub fn cards() -> Vec<(String, String)> {
+    fs::read_to_string("/proc/asound/cards")
+        .map(|contents| parse_cards(&contents))
+        .unwrap_or_default()
+}
+
+/// Return whether a card with the index or id is present.
+pub fn has_card(cards: &[(String, String)], card: &str) -> bool {
+    cards.iter().any(|(index, id)| index == card || id == card)
+}
+
+/// Select the card to use: the first preferred card that is plugged in, or
+/// the default card.
+pub fn select_card<'a>(preferred: &'a [AlsaCard], default: &'a AlsaCard, cards: &[(String, String)]) -> &'a AlsaCard {
+    preferred.iter().find(|card| has_card(cards, &card.card)).unwrap_or(default)
+}
+
+/// Parse `/proc/asound/cards`, returning the index and the id of every card.
+pub fn parse_cards(contents: &str) -> Vec<(String, String)> {
+    contents
+        .lines()
+        .filter_map(|line| {
+            let (index, rest) = line.trim_start().split_once(" [")?;
+            let (id, _) = rest.split_once(']')?;
+            Some((index.to_string(), id.trim().to_string()))
+        })
+        .collect()
+}
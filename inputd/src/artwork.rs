@@ -0,0 +1,161 @@
+use std::{
+    collections::hash_map::DefaultHasher,
+    fs,
+    hash::{Hash, Hasher},
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+};
+
+use image::imageops::FilterType;
+
+/// Width and height of the artwork on the display in pixels.
+pub const ARTWORK_SIZE: usize = 120;
+
+/// Maximum number of files in the cache. The oldest ones are removed.
+const MAX_CACHED_FILES: usize = 100;
+
+/// Base URL of volumio's web server, which serves the artwork of the tracks.
+const VOLUMIO_URL: &str = "http://127.0.0.1:3000";
+
+/// A black and white image.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Bitmap {
+    pub width: usize,
+    pub height: usize,
+    /// Whether each pixel is black, row by row.
+    pub black: Vec<bool>,
+}
+
+impl Bitmap {
+    pub fn is_black(&self, x: usize, y: usize) -> bool {
+        x < self.width && y < self.height && self.black[y * self.width + x]
+    }
+}
+
+/// Dither grayscale pixels (row by row) to black and white, diffusing the
+/// error of every pixel to its neighbours (Floyd-Steinberg).
+pub fn dither(luma: &[u8], width: usize, height: usize) -> Bitmap {
+    let mut values: Vec<i16> = luma.iter().map(|&value| value as i16).collect();
+    let mut black = vec![false; width * height];
+    for y in 0..height {
+        for x in 0..width {
+            let old = values[y * width + x];
+            let new = if old < 128 { 0 } else { 255 };
+            black[y * width + x] = new == 0;
+            let error = old - new;
+            let mut spread = |dx: isize, dy: usize, weight: i16| {
+                let nx = x as isize + dx;
+                if nx >= 0 && (nx as usize) < width && y + dy < height {
+                    values[(y + dy) * width + nx as usize] += error * weight / 16;
+                }
+            };
+            spread(1, 0, 7);
+            spread(-1, 1, 3);
+            spread(0, 1, 5);
+            spread(1, 1, 1);
+        }
+    }
+    Bitmap { width, height, black }
+}
+
+/// Return the URL of artwork reported by volumio, which is relative to its
+/// web server for local files.
+pub fn resolve(url: &str) -> String {
+    if url.starts_with('/') {
+        format!("{}{}", VOLUMIO_URL, url)
+    } else {
+        url.to_string()
+    }
+}
+
+/// Return the path of the cached file of an artwork URL.
+pub fn cache_path(dir: &str, url: &str) -> PathBuf {
+    let mut hasher = DefaultHasher::new();
+    url.hash(&mut hasher);
+    Path::new(dir).join(format!("{:016x}", hasher.finish()))
+}
+
+/// Fetches the artwork, caches the files and keeps the dithered image of the
+/// last one.
+pub struct ArtworkCache {
+    dir: String,
+    last: Option<(String, Option<Bitmap>)>,
+}
+
+impl ArtworkCache {
+    pub fn new(dir: &str) -> Self {
+        Self {
+            dir: dir.to_string(),
+            last: None,
+        }
+    }
+
+    /// Return the dithered artwork of a URL.
+    ///
+    /// Artwork that can't be fetched or decoded is only tried (and logged)
+    /// once, until another URL was requested.
+    pub fn get(&mut self, url: &str) -> Option<Bitmap> {
+        if let Some((last_url, bitmap)) = &self.last {
+            if last_url == url {
+                return bitmap.clone();
+            }
+        }
+        let bitmap = self.load(url).map_err(|e| warn!("artwork", { url: url }, "{}", e)).ok();
+        self.last = Some((url.to_string(), bitmap.clone()));
+        bitmap
+    }
+
+    fn load(&self, url: &str) -> Result<Bitmap, String> {
+        let path = cache_path(&self.dir, url);
+        if !path.is_file() {
+            fs::create_dir_all(&self.dir).map_err(|e| format!("Could not create {}: {}", self.dir, e))?;
+            download(url, &path)?;
+            prune(&self.dir);
+        }
+        let image = image::open(&path).map_err(|e| format!("Could not decode artwork {}: {}", url, e))?;
+        let size = ARTWORK_SIZE as u32;
+        let luma = image.resize_to_fill(size, size, FilterType::Triangle).to_luma8();
+        Ok(dither(luma.as_raw(), ARTWORK_SIZE, ARTWORK_SIZE))
+    }
+}
+
+/// Download a file with curl.
+fn download(url: &str, path: &Path) -> Result<(), String> {
+    // An interrupted download must not end up in the cache
+    let partial = path.with_extension("part");
+    let status_res = Command::new("/usr/bin/curl")
+        .args(["-sfL", "--max-time", "10", "-o"])
+        .arg(&partial)
+        .arg(url)
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status();
+    match status_res {
+        Ok(status) if status.success() => {
+            fs::rename(&partial, path).map_err(|e| format!("Could not cache artwork {}: {}", url, e))
+        },
+        Ok(status) => {
+            let _ = fs::remove_file(&partial);
+            Err(format!("Exit status {} of curl when fetching artwork {}", status, url))
+        },
+        Err(e) => Err(format!("Could not run curl: {}", e)),
+    }
+}
+
+/// Remove the oldest files from the cache, if it holds too many.
+fn prune(dir: &str) {
+    let mut files: Vec<_> = fs::read_dir(dir)
+        .map(|entries| {
+            entries
+                .filter_map(|entry| entry.ok())
+                .filter_map(|entry| Some((entry.metadata().ok()?.modified().ok()?, entry.path())))
+                .collect()
+        })
+        .unwrap_or_default();
+    if files.len() > MAX_CACHED_FILES {
+        files.sort();
+        for (_, path) in &files[..files.len() - MAX_CACHED_FILES] {
+            let _ = fs::remove_file(path);
+        }
+    }
+}
@@ -0,0 +1,74 @@
+use std::{
+    path::{self, Path},
+    process::{Command, Stdio},
+    slice,
+};
+
+use crate::config::Config;
+
+/// Write the existing files to a gzipped tar archive, e.g. to set up another
+/// SD card with the same configuration and state.
+///
+/// The files are stored with their absolute paths, so that they are imported
+/// to the same paths. Returns the paths of the archived files.
+pub fn export(archive: &str, paths: &[String]) -> Result<Vec<String>, String> {
+    let files: Vec<String> = paths
+        .iter()
+        .filter(|path| Path::new(path).is_file())
+        .filter_map(|path| absolute(path))
+        .collect();
+    if files.is_empty() {
+        return Err("None of the files exist".into());
+    }
+    tar(&["-czPf", archive], &files)?;
+    Ok(files)
+}
+
+/// Restore the files of an archive written by `export`.
+///
+/// Only the `paths` that `export` archives are restored, since the files are
+/// extracted to their absolute paths, usually by root. Archives with any
+/// other entries are rejected. If the archive contains the configuration
+/// file, it must be valid, so that the daemon doesn't fail to start
+/// afterwards. Returns the paths of the restored files.
+pub fn import(archive: &str, config: Option<&str>, paths: &[String]) -> Result<Vec<String>, String> {
+    let allowed: Vec<String> = paths.iter().filter_map(|path| absolute(path)).collect();
+    let listing = tar(&["-tzPf", archive], &[])?;
+    let mut entries = listing.lines().filter(|line| !line.is_empty());
+    if let Some(entry) = entries.find(|entry| !allowed.iter().any(|path| path == entry)) {
+        return Err(format!("{} contains {}, which isn't written by the export", archive, entry));
+    }
+    let files = parse_listing(&listing);
+    if files.is_empty() {
+        return Err(format!("{} contains no files", archive));
+    }
+    if let Some(config) = config.and_then(absolute).filter(|config| files.contains(config)) {
+        Config::parse(&tar(&["-xzPOf", archive], slice::from_ref(&config))?)
+            .map_err(|e| format!("Invalid configuration {} in {}: {}", config, archive, e))?;
+    }
+    tar(&["-xzPf", archive], &files)?;
+    Ok(files)
+}
+
+/// Return the files of the output of `tar -t`, without directories.
+pub fn parse_listing(listing: &str) -> Vec<String> {
+    listing
+        .lines()
+        .filter(|line| !line.is_empty() && !line.ends_with('/'))
+        .map(str::to_string)
+        .collect()
+}
+
+/// Return the absolute path of a file, which need not exist.
+fn absolute(path: &str) -> Option<String> {
+    path::absolute(path).ok().map(|path| path.display().to_string())
+}
+
+/// Run tar and return its output.
+fn tar(args: &[&str], files: &[String]) -> Result<String, String> {
+    match Command::new("tar").args(args).args(files).stderr(Stdio::inherit()).output() {
+        Ok(output) if output.status.success() => Ok(String::from_utf8_lossy(&output.stdout).into_owned()),
+        Ok(output) => Err(format!("Exit status {} of tar", output.status)),
+        Err(e) => Err(format!("Could not run tar: {}", e)),
+    }
+}
@@ -0,0 +1,66 @@
+/// Volts per bit of the ADC at a full scale range of ±4.096V.
+const VOLTS_PER_BIT: f64 = 4.096 / 32768.0;
+
+/// The battery must rise this far above the warning voltage before the low
+/// battery warning is cleared.
+const HYSTERESIS: f64 = 0.05;
+
+/// Number of consecutive critical readings before shutting down, so that a
+/// single voltage drop (e.g. a load peak) doesn't turn the radio off.
+const CRITICAL_READINGS: u8 = 3;
+
+/// Convert a raw ADC reading to the battery voltage.
+pub fn voltage(raw: i16, divider: f64) -> f64 {
+    raw.max(0) as f64 * VOLTS_PER_BIT * divider
+}
+
+/// A change of the battery state.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BatteryEvent {
+    /// The voltage dropped below the warning voltage.
+    Low,
+    /// The voltage recovered, e.g. because the charger was connected.
+    Recovered,
+    /// The voltage stayed below the shutdown voltage.
+    Critical,
+}
+
+/// Tracks the battery voltage.
+pub struct BatteryMonitor {
+    warn_voltage: f64,
+    shutdown_voltage: f64,
+    low: bool,
+    critical_readings: u8,
+}
+
+impl BatteryMonitor {
+    pub fn new(warn_voltage: f64, shutdown_voltage: f64) -> Self {
+        Self {
+            warn_voltage,
+            shutdown_voltage,
+            low: false,
+            critical_readings: 0,
+        }
+    }
+
+    /// Update the monitor with a new voltage reading.
+    pub fn update(&mut self, volts: f64) -> Option<BatteryEvent> {
+        if volts < self.shutdown_voltage {
+            self.critical_readings = self.critical_readings.saturating_add(1);
+        } else {
+            self.critical_readings = 0;
+        }
+
+        if self.critical_readings == CRITICAL_READINGS {
+            Some(BatteryEvent::Critical)
+        } else if !self.low && volts < self.warn_voltage {
+            self.low = true;
+            Some(BatteryEvent::Low)
+        } else if self.low && volts > self.warn_voltage + HYSTERESIS {
+            self.low = false;
+            Some(BatteryEvent::Recovered)
+        } else {
+            None
+        }
+    }
+}
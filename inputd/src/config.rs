@@ -0,0 +1,1998 @@
+use std::{collections::HashMap, fmt, str::FromStr, time::Duration};
+
+use serde::{Deserialize, Deserializer};
+
+use crate::{
+    hd44780::LcdLine,
+    i18n::Language,
+    leds::{DaemonState, Pattern},
+    LOOKUP_TABLE_VOL,
+};
+
+/// Configuration file contents.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// Language of the texts shown on the display.
+    #[serde(default)]
+    pub language: Language,
+    /// How the GPIO pins are accessed.
+    #[serde(default)]
+    pub gpio: GpioConfig,
+    /// Buttons and switches connected to the GPIO pins.
+    ///
+    /// If not set, the buttons of the original Grundig radio are used.
+    #[serde(default = "default_buttons")]
+    pub buttons: Vec<Button>,
+    /// Debouncing of the buttons.
+    #[serde(default)]
+    pub debounce: Debounce,
+    /// Protection of the "shutdown" button against flaky contacts.
+    #[serde(default)]
+    pub shutdown: ShutdownConfig,
+    /// The lock against small children.
+    #[serde(default)]
+    pub lock: LockConfig,
+    /// Additional ADCs, besides the default ADS1115 at 0x48.
+    #[serde(default)]
+    pub adcs: Vec<AdcDevice>,
+    /// Analog controls connected to the ADC.
+    ///
+    /// If not set, the volume knob is read from A0 and the tone knob from A1.
+    pub analog: Option<Vec<AnalogControl>>,
+    /// Bands with their stations, selected with the band buttons.
+    #[serde(default)]
+    pub bands: Vec<Band>,
+    /// Names and settings of the stations.
+    #[serde(default)]
+    pub stations: Vec<Station>,
+    /// How long streams may take to start, and how often the next one is
+    /// tried.
+    #[serde(default)]
+    pub streams: Streams,
+    /// Station selection with the tuning dial.
+    #[serde(default)]
+    pub tuning: Tuning,
+    /// Simulated shortwave reception of recordings on a band.
+    pub dx: Option<Dx>,
+    /// Rotary encoder connected to the GPIO pins.
+    pub encoder: Option<Encoder>,
+    /// Input devices like IR receivers.
+    #[serde(default)]
+    pub evdev: Vec<EvdevDevice>,
+    /// Outputs like the dial lamp connected to the GPIO pins.
+    #[serde(default)]
+    pub outputs: Vec<Output>,
+    /// Tuning eye tube (e.g. EM34).
+    pub magic_eye: Option<MagicEye>,
+    /// Status LEDs.
+    #[serde(default)]
+    pub leds: Vec<Led>,
+    /// Display showing what's playing.
+    pub display: Option<DisplayConfig>,
+    /// Headphone jack with a detect switch.
+    pub headphones: Option<Headphones>,
+    /// Line output that can be used instead of the speakers.
+    pub line_out: Option<LineOut>,
+    /// ALSA mixer used to set the volume instead of volumio.
+    pub alsa: Option<Alsa>,
+    /// Battery voltage monitoring.
+    pub battery: Option<Battery>,
+    /// CPU temperature and undervoltage monitoring.
+    pub health: Option<Health>,
+    /// Temperature controlled fan, e.g. for the amplifier compartment.
+    pub fan: Option<Fan>,
+    /// Ambient light sensor that the dial lamp and the display are dimmed
+    /// with.
+    pub ambient_light: Option<AmbientLight>,
+    /// Power saving while the radio isn't used.
+    pub idle: Option<Idle>,
+    /// Fewer writes to the SD card.
+    pub low_write: Option<LowWrite>,
+    /// Soft power latch circuit that keeps the radio powered.
+    pub power_latch: Option<PowerLatch>,
+    /// Times at which playback is started or stopped automatically.
+    #[serde(default)]
+    pub schedule: Vec<ScheduleEntry>,
+    /// Real-time clock that keeps the time while the radio is off.
+    pub rtc: Option<Rtc>,
+    /// Weather forecast shown on the display.
+    pub weather: Option<WeatherConfig>,
+    /// Notifications about failures.
+    pub notify: Option<Notify>,
+    /// Named profiles, e.g. for traveling. The top-level keys and sections of
+    /// the selected profile replace those of the configuration.
+    #[serde(default)]
+    pub profiles: HashMap<String, toml::value::Table>,
+    /// BCM numbers of GPIO pins, by profile name. A profile is selected at
+    /// startup if a jumper pulls its pin low.
+    #[serde(default)]
+    pub profile_jumpers: HashMap<String, u8>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            language: Language::default(),
+            gpio: GpioConfig::default(),
+            buttons: default_buttons(),
+            debounce: Debounce::default(),
+            shutdown: ShutdownConfig::default(),
+            lock: LockConfig::default(),
+            adcs: vec![],
+            analog: None,
+            bands: vec![],
+            stations: vec![],
+            streams: Streams::default(),
+            tuning: Tuning::default(),
+            dx: None,
+            encoder: None,
+            evdev: vec![],
+            outputs: vec![],
+            magic_eye: None,
+            leds: vec![],
+            display: None,
+            headphones: None,
+            line_out: None,
+            alsa: None,
+            battery: None,
+            health: None,
+            fan: None,
+            ambient_light: None,
+            idle: None,
+            low_write: None,
+            power_latch: None,
+            schedule: vec![],
+            rtc: None,
+            weather: None,
+            notify: None,
+            profiles: HashMap::new(),
+            profile_jumpers: HashMap::new(),
+        }
+    }
+}
+
+/// A button or switch connected to a GPIO pin.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct Button {
+    /// Name of the button, used in logs and to refer to the button.
+    pub name: String,
+    /// BCM number of the GPIO pin. The pin is pulled up, so by default the
+    /// button is pressed when the pin is low.
+    pub pin: u8,
+    /// Whether the button is pressed when the pin is high.
+    #[serde(default)]
+    pub inverted: bool,
+    pub action: ButtonAction,
+    /// Overrides the global number of debounce samples.
+    pub debounce_samples: Option<u8>,
+    /// Overrides the global debounce sampling interval.
+    pub debounce_interval_ms: Option<u64>,
+    /// Volume in percent at full knob deflection while this band is selected.
+    /// Lower volumes are scaled accordingly.
+    pub max_volume: Option<u8>,
+    /// Number of seconds after which a held button counts as stuck and is
+    /// ignored until it is released. 0 disables the detection.
+    pub stuck_after_s: Option<u64>,
+}
+
+impl Button {
+    fn new(name: &str, pin: u8, inverted: bool, action: ButtonAction) -> Self {
+        Self {
+            name: name.into(),
+            pin,
+            inverted,
+            action,
+            debounce_samples: None,
+            debounce_interval_ms: None,
+            max_volume: None,
+            stuck_after_s: None,
+        }
+    }
+
+    /// Return the debounce parameters of this button.
+    pub fn debounce(&self, global: &Debounce) -> Debounce {
+        Debounce {
+            samples: self.debounce_samples.unwrap_or(global.samples),
+            interval_ms: self.debounce_interval_ms.unwrap_or(global.interval_ms),
+        }
+    }
+
+    /// Return the time after which a held button counts as stuck.
+    ///
+    /// Band buttons stay pressed while the band is selected, so only other
+    /// buttons are watched by default, for a minute.
+    pub fn stuck_after(&self) -> Option<Duration> {
+        let seconds = match self.stuck_after_s {
+            Some(seconds) => seconds,
+            None if self.band().is_some() => 0,
+            None => 60,
+        };
+        Some(Duration::from_secs(seconds)).filter(|duration| !duration.is_zero())
+    }
+
+    /// Return the playlist if this is a band button with a playlist.
+    pub fn playlist(&self) -> Option<&str> {
+        match &self.action {
+            ButtonAction::Playlist(playlist) => Some(playlist),
+            _ => None,
+        }
+    }
+
+    /// Return the band if this is a band button. Buttons with a playlist
+    /// select a band named after the button.
+    pub fn band(&self) -> Option<&str> {
+        match &self.action {
+            ButtonAction::Playlist(_) => Some(&self.name),
+            ButtonAction::Band(band) => Some(band),
+            _ => None,
+        }
+    }
+}
+
+/// What happens when a button is pressed.
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ButtonAction {
+    /// Select a band with a single playlist, named after the button: Play
+    /// the playlist (or the station selected with the tuning control) and
+    /// stop playback when the button is released.
+    Playlist(String),
+    /// Select a band of `[[bands]]`: Play the station selected with the
+    /// tuning control and stop playback when the button is released.
+    Band(String),
+    /// Stop playback.
+    Stop,
+    /// Toggle mute.
+    Mute,
+    /// Stop playback after the specified number of minutes. Pressing the
+    /// button again cancels the timer.
+    SleepTimer(u64),
+    /// Switch between the speakers and the line output.
+    LineOut,
+    /// Lower the volume for a while, e.g. when the doorbell rings.
+    Duck(Duck),
+    /// Pause or resume playback.
+    Pause,
+    /// Shut down the system.
+    Shutdown,
+}
+
+/// A band with its stations, selected with a band button.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct Band {
+    /// Name of the band, used in logs and to refer to the band.
+    pub name: String,
+    /// Stations (volumio playlists) in the order they appear on the dial.
+    /// Without a tuning control, the first one is played.
+    pub stations: Vec<String>,
+    /// Stations that are played instead of the first one at times of the
+    /// week, without a tuning control.
+    #[serde(default)]
+    pub programs: Vec<Program>,
+}
+
+impl Band {
+    /// Return the station that is played on the weekday at the minute after
+    /// midnight: The one of the first program that is on, or the first
+    /// station of the band.
+    pub fn station(&self, now: Option<(Weekday, u16)>) -> Option<&str> {
+        let program = now.and_then(|(weekday, minutes)| {
+            self.programs.iter().find(|program| program.is_on(weekday, minutes))
+        });
+        match program {
+            Some(program) => Some(&program.station),
+            None => self.stations.first().map(String::as_str),
+        }
+    }
+}
+
+/// A station that a band plays during a time range.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct Program {
+    /// Days on which the program starts. Every day if empty.
+    #[serde(default)]
+    pub days: Vec<Weekday>,
+    /// Local start time as HH:MM.
+    pub from: String,
+    /// Local end time as HH:MM, past midnight if before the start time.
+    pub to: String,
+    /// Station (volumio playlist) to play.
+    pub station: String,
+}
+
+impl Program {
+    /// Return whether the program is on at the weekday at the minute after
+    /// midnight.
+    pub fn is_on(&self, weekday: Weekday, minutes: u16) -> bool {
+        let (from, to) = match (parse_minutes(&self.from), parse_minutes(&self.to)) {
+            (Some(from), Some(to)) => (from, to),
+            _ => return false,
+        };
+        let starts_on = |weekday| self.days.is_empty() || self.days.contains(&weekday);
+        if from < to {
+            starts_on(weekday) && (from..to).contains(&minutes)
+        } else {
+            // Programs that end past midnight are still on in the early
+            // hours of the next day
+            (starts_on(weekday) && minutes >= from) || (starts_on(weekday.previous()) && minutes < to)
+        }
+    }
+}
+
+/// Name and settings of a station.
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct Station {
+    /// Name of the volumio playlist, which is used to refer to the station
+    /// in the band lists, the schedule and the history.
+    pub id: String,
+    /// Name that is shown on the display and in notifications, if not the
+    /// id.
+    pub name: Option<String>,
+    /// Volume in percent at full knob deflection while this station plays,
+    /// e.g. for a stream that is louder than the others. It is combined with
+    /// the maximum volume of the band.
+    pub max_volume: Option<u8>,
+    /// URL of a logo that is shown instead of the artwork of the stream.
+    pub artwork: Option<String>,
+    /// Override the time in milliseconds a stream may take to start.
+    pub stream_timeout_ms: Option<u64>,
+    /// Override the number of streams that are tried before giving up.
+    pub stream_attempts: Option<u32>,
+    /// Override the delay in milliseconds before trying the next stream.
+    pub stream_backoff_ms: Option<u64>,
+}
+
+impl Station {
+    /// Return the name that is shown for the station.
+    pub fn name(&self) -> &str {
+        self.name.as_deref().unwrap_or(&self.id)
+    }
+
+    /// Return the retry policy of the station's streams, with the overrides
+    /// of the station applied to the one of `[streams]`.
+    pub fn retry_policy(&self, streams: &Streams) -> RetryPolicy {
+        RetryPolicy {
+            timeout: Duration::from_millis(self.stream_timeout_ms.unwrap_or(streams.timeout_ms)),
+            attempts: self.stream_attempts.unwrap_or(streams.attempts),
+            backoff: Duration::from_millis(self.stream_backoff_ms.unwrap_or(streams.backoff_ms)),
+        }
+    }
+}
+
+/// How long streams may take to start, and how often the next one is tried,
+/// unless a station overrides it.
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(default, deny_unknown_fields)]
+pub struct Streams {
+    /// Time in milliseconds after which a stream that didn't start is
+    /// skipped for the next stream of the playlist.
+    pub timeout_ms: u64,
+    /// Number of streams of a playlist that are tried before giving up.
+    pub attempts: u32,
+    /// Delay in milliseconds before trying the next stream, doubled with
+    /// every further attempt. At most `MAX_STREAM_BACKOFF_MS`.
+    pub backoff_ms: u64,
+}
+
+/// Maximum delay in milliseconds before trying the next stream (an hour).
+pub const MAX_STREAM_BACKOFF_MS: u64 = 3_600_000;
+
+impl Streams {
+    /// Return the retry policy of streams of stations without overrides.
+    pub fn retry_policy(&self) -> RetryPolicy {
+        RetryPolicy {
+            timeout: Duration::from_millis(self.timeout_ms),
+            attempts: self.attempts,
+            backoff: Duration::from_millis(self.backoff_ms),
+        }
+    }
+}
+
+impl Default for Streams {
+    fn default() -> Self {
+        Self {
+            timeout_ms: 15000,
+            attempts: 3,
+            backoff_ms: 0,
+        }
+    }
+}
+
+/// How long the streams of a playlist may take to start, and how often the
+/// next one is tried.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// Time after which a stream that didn't start is given up.
+    pub timeout: Duration,
+    /// Number of streams that are tried before giving up the playlist.
+    pub attempts: u32,
+    /// Delay before trying the next stream, doubled with every attempt.
+    pub backoff: Duration,
+}
+
+impl RetryPolicy {
+    /// Return the delay before the next stream is tried after `attempts`
+    /// streams didn't start.
+    pub fn delay(&self, attempts: u32) -> Duration {
+        self.backoff.saturating_mul(2u32.pow(attempts.clamp(1, 10) - 1))
+    }
+}
+
+/// Lowering of the volume for a while.
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct Duck {
+    /// Maximum volume in percent while ducked.
+    pub volume: u8,
+    /// How long the volume is lowered. Pressing the button again restarts
+    /// the time.
+    pub duration_s: u64,
+}
+
+/// Debounce parameters.
+///
+/// A button is only considered pressed or released once its pin has read the
+/// same level for `samples` consecutive samples, taken every `interval_ms`.
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(default, deny_unknown_fields)]
+pub struct Debounce {
+    /// Number of consecutive samples (1-32).
+    pub samples: u8,
+    /// Interval between two samples in milliseconds.
+    pub interval_ms: u64,
+}
+
+impl Default for Debounce {
+    fn default() -> Self {
+        Self {
+            samples: 16,
+            interval_ms: 10,
+        }
+    }
+}
+
+/// Protection of the "shutdown" button against flaky contacts, which would
+/// halt the system while the SD card is written.
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(default, deny_unknown_fields)]
+pub struct ShutdownConfig {
+    /// Number of seconds the button must have been released (or the daemon
+    /// running) before pressing it shuts down the system. Earlier presses
+    /// only stop playback.
+    pub min_on_s: u64,
+    /// Only stop playback instead of shutting down.
+    pub stop_only: bool,
+}
+
+impl ShutdownConfig {
+    /// Return whether pressing the button shuts down the system, after it
+    /// was released for the duration.
+    pub fn halts(&self, on_for: Duration) -> bool {
+        !self.stop_only && on_for >= Duration::from_secs(self.min_on_s)
+    }
+}
+
+impl Default for ShutdownConfig {
+    fn default() -> Self {
+        Self {
+            min_on_s: 10,
+            stop_only: false,
+        }
+    }
+}
+
+/// The lock that keeps small children from changing the band and turning up
+/// the volume. It is engaged through the control socket or by holding down the
+/// lock button.
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(default, deny_unknown_fields)]
+pub struct LockConfig {
+    /// Volume in percent that is not exceeded while locked.
+    pub max_volume: u8,
+    /// Button that locks and unlocks the radio when it's held down.
+    pub button: Option<String>,
+    /// Number of seconds the button must be held down.
+    pub hold_s: u64,
+}
+
+impl Default for LockConfig {
+    fn default() -> Self {
+        Self {
+            max_volume: 30,
+            button: None,
+            hold_s: 5,
+        }
+    }
+}
+
+/// How the GPIO pins are accessed.
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(default, deny_unknown_fields)]
+pub struct GpioConfig {
+    pub backend: GpioBackend,
+    /// GPIO character device whose line offsets are used as pin numbers
+    /// (`cdev` backend only).
+    pub chip: String,
+}
+
+impl Default for GpioConfig {
+    fn default() -> Self {
+        Self {
+            backend: GpioBackend::default(),
+            chip: "/dev/gpiochip0".into(),
+        }
+    }
+}
+
+/// The way the GPIO pins are accessed.
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum GpioBackend {
+    /// Memory-mapped registers of the Raspberry Pi, with BCM pin numbers.
+    #[default]
+    Rppal,
+    /// GPIO character device of the kernel, for other boards.
+    Cdev,
+}
+
+fn default_buttons() -> Vec<Button> {
+    vec![
+        Button::new("aus", 17, true, ButtonAction::Shutdown),
+        Button::new("tonabnehmer", 27, false, ButtonAction::Playlist("jazz".into())),
+        Button::new("ukw", 22, false, ButtonAction::Playlist("mellow".into())),
+        Button::new("kurz", 5, false, ButtonAction::Playlist("world".into())),
+        Button::new("mittel", 6, false, ButtonAction::Playlist("rockblues".into())),
+        Button::new("lang", 13, false, ButtonAction::Playlist("progrock".into())),
+    ]
+}
+
+/// An input channel of the ADC.
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Channel {
+    A0,
+    A1,
+    A2,
+    A3,
+    #[serde(rename = "A0-A1")]
+    A0A1,
+    #[serde(rename = "A0-A3")]
+    A0A3,
+    #[serde(rename = "A1-A3")]
+    A1A3,
+    #[serde(rename = "A2-A3")]
+    A2A3,
+}
+
+impl fmt::Display for Channel {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Channel::A0 => "A0",
+            Channel::A1 => "A1",
+            Channel::A2 => "A2",
+            Channel::A3 => "A3",
+            Channel::A0A1 => "A0-A1",
+            Channel::A0A3 => "A0-A3",
+            Channel::A1A3 => "A1-A3",
+            Channel::A2A3 => "A2-A3",
+        };
+        f.write_str(name)
+    }
+}
+
+impl FromStr for Channel {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let channels = [
+            Channel::A0,
+            Channel::A1,
+            Channel::A2,
+            Channel::A3,
+            Channel::A0A1,
+            Channel::A0A3,
+            Channel::A1A3,
+            Channel::A2A3,
+        ];
+        channels
+            .iter()
+            .copied()
+            .find(|channel| channel.to_string() == s)
+            .ok_or_else(|| format!("Unknown ADC channel \"{}\"", s))
+    }
+}
+
+/// What an analog control is used for.
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    /// Controls the playback volume.
+    Volume,
+    /// Selects the station within the current band.
+    Tuning,
+    /// Controls the balance between the left and the right channel.
+    Balance,
+    /// The value is only logged.
+    Monitor,
+}
+
+/// Return the name of a channel, prefixed with the name of the ADC unless
+/// it's the default one, e.g. `A0` or `power/A0`.
+pub fn channel_name(adc: Option<&str>, channel: Channel) -> String {
+    match adc {
+        Some(adc) => format!("{}/{}", adc, channel),
+        None => channel.to_string(),
+    }
+}
+
+/// An additional ADS1115 ADC.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct AdcDevice {
+    /// Name that analog controls refer to.
+    pub name: String,
+    /// I2C address (0x48-0x4b, depending on the ADDR pin).
+    #[serde(default = "default_adc_address")]
+    pub address: u8,
+    /// I2C bus device, if not the one given by `--i2c`.
+    pub i2c: Option<String>,
+}
+
+fn default_adc_address() -> u8 {
+    0x48
+}
+
+/// A potentiometer connected to an ADC channel.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct AnalogControl {
+    /// Name of the ADC, if not the default one.
+    pub adc: Option<String>,
+    pub channel: Channel,
+    pub role: Role,
+    /// Calibration table with `(angle, value)` pairs, sorted by angle.
+    #[serde(default = "default_lookup_table")]
+    pub lookup_table: Vec<(u16, u16)>,
+    /// Width of the dead zone around the centre in percent of the range
+    /// (balance controls only).
+    #[serde(default)]
+    pub center_dead_zone: u8,
+}
+
+fn default_lookup_table() -> Vec<(u16, u16)> {
+    LOOKUP_TABLE_VOL.to_vec()
+}
+
+impl AnalogControl {
+    fn new(channel: Channel, role: Role) -> Self {
+        Self {
+            adc: None,
+            channel,
+            role,
+            lookup_table: default_lookup_table(),
+            center_dead_zone: 0,
+        }
+    }
+}
+
+/// A quadrature rotary encoder with an optional push switch.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct Encoder {
+    /// BCM number of the GPIO pin connected to output A.
+    pub pin_a: u8,
+    /// BCM number of the GPIO pin connected to output B.
+    pub pin_b: u8,
+    /// BCM number of the GPIO pin connected to the push switch, which
+    /// toggles mute.
+    pub switch_pin: Option<u8>,
+    pub role: EncoderRole,
+    /// Number of quadrature steps per detent (1-4).
+    #[serde(default = "default_steps_per_detent")]
+    pub steps_per_detent: u8,
+}
+
+fn default_steps_per_detent() -> u8 {
+    4
+}
+
+/// An output connected to a GPIO pin, e.g. the relay or transistor driving
+/// the dial lamp.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct Output {
+    /// BCM number of the GPIO pin.
+    pub pin: u8,
+    /// Whether the output is active low.
+    #[serde(default)]
+    pub inverted: bool,
+    /// When the output is switched on.
+    #[serde(default)]
+    pub when: OutputTrigger,
+    /// Dim the output with the volume using software PWM. Don't use this
+    /// with relays.
+    #[serde(default)]
+    pub dim_with_volume: bool,
+    /// Brightness in percent at volume 0 when dimmed with the volume.
+    #[serde(default)]
+    pub min_brightness: u8,
+    /// Blink the output when a playlist could not be started.
+    #[serde(default)]
+    pub blink_on_error: bool,
+    /// Dim the output with the ambient light using software PWM.
+    #[serde(default)]
+    pub dim_with_light: bool,
+}
+
+/// When an output is switched on.
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputTrigger {
+    /// While a playlist is playing.
+    #[default]
+    Playing,
+    /// While the daemon is running.
+    Always,
+}
+
+/// A status LED that shows the state of the daemon with blink patterns.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct Led {
+    /// BCM number of the GPIO pin.
+    pub pin: u8,
+    /// Whether the LED is active low.
+    #[serde(default)]
+    pub inverted: bool,
+    /// Blink patterns overriding the default patterns.
+    #[serde(default)]
+    pub patterns: LedPatterns,
+}
+
+/// Blink patterns of a status LED by state.
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(deny_unknown_fields)]
+pub struct LedPatterns {
+    pub booting: Option<Pattern>,
+    pub ready: Option<Pattern>,
+    pub buffering: Option<Pattern>,
+    pub playing: Option<Pattern>,
+    pub error: Option<Pattern>,
+}
+
+impl Led {
+    /// Return the blink pattern for the specified state.
+    pub fn pattern(&self, state: DaemonState) -> Pattern {
+        let pattern = match state {
+            DaemonState::Booting => self.patterns.booting,
+            DaemonState::Ready => self.patterns.ready,
+            DaemonState::Buffering => self.patterns.buffering,
+            DaemonState::Playing => self.patterns.playing,
+            DaemonState::Error => self.patterns.error,
+        };
+        pattern.unwrap_or_else(|| state.default_pattern())
+    }
+}
+
+/// A display showing the station, the track title, the volume and a clock.
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(tag = "type", rename_all = "lowercase", deny_unknown_fields)]
+pub enum DisplayConfig {
+    /// 128x64 SSD1306 OLED on the ADC's I2C bus.
+    Ssd1306 {
+        #[serde(default = "default_ssd1306_address")]
+        address: u8,
+    },
+    /// HD44780 character LCD with a PCF8574 I2C backpack on the ADC's bus.
+    Hd44780 {
+        #[serde(default = "default_hd44780_address")]
+        address: u8,
+        /// Number of characters per line.
+        #[serde(default = "default_hd44780_columns")]
+        columns: usize,
+        /// What is shown on every line (up to 4 lines).
+        #[serde(default = "default_hd44780_layout")]
+        layout: Vec<LcdLine>,
+    },
+    /// Waveshare 2.9" e-paper panel on SPI0.
+    Epaper {
+        /// BCM numbers of the GPIO pins connected to DC, RST and BUSY.
+        dc_pin: u8,
+        reset_pin: u8,
+        busy_pin: u8,
+        /// Minimum time between two refreshes in seconds.
+        #[serde(default = "default_epaper_min_refresh_s")]
+        min_refresh_s: u64,
+        /// Directory in which artwork is cached. Artwork is only shown if
+        /// set.
+        artwork_cache: Option<String>,
+    },
+}
+
+fn default_ssd1306_address() -> u8 {
+    0x3c
+}
+
+fn default_hd44780_address() -> u8 {
+    0x27
+}
+
+fn default_hd44780_columns() -> usize {
+    16
+}
+
+fn default_epaper_min_refresh_s() -> u64 {
+    60
+}
+
+fn default_hd44780_layout() -> Vec<LcdLine> {
+    vec![LcdLine::Station, LcdLine::Title]
+}
+
+/// An ALSA mixer control.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct Alsa {
+    /// Index or name of the sound card (see `aplay -l`).
+    #[serde(default = "default_alsa_card")]
+    pub card: String,
+    /// Name of the mixer control (see `amixer -c <card> scontrols`).
+    pub mixer: String,
+    /// Sound file that is played on startup to check the audio output.
+    pub self_test: Option<String>,
+    /// Cards that are used instead of `card` while they are plugged in, in
+    /// order of priority.
+    #[serde(default)]
+    pub preferred: Vec<AlsaCard>,
+}
+
+impl Alsa {
+    /// Return the card that is used if no preferred card is plugged in.
+    pub fn default_card(&self) -> AlsaCard {
+        AlsaCard {
+            card: self.card.clone(),
+            mixer: self.mixer.clone(),
+        }
+    }
+}
+
+/// A sound card and the mixer control that sets its volume.
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct AlsaCard {
+    /// Index or name of the sound card.
+    pub card: String,
+    /// Name of the mixer control.
+    pub mixer: String,
+}
+
+fn default_alsa_card() -> String {
+    "0".into()
+}
+
+/// A battery (e.g. of a UPS hat) whose voltage is connected to the ADC.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct Battery {
+    /// Name of the ADC, if not the default one.
+    pub adc: Option<String>,
+    pub channel: Channel,
+    /// Ratio of the voltage divider between the battery and the ADC input.
+    #[serde(default = "default_battery_divider")]
+    pub divider: f64,
+    /// A warning is logged below this voltage.
+    pub warn_voltage: f64,
+    /// The radio is shut down below this voltage.
+    pub shutdown_voltage: f64,
+}
+
+fn default_battery_divider() -> f64 {
+    1.0
+}
+
+/// Monitoring of the CPU temperature and the supply voltage.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct Health {
+    /// A warning is logged above this CPU temperature in degrees Celsius.
+    #[serde(default = "default_health_warn_temperature")]
+    pub warn_temperature: f64,
+    /// Whether to poll the analog controls less often while the CPU is
+    /// throttled or too hot.
+    #[serde(default)]
+    pub slow_down: bool,
+}
+
+fn default_health_warn_temperature() -> f64 {
+    75.0
+}
+
+/// A fan driven with software PWM according to a temperature.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct Fan {
+    /// BCM number of the GPIO pin driving the fan.
+    pub pin: u8,
+    /// Whether the fan runs while the pin is low.
+    #[serde(default)]
+    pub inverted: bool,
+    /// File with the temperature in millidegrees Celsius, e.g. a thermal
+    /// zone or a hwmon sensor.
+    #[serde(default = "default_fan_sensor")]
+    pub sensor: String,
+    /// `(temperature, duty cycle)` pairs with the temperature in degrees
+    /// Celsius and the duty cycle in percent, sorted by temperature.
+    #[serde(default = "default_fan_curve")]
+    pub curve: Vec<(f64, u8)>,
+}
+
+fn default_fan_sensor() -> String {
+    "/sys/class/thermal/thermal_zone0/temp".into()
+}
+
+fn default_fan_curve() -> Vec<(f64, u8)> {
+    vec![(50.0, 0), (55.0, 40), (70.0, 100)]
+}
+
+/// An ambient light sensor on the I2C bus.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct AmbientLight {
+    pub sensor: LightSensorType,
+    /// I2C address, if not the default one of the sensor.
+    pub address: Option<u8>,
+    /// Brightness in percent in the dark.
+    #[serde(default = "default_ambient_min_brightness")]
+    pub min_brightness: u8,
+    /// Brightness in percent in daylight.
+    #[serde(default = "default_ambient_max_brightness")]
+    pub max_brightness: u8,
+    /// Illuminance in lux up to which the minimum brightness is used.
+    #[serde(default = "default_ambient_dark_lux")]
+    pub dark_lux: f64,
+    /// Illuminance in lux from which the maximum brightness is used.
+    #[serde(default = "default_ambient_bright_lux")]
+    pub bright_lux: f64,
+}
+
+fn default_ambient_min_brightness() -> u8 {
+    10
+}
+
+fn default_ambient_max_brightness() -> u8 {
+    100
+}
+
+fn default_ambient_dark_lux() -> f64 {
+    1.0
+}
+
+fn default_ambient_bright_lux() -> f64 {
+    300.0
+}
+
+impl AmbientLight {
+    /// Return the I2C address of the sensor.
+    pub fn address(&self) -> u8 {
+        self.address.unwrap_or(match self.sensor {
+            LightSensorType::Bh1750 => 0x23,
+            LightSensorType::Tsl2561 => 0x39,
+        })
+    }
+}
+
+/// A supported ambient light sensor.
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum LightSensorType {
+    Bh1750,
+    Tsl2561,
+}
+
+/// A soft power latch: A momentary power button switches the power on, and
+/// the daemon keeps it on by driving the hold pin.
+///
+/// The daemon never releases the latch itself, the gpio-poweroff overlay does
+/// that once the system is halted.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct PowerLatch {
+    /// BCM number of the GPIO pin that keeps the power on. It is driven high
+    /// at startup and stays high after the daemon exits.
+    pub hold_pin: u8,
+    /// Whether the power is kept on by driving the pin low.
+    #[serde(default)]
+    pub inverted: bool,
+    /// BCM number of the GPIO pin on which the latch requests to switch the
+    /// power off, e.g. when the power button is pressed again. The pin is
+    /// pulled up, a request pulls it low. A request always shuts the system
+    /// down.
+    pub request_pin: Option<u8>,
+}
+
+/// Notifications about failures: crashed threads, a station that is down,
+/// a low battery and undervoltage.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct Notify {
+    /// Minutes a station must be down before a notification is sent.
+    #[serde(default = "default_notify_station_down_minutes")]
+    pub station_down_minutes: u64,
+    /// Where notifications are sent.
+    pub target: NotifyTarget,
+}
+
+fn default_notify_station_down_minutes() -> u64 {
+    10
+}
+
+/// A service that notifications are sent to.
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(tag = "type", rename_all = "lowercase", deny_unknown_fields)]
+pub enum NotifyTarget {
+    /// Generic webhook, receiving `{"text": "..."}` as a POST request.
+    Webhook { url: String },
+    /// ntfy.sh or a self-hosted ntfy server.
+    Ntfy {
+        #[serde(default = "default_ntfy_server")]
+        server: String,
+        topic: String,
+    },
+    /// Telegram bot.
+    Telegram { bot_token: String, chat_id: String },
+}
+
+fn default_ntfy_server() -> String {
+    "https://ntfy.sh".into()
+}
+
+/// Weather forecast from Open-Meteo.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct WeatherConfig {
+    /// Latitude of the location in degrees.
+    pub latitude: f64,
+    /// Longitude of the location in degrees.
+    pub longitude: f64,
+    /// Minutes between two updates of the forecast.
+    #[serde(default = "default_weather_interval_minutes")]
+    pub interval_minutes: u64,
+}
+
+fn default_weather_interval_minutes() -> u64 {
+    30
+}
+
+/// A DS3231 real-time clock on the ADC's I2C bus.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct Rtc {
+    /// I2C address of the clock.
+    #[serde(default = "default_rtc_address")]
+    pub address: u8,
+}
+
+fn default_rtc_address() -> u8 {
+    0x68
+}
+
+/// Something that is done automatically at a time of the week.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct ScheduleEntry {
+    /// Days on which the entry applies. Every day if empty.
+    #[serde(default)]
+    pub days: Vec<Weekday>,
+    /// Local time as HH:MM.
+    pub time: String,
+    pub action: ScheduledAction,
+    /// Playlist to play, for the "play" action.
+    pub playlist: Option<String>,
+    /// Volume in percent to play at, for the "play" action.
+    pub volume: Option<u8>,
+}
+
+impl ScheduleEntry {
+    /// Return the time of the entry in minutes after midnight.
+    pub fn minutes(&self) -> Option<u16> {
+        parse_minutes(&self.time)
+    }
+
+    /// Return whether the entry is due on the weekday at the minute after
+    /// midnight.
+    pub fn is_due(&self, weekday: Weekday, minutes: u16) -> bool {
+        (self.days.is_empty() || self.days.contains(&weekday)) && self.minutes() == Some(minutes)
+    }
+}
+
+/// Parse a time as HH:MM to minutes after midnight.
+fn parse_minutes(time: &str) -> Option<u16> {
+    let (hours, minutes) = time.split_once(':')?;
+    let hours: u16 = hours.parse().ok().filter(|hours| *hours < 24)?;
+    let minutes: u16 = minutes.parse().ok().filter(|minutes| *minutes < 60)?;
+    Some(hours * 60 + minutes)
+}
+
+/// A day of the week.
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Weekday {
+    Mon,
+    Tue,
+    Wed,
+    Thu,
+    Fri,
+    Sat,
+    Sun,
+}
+
+impl Weekday {
+    /// Return the ISO 8601 number of the weekday (1 is Monday).
+    pub fn iso(self) -> u8 {
+        self as u8 + 1
+    }
+
+    /// Return the weekday with the ISO 8601 number (1 is Monday).
+    pub fn from_iso(number: u8) -> Option<Self> {
+        let weekdays = [Self::Mon, Self::Tue, Self::Wed, Self::Thu, Self::Fri, Self::Sat, Self::Sun];
+        weekdays.get(usize::from(number).checked_sub(1)?).copied()
+    }
+
+    /// Return the day before.
+    pub fn previous(self) -> Self {
+        Self::from_iso((self.iso() + 5) % 7 + 1).unwrap()
+    }
+}
+
+/// What a schedule entry does.
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ScheduledAction {
+    /// Play the playlist.
+    Play,
+    /// Stop playback.
+    Stop,
+    /// Stop playback and shut down the system.
+    Shutdown,
+}
+
+/// Power saving while nothing is playing and no control is used.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct Idle {
+    /// Minutes without playback and input after which the display and the
+    /// outputs are switched off.
+    pub after_minutes: u64,
+    /// Whether to shut down the system instead.
+    #[serde(default)]
+    pub shutdown: bool,
+}
+
+/// Batching of the writes of the state, history and status files, to spare
+/// the SD card of a radio that runs all the time.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct LowWrite {
+    /// Minimum number of minutes between two writes of a file.
+    #[serde(default = "default_low_write_interval_minutes")]
+    pub interval_minutes: u64,
+}
+
+fn default_low_write_interval_minutes() -> u64 {
+    5
+}
+
+/// A headphone jack with a detect switch connected to a GPIO pin.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct Headphones {
+    /// BCM number of the GPIO pin connected to the detect switch. The pin is
+    /// pulled up, so by default headphones are plugged in when it is low.
+    pub detect_pin: u8,
+    /// Whether headphones are plugged in when the pin is high.
+    #[serde(default)]
+    pub inverted: bool,
+    /// BCM number of the GPIO pin that enables the speaker amplifier. It is
+    /// driven low while headphones are plugged in.
+    pub amp_enable_pin: Option<u8>,
+    /// Volume in percent at full knob deflection while headphones are
+    /// plugged in. Lower volumes are scaled accordingly.
+    #[serde(default = "default_headphones_max_volume")]
+    pub max_volume: u8,
+    /// Shell command that switches the output to the headphones, e.g. an
+    /// `amixer` call that changes the ALSA route.
+    pub headphones_command: Option<String>,
+    /// Shell command that switches the output back to the speakers.
+    pub speakers_command: Option<String>,
+}
+
+fn default_headphones_max_volume() -> u8 {
+    60
+}
+
+/// A line output, e.g. on the back of the radio to connect a bigger
+/// amplifier, that is switched on instead of the speakers with a button.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct LineOut {
+    /// Shell command that switches the output to the line output, e.g. an
+    /// `amixer` call that changes the ALSA route and mutes the speaker
+    /// amplifier.
+    pub line_out_command: String,
+    /// Shell command that switches the output back to the speakers.
+    pub speakers_command: String,
+}
+
+/// A tuning eye tube, driven through a PWM output with a low-pass filter or
+/// an MCP4725 DAC that controls the grid voltage.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct MagicEye {
+    /// BCM number of the GPIO pin used for software PWM.
+    pub pin: Option<u8>,
+    /// I2C address of the MCP4725 DAC on the ADC's bus.
+    pub mcp4725_address: Option<u8>,
+    /// What the eye shows.
+    pub source: MagicEyeSource,
+    /// Whether the eye closes at high output levels.
+    #[serde(default)]
+    pub inverted: bool,
+}
+
+/// What the magic eye shows.
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum MagicEyeSource {
+    /// How accurately the tuning dial points at a station.
+    Tuning,
+    /// Whether a playlist is playing without errors.
+    Playback,
+    /// The link quality of the Wi-Fi connection.
+    Wifi,
+}
+
+/// What a rotary encoder is used for.
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum EncoderRole {
+    /// Controls the playback volume.
+    Volume,
+    /// Selects the station within the current band.
+    Tuning,
+}
+
+/// An evdev input device whose keys trigger actions.
+///
+/// IR receivers that are handled by the kernel (rc-core, e.g. with the
+/// `gpio-ir` overlay) show up as evdev devices as well.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct EvdevDevice {
+    /// Path to the device, e.g. `/dev/input/by-path/platform-ir-receiver@12-event`.
+    pub device: String,
+    /// Actions by key code.
+    #[serde(default)]
+    pub keys: HashMap<String, KeyAction>,
+    /// Names of emulated buttons by key code.
+    ///
+    /// These keys behave like the piano keys of the radio: Pressing a band
+    /// key selects the band and releases the previously pressed one, pressing
+    /// it again releases it.
+    #[serde(default)]
+    pub buttons: HashMap<String, String>,
+}
+
+impl EvdevDevice {
+    /// Return the action for the specified key code.
+    pub fn action(&self, code: u16) -> Option<&KeyAction> {
+        self.keys.get(&code.to_string())
+    }
+
+    /// Return the name of the emulated button for the specified key code.
+    pub fn button(&self, code: u16) -> Option<&str> {
+        self.buttons.get(&code.to_string()).map(String::as_str)
+    }
+}
+
+/// An action that can be triggered with a key.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum KeyAction {
+    /// Select the band of the band button with the specified name.
+    Band(String),
+    VolumeUp,
+    VolumeDown,
+    Mute,
+    Stop,
+    /// Switch between the speakers and the line output.
+    LineOut,
+    Shutdown,
+}
+
+impl<'de> Deserialize<'de> for KeyAction {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let name = String::deserialize(deserializer)?;
+        Ok(match name.as_str() {
+            "volume_up" => KeyAction::VolumeUp,
+            "volume_down" => KeyAction::VolumeDown,
+            "mute" => KeyAction::Mute,
+            "stop" => KeyAction::Stop,
+            "line_out" => KeyAction::LineOut,
+            "shutdown" => KeyAction::Shutdown,
+            _ => KeyAction::Band(name),
+        })
+    }
+}
+
+/// Station selection with the tuning dial.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct Tuning {
+    /// Playlist that is played while the dial is between two stations.
+    pub static_playlist: Option<String>,
+    /// Width of the gap between two stations in percent of the dial range.
+    pub gap: u8,
+    /// Hysteresis in percent of the dial range.
+    pub hysteresis: u8,
+    /// Stations (volumio playlists) per band, in the order they appear on
+    /// the dial.
+    pub bands: HashMap<String, Vec<String>>,
+    /// The way the dial value is mapped to the stations.
+    pub mode: TuningMode,
+    /// Duration for which static is played in the seek mode, after the dial
+    /// came to rest.
+    pub seek_static_ms: u64,
+    /// Dial positions in percent of the stations per band, for the seek mode.
+    pub positions: HashMap<String, HashMap<String, u8>>,
+}
+
+/// The way the value of the tuning dial is mapped to the stations.
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum TuningMode {
+    /// The dial range is divided into equally wide slots, one per station.
+    #[default]
+    Slots,
+    /// Turning the dial plays static, then the station nearest to the dial is
+    /// locked onto.
+    Seek,
+}
+
+impl Default for Tuning {
+    fn default() -> Self {
+        Self {
+            static_playlist: None,
+            gap: 0,
+            hysteresis: 2,
+            bands: HashMap::new(),
+            mode: TuningMode::Slots,
+            seek_static_ms: 800,
+            positions: HashMap::new(),
+        }
+    }
+}
+
+impl Tuning {
+    /// Return the stations of the specified band.
+    pub fn stations(&self, band: &str) -> &[String] {
+        self.bands.get(band).map(Vec::as_slice).unwrap_or(&[])
+    }
+
+    /// Return the dial positions of the stations of the specified band, in
+    /// the order of the stations.
+    pub fn positions(&self, band: &str) -> Vec<u8> {
+        let positions = self.positions.get(band);
+        self.stations(band)
+            .iter()
+            .filter_map(|station| positions?.get(station).copied())
+            .collect()
+    }
+}
+
+/// Simulated shortwave ("DX") reception: While the band is selected,
+/// recordings are played with fading and noise instead of a playlist.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct Dx {
+    /// The band on which the recordings are received.
+    pub band: String,
+    /// Recordings (files, directories with recordings or URLs, e.g. of
+    /// archive.org) that are played in random order.
+    pub recordings: Vec<String>,
+    /// Amplitude of the noise, between 0 and 1.
+    #[serde(default = "default_dx_noise")]
+    pub noise: f64,
+    /// Depth of the fading, between 0 and 1.
+    #[serde(default = "default_dx_fading")]
+    pub fading: f64,
+    /// ALSA device that the recordings are played on.
+    #[serde(default = "default_dx_device")]
+    pub device: String,
+}
+
+fn default_dx_noise() -> f64 {
+    0.1
+}
+
+fn default_dx_fading() -> f64 {
+    0.6
+}
+
+fn default_dx_device() -> String {
+    "default".into()
+}
+
+impl Config {
+    /// Parse and validate the configuration.
+    pub fn parse(contents: &str) -> Result<Self, String> {
+        let config: Config = toml::from_str(contents).map_err(|e| format!("Parse error: {}", e))?;
+        config.validate()?;
+        config.validate_profiles(contents, "")?;
+        Ok(config)
+    }
+
+    /// Parse and validate the configuration, with the top-level keys and
+    /// sections of `overrides` replacing those of `contents`.
+    pub fn parse_with_overrides(contents: &str, overrides: &str) -> Result<Self, String> {
+        Self::merge(contents, overrides, None)
+    }
+
+    /// Parse and validate the configuration like `parse_with_overrides`, with
+    /// the top-level keys and sections of the profile replacing the others.
+    pub fn parse_with_profile(contents: &str, overrides: &str, profile: &str) -> Result<Self, String> {
+        Self::merge(contents, overrides, Some(profile))
+    }
+
+    fn merge(contents: &str, overrides: &str, profile: Option<&str>) -> Result<Self, String> {
+        let mut table: toml::value::Table = toml::from_str(contents).map_err(|e| format!("Parse error: {}", e))?;
+        let override_table: toml::value::Table =
+            toml::from_str(overrides).map_err(|e| format!("Parse error in overrides: {}", e))?;
+        table.extend(override_table);
+        if let Some(profile) = profile {
+            let profile_table = table
+                .get("profiles")
+                .and_then(|profiles| profiles.get(profile))
+                .and_then(|profile| profile.as_table())
+                .cloned()
+                .ok_or_else(|| format!("Unknown profile \"{}\"", profile))?;
+            table.extend(profile_table);
+        }
+        let config: Config = toml::Value::Table(table)
+            .try_into()
+            .map_err(|e| format!("Parse error: {}", e))?;
+        config.validate()?;
+        if profile.is_none() {
+            config.validate_profiles(contents, overrides)?;
+        }
+        Ok(config)
+    }
+
+    /// Check that the configuration is valid with every profile, so that
+    /// errors show up before a profile is selected.
+    fn validate_profiles(&self, contents: &str, overrides: &str) -> Result<(), String> {
+        for profile in self.profiles.keys() {
+            Self::merge(contents, overrides, Some(profile)).map_err(|e| format!("Profile \"{}\": {}", profile, e))?;
+        }
+        Ok(())
+    }
+
+    /// Return the button with the specified name.
+    pub fn button(&self, name: &str) -> Option<&Button> {
+        self.buttons.iter().find(|button| button.name == name)
+    }
+
+    /// Return the band with the specified name, if it's one of `[[bands]]`.
+    pub fn band(&self, name: &str) -> Option<&Band> {
+        self.bands.iter().find(|band| band.name == name)
+    }
+
+    /// Return whether a band with the specified name exists, either one of
+    /// `[[bands]]` or one of a button with a playlist.
+    fn is_band(&self, name: &str) -> bool {
+        self.band(name).is_some() || self.button(name).and_then(Button::playlist).is_some()
+    }
+
+    /// Return whether stations are selected with an analog control or the
+    /// encoder.
+    pub fn has_tuning_control(&self) -> bool {
+        self.analog.as_deref().unwrap_or(&[]).iter().any(|c| c.role == Role::Tuning)
+            || self.encoder.as_ref().is_some_and(|encoder| encoder.role == EncoderRole::Tuning)
+    }
+
+    /// Return the tuning configuration, including the stations of
+    /// `[[bands]]` if they are selected with a tuning control.
+    pub fn tuning(&self) -> Tuning {
+        let mut tuning = self.tuning.clone();
+        if self.has_tuning_control() {
+            for band in self.bands.iter().filter(|band| !self.is_dx_band(&band.name)) {
+                tuning.bands.insert(band.name.clone(), band.stations.clone());
+            }
+        }
+        tuning
+    }
+
+    /// Return whether recordings are received on the band instead of its
+    /// stations.
+    pub fn is_dx_band(&self, name: &str) -> bool {
+        self.dx.as_ref().is_some_and(|dx| dx.band == name)
+    }
+
+    /// Return the playlist that is played when a band is selected on the
+    /// weekday at the minute after midnight, or `None` if the tuning control
+    /// selects the station or it's the DX band.
+    pub fn band_playlist(&self, name: &str, now: Option<(Weekday, u16)>) -> Option<&str> {
+        match self.band(name) {
+            _ if self.is_dx_band(name) => None,
+            Some(_) if self.has_tuning_control() => None,
+            Some(band) => band.station(now),
+            None if !self.tuning.stations(name).is_empty() => None,
+            None => self.buttons.iter().find(|button| button.name == name)?.playlist(),
+        }
+    }
+
+    /// Return all stations of the bands, the schedule, the buttons and
+    /// `[[stations]]`, without duplicates.
+    pub fn all_stations(&self) -> Vec<&str> {
+        let bands = self.bands.iter().filter(|band| !self.is_dx_band(&band.name));
+        let band_stations =
+            bands.flat_map(|band| band.stations.iter().chain(band.programs.iter().map(|program| &program.station)));
+        let all = band_stations
+            .chain(self.tuning.bands.values().flatten())
+            .map(String::as_str)
+            .chain(self.buttons.iter().filter_map(Button::playlist))
+            .chain(self.schedule.iter().filter_map(|entry| entry.playlist.as_deref()))
+            .chain(self.stations.iter().map(|station| station.id.as_str()));
+        let mut stations = vec![];
+        for station in all {
+            if !stations.contains(&station) {
+                stations.push(station);
+            }
+        }
+        stations
+    }
+
+    /// Return the maximum volume of a band, which is set on its button.
+    pub fn band_max_volume(&self, name: &str) -> Option<u8> {
+        self.buttons.iter().find(|button| button.band() == Some(name))?.max_volume
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        for (i, button) in self.buttons.iter().enumerate() {
+            if self.buttons[..i].iter().any(|other| other.name == button.name) {
+                return Err(format!("Duplicate button name \"{}\"", button.name));
+            }
+            if self.buttons[..i].iter().any(|other| other.pin == button.pin) {
+                return Err(format!("GPIO pin {} is used by more than one button", button.pin));
+            }
+            let debounce = button.debounce(&self.debounce);
+            if !(1..=32).contains(&debounce.samples) {
+                return Err(format!("Debounce samples of button \"{}\" must be between 1 and 32", button.name));
+            }
+            if debounce.interval_ms == 0 {
+                return Err(format!("Debounce interval of button \"{}\" must not be 0", button.name));
+            }
+            if button.max_volume.is_some() && button.band().is_none() {
+                return Err(format!("Only band buttons can have a maximum volume (button \"{}\")", button.name));
+            }
+            if let ButtonAction::Band(band) = &button.action {
+                if self.band(band).is_none() {
+                    return Err(format!("Unknown band \"{}\" of button \"{}\"", band, button.name));
+                }
+            }
+            if button.action == ButtonAction::LineOut && self.line_out.is_none() {
+                return Err(format!("Button \"{}\" switches to the line output, which requires [line_out]", button.name));
+            }
+            if let ButtonAction::Duck(duck) = &button.action {
+                if duck.volume > 100 || duck.duration_s == 0 {
+                    return Err(format!(
+                        "Button \"{}\" must duck to a percentage for more than 0 seconds",
+                        button.name
+                    ));
+                }
+            }
+            if button.max_volume.is_some_and(|max_volume| max_volume > 100) {
+                return Err(format!("Maximum volume of button \"{}\" must be a percentage", button.name));
+            }
+        }
+
+        for (i, output) in self.outputs.iter().enumerate() {
+            if self.buttons.iter().any(|button| button.pin == output.pin)
+                || self.outputs[..i].iter().any(|other| other.pin == output.pin)
+            {
+                return Err(format!("GPIO pin {} of output is already in use", output.pin));
+            }
+            if output.min_brightness > 100 {
+                return Err(format!("Minimum brightness of output on GPIO pin {} must be a percentage", output.pin));
+            }
+        }
+
+        for (i, led) in self.leds.iter().enumerate() {
+            if self.buttons.iter().any(|button| button.pin == led.pin)
+                || self.outputs.iter().any(|output| output.pin == led.pin)
+                || self.leds[..i].iter().any(|other| other.pin == led.pin)
+            {
+                return Err(format!("GPIO pin {} of LED is already in use", led.pin));
+            }
+        }
+
+        if let Some(DisplayConfig::Hd44780 { columns, layout, .. }) = &self.display {
+            if !(1..=40).contains(columns) || !(1..=4).contains(&layout.len()) {
+                return Err("HD44780 displays have 1-40 columns and 1-4 lines".into());
+            }
+        }
+
+        if let Some(headphones) = &self.headphones {
+            if headphones.max_volume > 100 {
+                return Err("Maximum headphone volume must be a percentage".into());
+            }
+        }
+
+        let controls = self.analog.as_deref().unwrap_or(&[]);
+        if let Some(eye) = &self.magic_eye {
+            if eye.pin.is_some() == eye.mcp4725_address.is_some() {
+                return Err("The magic eye needs either a GPIO pin or an MCP4725 address".into());
+            }
+            if let Some(pin) = eye.pin {
+                if self.buttons.iter().any(|button| button.pin == pin)
+                    || self.outputs.iter().any(|output| output.pin == pin)
+                    || self.leds.iter().any(|led| led.pin == pin)
+                {
+                    return Err(format!("GPIO pin {} of magic eye is already in use", pin));
+                }
+            }
+            if eye.source == MagicEyeSource::Tuning && !controls.iter().any(|c| c.role == Role::Tuning) {
+                return Err("A magic eye showing the tuning requires an analog control with the tuning role".into());
+            }
+        }
+        for role in &[Role::Volume, Role::Tuning, Role::Balance] {
+            if controls.iter().filter(|c| c.role == *role).count() > 1 {
+                return Err(format!("Only one analog control may have the {:?} role", role));
+            }
+        }
+        let mut adc_names: Vec<&str> = vec![];
+        let mut adc_addresses: Vec<(Option<&str>, u8)> = vec![];
+        for adc in &self.adcs {
+            if !(0x48..=0x4b).contains(&adc.address) {
+                return Err(format!("Address 0x{:02x} of ADC \"{}\" must be 0x48-0x4b", adc.address, adc.name));
+            }
+            if adc.name.is_empty() || adc.name.contains('/') {
+                return Err(format!("Invalid ADC name \"{}\"", adc.name));
+            }
+            if adc_names.contains(&adc.name.as_str()) {
+                return Err(format!("Duplicate ADC \"{}\"", adc.name));
+            }
+            if adc_addresses.contains(&(adc.i2c.as_deref(), adc.address)) {
+                return Err(format!("Address 0x{:02x} of ADC \"{}\" is already in use", adc.address, adc.name));
+            }
+            adc_names.push(&adc.name);
+            adc_addresses.push((adc.i2c.as_deref(), adc.address));
+        }
+        let used_adcs = controls
+            .iter()
+            .map(|c| (c.adc.as_deref(), c.channel))
+            .chain(self.battery.iter().map(|battery| (battery.adc.as_deref(), battery.channel)));
+        // An ADC at the address of the default one replaces it
+        let default_adc = self.adcs.iter().find(|adc| adc.i2c.is_none() && adc.address == 0x48);
+        for (adc, channel) in used_adcs {
+            match (adc, default_adc) {
+                (Some(adc), _) if !adc_names.contains(&adc) => {
+                    return Err(format!("Unknown ADC \"{}\" of channel {}", adc, channel_name(Some(adc), channel)));
+                },
+                (Some(_), _) | (None, None) => {},
+                (None, Some(default_adc)) => {
+                    return Err(format!("Channel {} must be read from ADC \"{}\"", channel, default_adc.name));
+                },
+            }
+        }
+        for control in controls {
+            let channel = channel_name(control.adc.as_deref(), control.channel);
+            validate_lookup_table(&control.lookup_table)
+                .map_err(|e| format!("Invalid lookup table for channel {}: {}", channel, e))?;
+            if control.center_dead_zone >= 50 {
+                return Err(format!("Dead zone of channel {} must be below 50%", channel));
+            }
+        }
+        if controls.iter().any(|c| c.role == Role::Balance) && self.alsa.is_none() {
+            return Err("A balance control requires an ALSA mixer".into());
+        }
+
+        if let Some(battery) = &self.battery {
+            if controls.iter().any(|c| c.adc == battery.adc && c.channel == battery.channel) {
+                return Err(format!(
+                    "ADC channel {} of battery is already in use",
+                    channel_name(battery.adc.as_deref(), battery.channel)
+                ));
+            }
+            if battery.divider <= 0.0 {
+                return Err("Battery voltage divider must be positive".into());
+            }
+            if battery.shutdown_voltage >= battery.warn_voltage {
+                return Err("Battery shutdown voltage must be below the warning voltage".into());
+            }
+        }
+
+        if let Some(latch) = &self.power_latch {
+            let in_use = |pin: u8| {
+                self.buttons.iter().any(|button| button.pin == pin)
+                    || self.outputs.iter().any(|output| output.pin == pin)
+                    || self.leds.iter().any(|led| led.pin == pin)
+                    || self.magic_eye.as_ref().is_some_and(|eye| eye.pin == Some(pin))
+                    || self.encoder.as_ref().is_some_and(|encoder| {
+                        encoder.pin_a == pin || encoder.pin_b == pin || encoder.switch_pin == Some(pin)
+                    })
+                    || self.headphones.as_ref().is_some_and(|headphones| {
+                        headphones.detect_pin == pin || headphones.amp_enable_pin == Some(pin)
+                    })
+                    || self.profile_jumpers.values().any(|jumper| *jumper == pin)
+            };
+            for pin in std::iter::once(latch.hold_pin).chain(latch.request_pin) {
+                if in_use(pin) {
+                    return Err(format!("GPIO pin {} of power latch is already in use", pin));
+                }
+            }
+            if latch.request_pin == Some(latch.hold_pin) {
+                return Err("The power latch needs different hold and request pins".into());
+            }
+        }
+
+        if let Some(light) = &self.ambient_light {
+            if light.min_brightness > light.max_brightness || light.max_brightness > 100 {
+                return Err("Ambient light brightness must be percentages with the minimum below the maximum".into());
+            }
+            if light.dark_lux <= 0.0 || light.bright_lux <= light.dark_lux {
+                return Err("Ambient light illuminances must be positive with the dark one below the bright one".into());
+            }
+        }
+
+        if let Some(fan) = &self.fan {
+            let pin = fan.pin;
+            if self.buttons.iter().any(|button| button.pin == pin)
+                || self.outputs.iter().any(|output| output.pin == pin)
+                || self.leds.iter().any(|led| led.pin == pin)
+                || self.magic_eye.as_ref().is_some_and(|eye| eye.pin == Some(pin))
+                || self
+                    .power_latch
+                    .as_ref()
+                    .is_some_and(|latch| latch.hold_pin == pin || latch.request_pin == Some(pin))
+            {
+                return Err(format!("GPIO pin {} of fan is already in use", pin));
+            }
+            if fan.curve.is_empty() {
+                return Err("The fan curve needs at least one point".into());
+            }
+            if fan.curve.windows(2).any(|pair| pair[1].0 <= pair[0].0) {
+                return Err("Temperatures of the fan curve must be strictly increasing".into());
+            }
+            if fan.curve.iter().any(|(_, duty)| *duty > 100) {
+                return Err("Duty cycles of the fan curve must be percentages".into());
+            }
+        }
+
+        if let Some(dx) = &self.dx {
+            if !self.is_band(&dx.band) {
+                return Err(format!("Unknown DX band \"{}\"", dx.band));
+            }
+            if self.tuning.bands.contains_key(&dx.band) {
+                return Err(format!("The DX band \"{}\" can't have tuning stations", dx.band));
+            }
+            if dx.recordings.is_empty() {
+                return Err("DX reception needs at least one recording".into());
+            }
+            if !(0.0..=1.0).contains(&dx.noise) || !(0.0..=1.0).contains(&dx.fading) {
+                return Err("DX noise and fading must be between 0 and 1".into());
+            }
+        }
+
+        if self.lock.max_volume > 100 {
+            return Err("Maximum volume of the lock must not exceed 100".into());
+        }
+        if let Some(name) = &self.lock.button {
+            match self.button(name) {
+                None => return Err(format!("Unknown lock button \"{}\"", name)),
+                // Band keys and the off key latch, so they're held down while
+                // a band plays or the radio is off
+                Some(button) if button.band().is_some() || button.action == ButtonAction::Shutdown => {
+                    return Err(format!("Lock button \"{}\" must not be a band or shutdown button", name));
+                },
+                Some(_) => {},
+            }
+            if self.lock.hold_s == 0 {
+                return Err("Hold time of the lock button must not be 0".into());
+            }
+        }
+
+        for entry in &self.schedule {
+            if entry.minutes().is_none() {
+                return Err(format!("Invalid schedule time \"{}\", expected HH:MM", entry.time));
+            }
+            if entry.action == ScheduledAction::Play && entry.playlist.is_none() {
+                return Err(format!("Schedule entry at {} plays without a playlist", entry.time));
+            }
+            if entry.volume.is_some_and(|volume| volume > 100) {
+                return Err(format!("Volume of schedule entry at {} must not exceed 100", entry.time));
+            }
+        }
+
+        if let Some(weather) = &self.weather {
+            if !(-90.0..=90.0).contains(&weather.latitude) || !(-180.0..=180.0).contains(&weather.longitude) {
+                return Err("Invalid weather location".into());
+            }
+            if weather.interval_minutes == 0 {
+                return Err("Weather interval must not be 0".into());
+            }
+        }
+
+        if self.idle.as_ref().is_some_and(|idle| idle.after_minutes == 0) {
+            return Err("Idle time must not be 0".into());
+        }
+
+        for (profile, pin) in &self.profile_jumpers {
+            if !self.profiles.contains_key(profile) {
+                return Err(format!("Unknown profile \"{}\" in profile jumpers", profile));
+            }
+            if self.buttons.iter().any(|button| button.pin == *pin) {
+                return Err(format!("GPIO pin {} is used by a button and a profile jumper", pin));
+            }
+        }
+
+        if self.low_write.as_ref().is_some_and(|low_write| low_write.interval_minutes == 0) {
+            return Err("Low-write interval must not be 0".into());
+        }
+
+        if self.streams.timeout_ms == 0 || self.streams.attempts == 0 {
+            return Err("Stream timeout and attempts must not be 0".into());
+        }
+        if self.streams.backoff_ms > MAX_STREAM_BACKOFF_MS {
+            return Err("Stream backoff must not exceed an hour".into());
+        }
+        for (i, station) in self.stations.iter().enumerate() {
+            if station.id.is_empty() || self.stations[..i].iter().any(|other| other.id == station.id) {
+                return Err(format!("Station ids must be unique and not empty (station \"{}\")", station.id));
+            }
+            if station.name.as_deref() == Some("") {
+                return Err(format!("Name of station \"{}\" must not be empty", station.id));
+            }
+            if station.max_volume.is_some_and(|max_volume| max_volume > 100) {
+                return Err(format!("Maximum volume of station \"{}\" must not exceed 100", station.id));
+            }
+            if station.stream_timeout_ms == Some(0) || station.stream_attempts == Some(0) {
+                return Err(format!("Stream timeout and attempts of station \"{}\" must not be 0", station.id));
+            }
+            if station.stream_backoff_ms.is_some_and(|backoff| backoff > MAX_STREAM_BACKOFF_MS) {
+                return Err(format!("Stream backoff of station \"{}\" must not exceed an hour", station.id));
+            }
+        }
+        for (i, band) in self.bands.iter().enumerate() {
+            if band.name.is_empty() || self.bands[..i].iter().any(|other| other.name == band.name) {
+                return Err(format!("Band names must be unique and not empty (band \"{}\")", band.name));
+            }
+            if self.button(&band.name).and_then(Button::playlist).is_some() {
+                return Err(format!("Band \"{}\" has the name of a button with a playlist", band.name));
+            }
+            if band.stations.is_empty() {
+                return Err(format!("Band \"{}\" has no stations", band.name));
+            }
+            if self.tuning.bands.contains_key(&band.name) {
+                return Err(format!("The stations of band \"{}\" must only be set in [[bands]]", band.name));
+            }
+            for program in &band.programs {
+                if parse_minutes(&program.from).is_none() || parse_minutes(&program.to).is_none() {
+                    return Err(format!("Invalid program time of band \"{}\", expected HH:MM", band.name));
+                }
+                if program.from == program.to || program.station.is_empty() {
+                    return Err(format!("Programs of band \"{}\" need a time range and a station", band.name));
+                }
+            }
+            if !band.programs.is_empty() && (self.has_tuning_control() || self.is_dx_band(&band.name)) {
+                return Err(format!("Band \"{}\" has programs, but the station is not played by itself", band.name));
+            }
+        }
+        if let Some(band) = self.tuning.bands.keys().find(|band| !self.is_band(band)) {
+            return Err(format!("Unknown band \"{}\" in tuning config", band));
+        }
+        let analog_tuning = controls.iter().any(|c| c.role == Role::Tuning);
+        let encoder_role = self.encoder.as_ref().map(|encoder| encoder.role);
+        if !self.tuning.bands.is_empty() && !analog_tuning && encoder_role != Some(EncoderRole::Tuning) {
+            return Err("Tuning stations require an analog control or an encoder with the tuning role".into());
+        }
+        if analog_tuning && encoder_role == Some(EncoderRole::Tuning) {
+            return Err("Stations can't be tuned with both an analog control and the encoder".into());
+        }
+        if self.tuning.mode == TuningMode::Seek {
+            if !analog_tuning {
+                return Err("The seek tuning mode requires an analog control with the tuning role".into());
+            }
+            let tuning = self.tuning();
+            for (band, positions) in &tuning.positions {
+                let stations = tuning.stations(band);
+                if !tuning.bands.contains_key(band) {
+                    return Err(format!("Unknown band \"{}\" in tuning positions", band));
+                }
+                if let Some(station) = positions.keys().find(|station| !stations.contains(station)) {
+                    return Err(format!("Unknown station \"{}\" in tuning positions of band \"{}\"", station, band));
+                }
+                if let Some(station) = positions.iter().find(|(_, &position)| position > 100).map(|(s, _)| s) {
+                    return Err(format!("Dial position of station \"{}\" must be at most 100", station));
+                }
+            }
+            for (band, stations) in &tuning.bands {
+                let positions = tuning.positions.get(band);
+                if let Some(station) = stations.iter().find(|s| positions.is_none_or(|p| !p.contains_key(*s))) {
+                    return Err(format!("Station \"{}\" of band \"{}\" has no dial position", station, band));
+                }
+            }
+        } else if !self.tuning.positions.is_empty() {
+            return Err("Tuning positions are only used in the seek tuning mode".into());
+        }
+        if encoder_role == Some(EncoderRole::Volume)
+            && (self.analog.is_none() || controls.iter().any(|c| c.role == Role::Volume))
+        {
+            return Err("When the encoder controls the volume, the analog controls must be \
+                        configured without a volume control"
+                .into());
+        }
+
+        for device in &self.evdev {
+            let mut codes = device.keys.keys().chain(device.buttons.keys());
+            if let Some(key) = codes.find(|key| key.parse::<u16>().is_err()) {
+                return Err(format!("Invalid key code \"{}\" for device {}", key, device.device));
+            }
+            if let Some(button) = device.buttons.values().find(|button| self.button(button).is_none()) {
+                return Err(format!("Unknown button \"{}\" for device {}", button, device.device));
+            }
+            for action in device.keys.values() {
+                match action {
+                    KeyAction::Band(band) if !self.is_band(band) => {
+                        return Err(format!("Unknown action \"{}\" for device {}", band, device.device));
+                    },
+                    KeyAction::LineOut if self.line_out.is_none() => {
+                        return Err(format!("The line_out key of device {} requires [line_out]", device.device));
+                    },
+                    _ => {},
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Return the configured analog controls.
+    ///
+    /// If none are configured, the default wiring is returned: volume on A0
+    /// and tone on A1, or volume on A0-A1 and tone on A2-A3 when measuring
+    /// differentially.
+    pub fn analog_controls(&self, differential: bool) -> Vec<AnalogControl> {
+        match (&self.analog, differential) {
+            (Some(controls), _) => controls.clone(),
+            (None, false) => vec![
+                AnalogControl::new(Channel::A0, Role::Volume),
+                AnalogControl::new(Channel::A1, Role::Monitor),
+            ],
+            (None, true) => vec![
+                AnalogControl::new(Channel::A0A1, Role::Volume),
+                AnalogControl::new(Channel::A2A3, Role::Monitor),
+            ],
+        }
+    }
+}
+
+/// Ensure that a lookup table can be used for interpolation.
+///
+/// The table needs at least two entries, and both the angles and the values
+/// must be strictly increasing.
+pub fn validate_lookup_table(table: &[(u16, u16)]) -> Result<(), String> {
+    if table.len() < 2 {
+        return Err("At least two entries are required".into());
+    }
+    for pair in table.windows(2) {
+        if pair[1].0 <= pair[0].0 {
+            return Err(format!("Angles must be strictly increasing ({} after {})", pair[1].0, pair[0].0));
+        }
+        if pair[1].1 <= pair[0].1 {
+            return Err(format!("Values must be strictly increasing ({} after {})", pair[1].1, pair[0].1));
+        }
+    }
+    Ok(())
+}
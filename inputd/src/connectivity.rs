@@ -0,0 +1,67 @@
+use std::{
+    fmt, fs,
+    net::ToSocketAddrs,
+    process::{Command, Stdio},
+};
+
+use crate::status;
+
+/// Host that answers plain HTTP requests to `/generate_204` with an empty
+/// 204 response. Captive portals intercept the request and answer with a
+/// redirect or a login page instead.
+const CHECK_HOST: &str = "connectivitycheck.gstatic.com";
+
+/// The state of the network connection.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Connectivity {
+    /// There is no default route, e.g. because Wi-Fi is not connected.
+    NoLink,
+    /// Host names cannot be resolved.
+    NoDns,
+    /// HTTP requests are intercepted by a captive portal.
+    CaptivePortal,
+    /// The internet is reachable.
+    Online,
+}
+
+impl fmt::Display for Connectivity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Connectivity::NoLink => "no link",
+            Connectivity::NoDns => "no DNS",
+            Connectivity::CaptivePortal => "captive portal",
+            Connectivity::Online => "online",
+        })
+    }
+}
+
+/// Check the network connection, from the link up to HTTP.
+pub fn check() -> Connectivity {
+    let link = fs::read_to_string("/proc/net/route").is_ok_and(|routes| status::has_default_route(&routes));
+    let dns = link && (CHECK_HOST, 80).to_socket_addrs().is_ok_and(|mut addrs| addrs.next().is_some());
+    let http_status = if dns { http_status(&format!("http://{}/generate_204", CHECK_HOST)) } else { None };
+    classify(link, dns, http_status)
+}
+
+/// Tell the state of the network connection from the results of the
+/// individual checks.
+pub fn classify(link: bool, dns: bool, http_status: Option<u16>) -> Connectivity {
+    match (link, dns, http_status) {
+        (false, _, _) => Connectivity::NoLink,
+        (true, false, _) => Connectivity::NoDns,
+        (true, true, Some(204)) => Connectivity::Online,
+        // Captive portals often block requests entirely until logged in
+        (true, true, _) => Connectivity::CaptivePortal,
+    }
+}
+
+/// Return the HTTP status code of a GET request, without following
+/// redirects.
+fn http_status(url: &str) -> Option<u16> {
+    let output = Command::new("/usr/bin/curl")
+        .args(["-s", "-o", "/dev/null", "--max-time", "10", "-w", "%{http_code}", url])
+        .stderr(Stdio::null())
+        .output()
+        .ok()?;
+    String::from_utf8_lossy(&output.stdout).trim().parse().ok().filter(|status| *status != 0)
+}
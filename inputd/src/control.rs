@@ -0,0 +1,146 @@
+use std::{
+    fs,
+    io::{self, BufRead, BufReader, Write},
+    os::unix::{
+        fs::PermissionsExt,
+        net::{UnixListener, UnixStream},
+    },
+    sync::Arc,
+    thread,
+};
+
+use crate::{config::LockConfig, events, log, SharedState};
+
+/// Accept connections on the control socket. Every connection sends one
+/// command line and receives the response until the socket is closed.
+///
+/// Commands:
+///
+/// - `logs`: The recent log records as JSON, one per line
+/// - `logs follow`: The recent log records, followed by new records as they
+///   are written
+/// - `events`: The recent events (button presses, stream starts and stops,
+///   volume changes and errors), one per line
+/// - `status`: The status report for remote debugging
+/// - `brightness`: The brightness of the dial lamp and the display
+/// - `brightness N`: Set the brightness to N percent instead of following
+///   the ambient light
+/// - `brightness auto`: Follow the ambient light again
+/// - `lock`: Whether the radio is locked against small children
+/// - `lock on`, `lock off`: Lock or unlock the radio
+pub fn serve(path: &str, cmd: &str, lock: &LockConfig, shared: Arc<SharedState>) -> Result<(), String> {
+    // Remove the socket of a previous run
+    let _ = fs::remove_file(path);
+    let listener = UnixListener::bind(path).map_err(|e| format!("Could not bind {}: {}", path, e))?;
+    // Allow the group to connect, e.g. an admin user in the volumio group
+    fs::set_permissions(path, fs::Permissions::from_mode(0o660))
+        .map_err(|e| format!("Could not set permissions of {}: {}", path, e))?;
+    let (cmd, lock) = (cmd.to_string(), lock.clone());
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            match stream {
+                Ok(stream) => {
+                    let (cmd, lock, shared) = (cmd.clone(), lock.clone(), shared.clone());
+                    thread::spawn(move || {
+                        if let Err(e) = handle(stream, &cmd, &lock, &shared) {
+                            // Clients closing the connection while following
+                            // are expected
+                            if e.kind() != io::ErrorKind::BrokenPipe {
+                                warn!("control", "Control connection failed: {}", e);
+                            }
+                        }
+                    });
+                },
+                Err(e) => warn!("control", "Could not accept control connection: {}", e),
+            }
+        }
+    });
+    Ok(())
+}
+
+/// Handle a control connection.
+fn handle(stream: UnixStream, cmd: &str, lock: &LockConfig, shared: &SharedState) -> io::Result<()> {
+    let mut command = String::new();
+    BufReader::new(&stream).read_line(&mut command)?;
+    let mut stream = &stream;
+    match command.trim() {
+        "logs" => {
+            for record in log::recent() {
+                writeln!(stream, "{}", record)?;
+            }
+        },
+        "logs follow" => {
+            let (recent, receiver) = log::follow();
+            for record in recent {
+                writeln!(stream, "{}", record)?;
+            }
+            for record in receiver {
+                writeln!(stream, "{}", record)?;
+            }
+        },
+        "events" => {
+            for event in events::recent() {
+                writeln!(stream, "{}", event)?;
+            }
+        },
+        "status" => write!(stream, "{}", shared.status(cmd).render())?,
+        "brightness" => writeln!(stream, "{}", describe_brightness(shared))?,
+        "lock" => writeln!(stream, "{}", describe_lock(shared))?,
+        "lock on" | "lock off" => {
+            shared.set_locked(cmd, lock, command.trim() == "lock on");
+            writeln!(stream, "{}", describe_lock(shared))?;
+        },
+        command => match command.strip_prefix("brightness ").map(parse_brightness) {
+            Some(Ok(brightness)) => {
+                match brightness {
+                    Some(brightness) => info!("control", "Brightness set to {}%", brightness),
+                    None => info!("control", "Brightness follows the ambient light"),
+                }
+                *shared.brightness_override.lock().unwrap() = brightness;
+                writeln!(stream, "{}", describe_brightness(shared))?;
+            },
+            Some(Err(e)) => writeln!(stream, "Error: {}", e)?,
+            None => writeln!(stream, "Error: Unknown command \"{}\"", command)?,
+        },
+    }
+    Ok(())
+}
+
+/// Parse the argument of the `brightness` command: A percentage, or `None`
+/// for `auto`.
+pub fn parse_brightness(value: &str) -> Result<Option<u8>, String> {
+    match value.trim() {
+        "auto" => Ok(None),
+        value => value
+            .parse()
+            .ok()
+            .filter(|brightness| *brightness <= 100)
+            .map(Some)
+            .ok_or_else(|| format!("Invalid brightness \"{}\", expected a percentage or \"auto\"", value)),
+    }
+}
+
+/// Describe the brightness and where it comes from.
+fn describe_brightness(shared: &SharedState) -> String {
+    match (*shared.brightness_override.lock().unwrap(), *shared.ambient_brightness.lock().unwrap()) {
+        (Some(brightness), _) => format!("brightness: {}% (manual)", brightness),
+        (None, Some(brightness)) => format!("brightness: {}% (ambient light)", brightness),
+        (None, None) => "brightness: 100% (not dimmed)".into(),
+    }
+}
+
+/// Describe whether the radio is locked.
+fn describe_lock(shared: &SharedState) -> String {
+    match *shared.lock_volume.lock().unwrap() {
+        Some(max_volume) => format!("lock: on (band buttons ignored, volume limited to {}%)", max_volume),
+        None => "lock: off".into(),
+    }
+}
+
+/// Send a command to the daemon and copy the response to `out`.
+pub fn request(path: &str, command: &str, out: &mut impl Write) -> Result<(), String> {
+    let mut stream = UnixStream::connect(path).map_err(|e| format!("Could not connect to {}: {}", path, e))?;
+    writeln!(stream, "{}", command).map_err(|e| format!("Could not send the command: {}", e))?;
+    io::copy(&mut stream, out).map_err(|e| format!("Could not read the response: {}", e))?;
+    Ok(())
+}
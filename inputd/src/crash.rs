@@ -0,0 +1,63 @@
+use std::{
+    backtrace::Backtrace,
+    env, fs, panic,
+    path::{Path, PathBuf},
+    thread,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::log;
+
+/// Install a panic hook that writes a crash report to a file in `dir` (or the
+/// temporary directory if that fails) before the default hook prints the
+/// panic message.
+///
+/// The summary (e.g. the command line options and the configuration) is
+/// added to every report.
+pub fn install_panic_hook(dir: PathBuf, summary: String) {
+    let default_hook = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        let thread = thread::current();
+        let report = report(
+            thread.name().unwrap_or("unnamed"),
+            &info.to_string(),
+            &Backtrace::force_capture().to_string(),
+            &log::recent(),
+            &summary,
+        );
+        // The directory may be on a read-only filesystem, the temporary
+        // directory usually isn't
+        match write_report(&dir, &report).or_else(|_| write_report(&env::temp_dir(), &report)) {
+            Ok(path) => eprintln!("Wrote crash report to {}", path.display()),
+            Err(e) => eprintln!("Error: Could not write crash report to {}: {}", dir.display(), e),
+        }
+        default_hook(info);
+    }));
+}
+
+/// Format a crash report.
+pub fn report(thread: &str, panic: &str, backtrace: &str, recent_events: &[String], summary: &str) -> String {
+    let mut report = format!(
+        "inputd {} crashed in thread '{}'\n\n{}\n\n## Backtrace\n\n{}\n\n## Recent events\n\n",
+        env!("CARGO_PKG_VERSION"),
+        thread,
+        panic,
+        backtrace.trim_end()
+    );
+    for event in recent_events {
+        report.push_str(event);
+        report.push('\n');
+    }
+    report.push_str("\n## Configuration\n\n");
+    report.push_str(summary.trim_end());
+    report.push('\n');
+    report
+}
+
+fn write_report(dir: &Path, report: &str) -> std::io::Result<PathBuf> {
+    fs::create_dir_all(dir)?;
+    let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+    let path = dir.join(format!("crash-{}.txt", timestamp.as_secs()));
+    fs::write(&path, report)?;
+    Ok(path)
+}
@@ -0,0 +1,110 @@
+use debouncr::Edge;
+
+/// Debounces a digital input.
+///
+/// Unlike the debouncers of the `debouncr` crate, the number of samples is
+/// chosen at runtime, so that it can be configured per pin.
+pub struct Debouncer {
+    mask: u32,
+    history: u32,
+    active: bool,
+}
+
+impl Debouncer {
+    /// Create a new debouncer that requires `samples` (1-32) consecutive
+    /// identical samples to change its state. The initial state is inactive.
+    pub fn new(samples: u8) -> Self {
+        let samples = samples.clamp(1, 32) as u32;
+        Self {
+            mask: u32::MAX >> (32 - samples),
+            history: 0,
+            active: false,
+        }
+    }
+
+    /// Update the debouncer with a new sample.
+    ///
+    /// Returns the edge if the debounced state changed.
+    pub fn update(&mut self, active: bool) -> Option<Edge> {
+        self.history = self.history << 1 | active as u32;
+        let stable = self.history & self.mask;
+        if !self.active && stable == self.mask {
+            self.active = true;
+            Some(Edge::Rising)
+        } else if self.active && stable == 0 {
+            self.active = false;
+            Some(Edge::Falling)
+        } else {
+            None
+        }
+    }
+}
+
+/// Weight of the latest edge in the smoothed bounce rate.
+const BOUNCE_RATE_SMOOTHING: f64 = 0.05;
+
+/// Smoothed number of bounces per edge above which a switch is reported as
+/// worn.
+const WORN_BOUNCE_RATE: f64 = 1.0;
+
+/// The smoothed bounce rate must drop below this before a worn switch is
+/// reported as fine again.
+const RECOVERED_BOUNCE_RATE: f64 = 0.5;
+
+/// Number of edges before a switch can be reported as worn, so that the
+/// first presses don't decide.
+const MIN_EDGES: u32 = 20;
+
+/// Press and bounce counts of a switch, to tell when its contacts need
+/// cleaning.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct SwitchWear {
+    /// Number of debounced presses.
+    pub presses: u32,
+    /// Number of debounced presses and releases.
+    pub edges: u32,
+    /// Number of raw level changes that didn't change the debounced state.
+    pub bounces: u32,
+    /// Bounces per edge, smoothed over the recent edges.
+    pub bounce_rate: f64,
+    /// Whether the bounce rate is too high.
+    pub worn: bool,
+    /// Raw level changes since the last edge.
+    changes: u32,
+    /// The last raw sample.
+    last: Option<bool>,
+}
+
+impl SwitchWear {
+    /// Update the counts with a raw sample and the resulting edge of the
+    /// debouncer.
+    ///
+    /// Returns whether the switch became worn.
+    pub fn update(&mut self, active: bool, edge: Option<Edge>, pressed: bool) -> bool {
+        if self.last.is_some_and(|last| last != active) {
+            self.changes += 1;
+        }
+        self.last = Some(active);
+        if edge.is_none() {
+            return false;
+        }
+
+        // One of the changes is the actual press or release
+        let bounces = self.changes.saturating_sub(1);
+        self.changes = 0;
+        self.edges += 1;
+        self.bounces += bounces;
+        if pressed {
+            self.presses += 1;
+        }
+        self.bounce_rate += (bounces as f64 - self.bounce_rate) * BOUNCE_RATE_SMOOTHING;
+
+        let was_worn = self.worn;
+        if self.edges >= MIN_EDGES && self.bounce_rate > WORN_BOUNCE_RATE {
+            self.worn = true;
+        } else if self.bounce_rate < RECOVERED_BOUNCE_RATE {
+            self.worn = false;
+        }
+        self.worn && !was_worn
+    }
+}
@@ -0,0 +1,165 @@
+use std::fs::{self, OpenOptions};
+
+use embedded_hal::blocking::i2c::Read;
+use linux_embedded_hal::I2cdev;
+
+use crate::{alsa, config::Config};
+
+/// Range of I2C addresses that are probed, without the reserved ones.
+const I2C_ADDRESSES: std::ops::RangeInclusive<u8> = 0x03..=0x77;
+
+/// A device on the I2C bus, told apart by its address.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum I2cDevice {
+    /// ADS1115 ADC for the analog controls.
+    Ads1115(u8),
+    /// DS3231 real-time clock.
+    Ds3231(u8),
+    /// MCP4725 DAC driving a magic eye.
+    Mcp4725(u8),
+    /// PCM512x audio DAC, e.g. of a HiFiBerry DAC+.
+    Pcm512x(u8),
+    /// SSD1306 OLED display.
+    Ssd1306(u8),
+    /// PCF8574 I2C backpack of an HD44780 character LCD.
+    Pcf8574(u8),
+}
+
+impl I2cDevice {
+    /// Return the device that is usually found at the address.
+    pub fn identify(address: u8) -> Option<Self> {
+        match address {
+            0x3c | 0x3d => Some(I2cDevice::Ssd1306(address)),
+            0x20..=0x27 | 0x38..=0x3f => Some(I2cDevice::Pcf8574(address)),
+            0x48..=0x4b => Some(I2cDevice::Ads1115(address)),
+            0x4c..=0x4f => Some(I2cDevice::Pcm512x(address)),
+            0x60..=0x67 => Some(I2cDevice::Mcp4725(address)),
+            0x68 => Some(I2cDevice::Ds3231(address)),
+            _ => None,
+        }
+    }
+
+    /// Return a description of the device.
+    pub fn description(&self) -> &'static str {
+        match self {
+            I2cDevice::Ads1115(_) => "ADS1115 ADC",
+            I2cDevice::Ds3231(_) => "DS3231 real-time clock",
+            I2cDevice::Mcp4725(_) => "MCP4725 DAC",
+            I2cDevice::Pcm512x(_) => "PCM512x audio DAC",
+            I2cDevice::Ssd1306(_) => "SSD1306 OLED display",
+            I2cDevice::Pcf8574(_) => "PCF8574 LCD backpack",
+        }
+    }
+}
+
+/// Return the addresses of the devices that answer on the I2C bus, and
+/// whether they are in use by a kernel driver.
+pub fn scan_i2c(i2c: &str) -> Result<Vec<(u8, bool)>, String> {
+    let mut dev = I2cdev::new(i2c).map_err(|e| format!("Could not open {}: {}", i2c, e))?;
+    let mut found = vec![];
+    for address in I2C_ADDRESSES {
+        match dev.read(address, &mut [0]) {
+            Ok(()) => found.push((address, false)),
+            // The address can't be used because a driver claimed it, e.g. the
+            // one of an audio DAC
+            Err(e) if e.to_string().to_lowercase().contains("busy") => found.push((address, true)),
+            Err(_) => {},
+        }
+    }
+    Ok(found)
+}
+
+/// Return the GPIO character devices and whether they can be opened.
+pub fn gpio_chips() -> Vec<(String, bool)> {
+    let mut chips: Vec<String> = fs::read_dir("/dev")
+        .map(|entries| {
+            entries
+                .filter_map(|entry| entry.ok())
+                .map(|entry| entry.file_name().to_string_lossy().into_owned())
+                .filter(|name| name.starts_with("gpiochip"))
+                .collect()
+        })
+        .unwrap_or_default();
+    chips.sort();
+    chips
+        .into_iter()
+        .map(|chip| {
+            let usable = OpenOptions::new().read(true).write(true).open(format!("/dev/{}", chip)).is_ok();
+            (chip, usable)
+        })
+        .collect()
+}
+
+/// Render a configuration skeleton for the detected hardware.
+///
+/// The ALSA card and mixer control are the ones of the audio DAC, if any.
+pub fn skeleton(devices: &[I2cDevice], alsa: Option<(&str, &str)>) -> String {
+    let mut config = String::from("# Generated by `inputd detect`, see config.example.toml for all options.\n");
+    let mut display = false;
+    for device in devices {
+        match *device {
+            I2cDevice::Ads1115(0x48) => {
+                config.push_str("\n[[analog]]\nchannel = \"A0\"\nrole = \"volume\"\n");
+            },
+            I2cDevice::Ads1115(address) => {
+                config.push_str(&format!("\n[[adcs]]\nname = \"adc{:02x}\"\naddress = 0x{:02x}\n", address, address));
+            },
+            I2cDevice::Ds3231(address) => config.push_str(&format!("\n[rtc]\naddress = 0x{:02x}\n", address)),
+            I2cDevice::Mcp4725(address) => config.push_str(&format!(
+                "\n[magic_eye]\nmcp4725_address = 0x{:02x}\nsource = \"playback\"\n",
+                address
+            )),
+            I2cDevice::Ssd1306(address) | I2cDevice::Pcf8574(address) if display => {
+                config.push_str(&format!("\n# Another display at 0x{:02x}: only one is supported\n", address));
+            },
+            I2cDevice::Ssd1306(address) => {
+                display = true;
+                config.push_str(&format!("\n[display]\ntype = \"ssd1306\"\naddress = 0x{:02x}\n", address));
+            },
+            I2cDevice::Pcf8574(address) => {
+                display = true;
+                config.push_str(&format!("\n[display]\ntype = \"hd44780\"\naddress = 0x{:02x}\n", address));
+            },
+            I2cDevice::Pcm512x(_) => {},
+        }
+    }
+    if let Some((card, mixer)) = alsa {
+        config.push_str(&format!("\n[alsa]\ncard = \"{}\"\nmixer = \"{}\"\n", card, mixer));
+    }
+    config
+}
+
+/// Print the detected hardware and a configuration skeleton.
+pub fn run(i2c: &str) -> Result<(), String> {
+    println!("I2C devices on {}:", i2c);
+    let mut devices = vec![];
+    for (address, in_use) in scan_i2c(i2c)? {
+        let device = I2cDevice::identify(address);
+        let description = device.map_or("unknown device", |device| device.description());
+        let driver = if in_use { ", in use by a driver" } else { "" };
+        println!("  0x{:02x}: {}{}", address, description, driver);
+        devices.extend(device);
+    }
+
+    println!("GPIO chips:");
+    for (chip, usable) in gpio_chips() {
+        println!("  {}{}", chip, if usable { "" } else { " (no permission)" });
+    }
+
+    println!("ALSA cards:");
+    let cards = alsa::cards();
+    for (index, id) in &cards {
+        println!("  {}: {}", index, id);
+    }
+
+    // The mixer of the first card that isn't the built-in audio or HDMI
+    let mixer = cards
+        .iter()
+        .filter(|(_, id)| !["Headphones", "b1", "vc4hdmi", "vc4hdmi0", "vc4hdmi1", "ALSA"].contains(&id.as_str()))
+        .find_map(|(_, id)| Some((id.clone(), alsa::controls(id).ok()?.into_iter().next()?)));
+
+    let config = skeleton(&devices, mixer.as_ref().map(|(card, mixer)| (card.as_str(), mixer.as_str())));
+    Config::parse(&config).map_err(|e| format!("Generated an invalid configuration: {}", e))?;
+    println!("\nSuggested configuration:\n\n{}", config);
+    Ok(())
+}
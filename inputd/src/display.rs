@@ -0,0 +1,79 @@
+use crate::artwork::Bitmap;
+
+/// Contents of a display.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Screen {
+    /// Name of the current station (playlist).
+    pub station: Option<String>,
+    /// Artist and title of the current track, if known.
+    pub title: Option<String>,
+    pub volume: u8,
+    pub muted: bool,
+    /// The current time, formatted as HH:MM.
+    pub clock: String,
+    /// The current date, e.g. "Fri 16.10.".
+    pub date: String,
+    /// The time of the next scheduled playback, formatted as HH:MM.
+    pub next_alarm: Option<String>,
+    /// Summary of the current weather, if known.
+    pub weather: Option<String>,
+    /// Artwork of the station or the track, on displays that show it.
+    pub artwork: Option<Bitmap>,
+}
+
+/// A display that shows what's playing.
+pub trait Display {
+    /// Show the screen.
+    ///
+    /// This is called periodically, `tick` increases with every call and is
+    /// used to scroll text that doesn't fit on the display.
+    fn show(&mut self, screen: &Screen, tick: usize) -> Result<(), String>;
+
+    /// Switch the display off or back on.
+    ///
+    /// Displays that keep their contents without power (e-paper) are left
+    /// as they are.
+    fn set_blanked(&mut self, _blanked: bool) -> Result<(), String> {
+        Ok(())
+    }
+
+    /// Set the brightness in percent, on displays that can be dimmed.
+    fn set_brightness(&mut self, _brightness: u8) -> Result<(), String> {
+        Ok(())
+    }
+}
+
+/// Replace characters that the displays can't show, e.g. umlauts.
+pub fn to_ascii(text: &str) -> String {
+    let mut ascii = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            ' '..='~' => ascii.push(c),
+            'ä' => ascii.push_str("ae"),
+            'ö' => ascii.push_str("oe"),
+            'ü' => ascii.push_str("ue"),
+            'Ä' => ascii.push_str("Ae"),
+            'Ö' => ascii.push_str("Oe"),
+            'Ü' => ascii.push_str("Ue"),
+            'ß' => ascii.push_str("ss"),
+            _ => ascii.push('?'),
+        }
+    }
+    ascii
+}
+
+/// Return the `width` characters of `text` that are visible at scroll
+/// position `offset`.
+///
+/// Text that fits is returned as is, longer text scrolls through like a
+/// marquee.
+pub fn scroll(text: &str, width: usize, offset: usize) -> String {
+    let chars: Vec<char> = text.chars().collect();
+    if chars.len() <= width {
+        return text.to_string();
+    }
+    let period = chars.len() + 3;
+    (0..width)
+        .map(|i| chars.get((offset + i) % period).copied().unwrap_or(' '))
+        .collect()
+}
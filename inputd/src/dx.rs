@@ -0,0 +1,123 @@
+use std::{
+    fs,
+    path::Path,
+    process::{Child, Command, Stdio},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::config::Dx;
+
+/// Range of the frequency in Hz with which the reception fades in and out.
+/// Each recording gets its own, so that they don't all sound alike.
+const FADE_FREQUENCIES: (f64, f64) = (0.1, 0.3);
+
+/// Return the recordings of the pool: the files and URLs as they are, and the
+/// files in directories, sorted by name.
+pub fn pool(recordings: &[String]) -> Vec<String> {
+    let mut pool = vec![];
+    for recording in recordings {
+        match fs::read_dir(recording) {
+            Ok(entries) => {
+                let mut files: Vec<String> = entries
+                    .filter_map(|entry| entry.ok())
+                    .map(|entry| entry.path())
+                    .filter(|path| path.is_file())
+                    .map(|path| path.display().to_string())
+                    .collect();
+                files.sort();
+                pool.extend(files);
+            },
+            Err(_) => pool.push(recording.clone()),
+        }
+    }
+    pool
+}
+
+/// Picks the recordings (or the stations of the lucky gesture) in a random
+/// order, without playing the same one twice in a row.
+pub struct Shuffle {
+    state: u64,
+    last: Option<usize>,
+}
+
+impl Shuffle {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            // The state of xorshift must not be 0
+            state: seed | 1,
+            last: None,
+        }
+    }
+
+    /// Create a shuffle seeded with the current time.
+    pub fn from_time() -> Self {
+        Self::new(SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_nanos() as u64)
+    }
+
+    /// Return the next pseudo-random number.
+    pub fn next_u64(&mut self) -> u64 {
+        self.state ^= self.state << 13;
+        self.state ^= self.state >> 7;
+        self.state ^= self.state << 17;
+        self.state
+    }
+
+    /// Return the index of the next entry of a pool with `len` entries.
+    pub fn pick(&mut self, len: usize) -> Option<usize> {
+        if len == 0 {
+            return None;
+        }
+        let mut index = (self.next_u64() % len as u64) as usize;
+        if len > 1 && self.last == Some(index) {
+            index = (index + 1) % len;
+        }
+        self.last = Some(index);
+        Some(index)
+    }
+
+    /// Return a fading frequency for the next recording.
+    pub fn fade_frequency(&mut self) -> f64 {
+        let (min, max) = FADE_FREQUENCIES;
+        min + (self.next_u64() % 1000) as f64 / 1000.0 * (max - min)
+    }
+}
+
+/// Return the arguments of ffmpeg to play a recording like it's received on
+/// shortwave: band-limited, fading in and out, and mixed with noise.
+pub fn ffmpeg_args(recording: &str, dx: &Dx, fade_frequency: f64) -> Vec<String> {
+    let filter = format!(
+        "[0:a]highpass=f=300,lowpass=f=3000,tremolo=f={:.2}:d={:.2}[rx];\
+         anoisesrc=color=brown:amplitude={:.2}[noise];\
+         [rx][noise]amix=inputs=2:duration=first",
+        fade_frequency, dx.fading, dx.noise
+    );
+    [
+        "-hide_banner",
+        "-loglevel",
+        "error",
+        "-nostdin",
+        "-i",
+        recording,
+        "-filter_complex",
+        &filter,
+        "-f",
+        "alsa",
+        &dx.device,
+    ]
+    .iter()
+    .map(|arg| arg.to_string())
+    .collect()
+}
+
+/// Start playing a recording.
+pub fn play(recording: &str, dx: &Dx, fade_frequency: f64) -> Result<Child, String> {
+    if !recording.contains("://") && !Path::new(recording).is_file() {
+        return Err(format!("Recording {} does not exist", recording));
+    }
+    Command::new("ffmpeg")
+        .args(ffmpeg_args(recording, dx, fade_frequency))
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .spawn()
+        .map_err(|e| format!("Could not run ffmpeg: {}", e))
+}
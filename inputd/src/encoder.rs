@@ -0,0 +1,55 @@
+use std::time::Duration;
+
+/// Direction changes for every transition between two quadrature states,
+/// indexed by `previous_state << 2 | current_state`. Invalid transitions
+/// (both signals changing at once) are ignored.
+const TRANSITIONS: [i8; 16] = [0, -1, 1, 0, 1, 0, 0, -1, -1, 0, 0, 1, 0, 1, -1, 0];
+
+/// Decodes the quadrature signal of a rotary encoder.
+pub struct QuadratureDecoder {
+    steps_per_detent: i8,
+    state: u8,
+    steps: i8,
+}
+
+impl QuadratureDecoder {
+    /// Create a new decoder with the current levels of both signals.
+    pub fn new(steps_per_detent: u8, a: bool, b: bool) -> Self {
+        Self {
+            steps_per_detent: steps_per_detent.clamp(1, 4) as i8,
+            state: (a as u8) << 1 | b as u8,
+            steps: 0,
+        }
+    }
+
+    /// Update the decoder with the current levels of both signals.
+    ///
+    /// Returns 1 or -1 when the encoder was turned by a full detent in the
+    /// respective direction, 0 otherwise.
+    pub fn update(&mut self, a: bool, b: bool) -> i8 {
+        let state = (a as u8) << 1 | b as u8;
+        self.steps += TRANSITIONS[(self.state << 2 | state) as usize];
+        self.state = state;
+        if self.steps >= self.steps_per_detent {
+            self.steps = 0;
+            1
+        } else if self.steps <= -self.steps_per_detent {
+            self.steps = 0;
+            -1
+        } else {
+            0
+        }
+    }
+}
+
+/// Return the volume change for a single detent.
+///
+/// Fast spins are accelerated, so that the full volume range can be covered
+/// without turning the encoder dozens of times.
+pub fn volume_step(since_last_detent: Duration) -> u8 {
+    match since_last_detent.as_millis() {
+        0..=29 => 5,
+        30..=79 => 2,
+        _ => 1,
+    }
+}
@@ -0,0 +1,227 @@
+use std::{
+    thread,
+    time::{Duration, Instant},
+};
+
+use rppal::{
+    gpio::Level,
+    spi::{Bus, Mode, SlaveSelect, Spi},
+};
+
+use crate::{
+    artwork::{Bitmap, ARTWORK_SIZE},
+    display::{self, Display, Screen},
+    font,
+    gpio::{InputPin, OutputPin},
+};
+
+/// Size of the panel in landscape orientation.
+const WIDTH: usize = 296;
+const HEIGHT: usize = 128;
+
+/// Size of the display RAM of a single frame, one bit per pixel.
+const FRAME_SIZE: usize = WIDTH * HEIGHT / 8;
+
+/// Maximum time a refresh may take.
+const BUSY_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The contents shown on the e-paper display.
+///
+/// The volume changes too often for e-paper, so it isn't shown.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Content {
+    pub station: Option<String>,
+    pub title: Option<String>,
+    pub clock: String,
+    pub artwork: Option<Bitmap>,
+}
+
+/// A Waveshare 2.9" e-paper panel (296x128, SSD1680 controller) connected to
+/// SPI0.
+///
+/// Every update is a full refresh, which avoids ghosting. Since a refresh
+/// takes a few seconds and the panel only tolerates a limited number of
+/// refreshes, the display is only refreshed when its contents changed, and
+/// at most once per `min_refresh_interval`.
+pub struct Epaper {
+    spi: Spi,
+    dc: OutputPin,
+    reset: OutputPin,
+    busy: InputPin,
+    min_refresh_interval: Duration,
+    shown: Option<Content>,
+    last_refresh: Option<Instant>,
+}
+
+impl Epaper {
+    pub fn new(
+        dc: OutputPin,
+        reset: OutputPin,
+        busy: InputPin,
+        min_refresh_interval: Duration,
+    ) -> Result<Self, String> {
+        let spi = Spi::new(Bus::Spi0, SlaveSelect::Ss0, 4_000_000, Mode::Mode0)
+            .map_err(|e| format!("Could not open SPI bus: {}", e))?;
+        let mut display = Self {
+            spi,
+            dc,
+            reset,
+            busy,
+            min_refresh_interval,
+            shown: None,
+            last_refresh: None,
+        };
+        display.init()?;
+        Ok(display)
+    }
+
+    fn init(&mut self) -> Result<(), String> {
+        // Hardware reset
+        self.reset.set_low();
+        thread::sleep(Duration::from_millis(10));
+        self.reset.set_high();
+        thread::sleep(Duration::from_millis(10));
+        self.wait_until_idle()?;
+
+        self.command(0x12, &[])?; // Software reset
+        self.wait_until_idle()?;
+        self.command(0x01, &[(WIDTH - 1) as u8, ((WIDTH - 1) >> 8) as u8, 0x00])?; // Gate lines
+        self.command(0x11, &[0x03])?; // Data entry mode: X and Y increment
+        self.command(0x44, &[0x00, (HEIGHT / 8 - 1) as u8])?; // RAM X range
+        self.command(0x45, &[0x00, 0x00, (WIDTH - 1) as u8, ((WIDTH - 1) >> 8) as u8])?; // RAM Y range
+        self.command(0x3c, &[0x05])?; // Border waveform
+        self.command(0x18, &[0x80])?; // Internal temperature sensor
+        self.wait_until_idle()
+    }
+
+    fn command(&mut self, command: u8, data: &[u8]) -> Result<(), String> {
+        self.dc.set_low();
+        self.spi.write(&[command]).map_err(|e| format!("Could not write to e-paper: {}", e))?;
+        self.dc.set_high();
+        for chunk in data.chunks(4096) {
+            self.spi.write(chunk).map_err(|e| format!("Could not write to e-paper: {}", e))?;
+        }
+        Ok(())
+    }
+
+    fn wait_until_idle(&self) -> Result<(), String> {
+        let started = Instant::now();
+        while self.busy.read() == Level::High {
+            if started.elapsed() > BUSY_TIMEOUT {
+                return Err("Timeout while waiting for e-paper".into());
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        Ok(())
+    }
+
+    fn refresh(&mut self, canvas: &Canvas) -> Result<(), String> {
+        self.command(0x4e, &[0x00])?; // RAM X address
+        self.command(0x4f, &[0x00, 0x00])?; // RAM Y address
+        self.command(0x24, &canvas.0)?;
+        self.command(0x22, &[0xf7])?; // Full update
+        self.command(0x20, &[])?;
+        self.wait_until_idle()
+    }
+}
+
+impl Display for Epaper {
+    fn show(&mut self, screen: &Screen, _tick: usize) -> Result<(), String> {
+        let content = Content {
+            station: screen.station.clone(),
+            title: screen.title.clone(),
+            clock: screen.clock.clone(),
+            artwork: screen.artwork.clone(),
+        };
+        if self.shown.as_ref() == Some(&content) {
+            return Ok(());
+        }
+        if let Some(last_refresh) = self.last_refresh {
+            if last_refresh.elapsed() < self.min_refresh_interval {
+                return Ok(());
+            }
+        }
+        self.last_refresh = Some(Instant::now());
+        self.refresh(&Canvas::render(&content))?;
+        self.shown = Some(content);
+        Ok(())
+    }
+}
+
+/// Display RAM contents: The panel is mounted in landscape orientation, the
+/// RAM is organized in portrait orientation with 8 pixels per byte, MSB first.
+/// Set bits are white.
+pub struct Canvas(pub Vec<u8>);
+
+impl Canvas {
+    /// Render the clock, the station name, the title and the artwork.
+    ///
+    /// The artwork is shown on the right, the text is cut off before it.
+    pub fn render(content: &Content) -> Self {
+        let mut canvas = Canvas(vec![0xff; FRAME_SIZE]);
+        let text_width = match &content.artwork {
+            Some(artwork) => {
+                let left = WIDTH - 4 - ARTWORK_SIZE;
+                for y in 0..artwork.height {
+                    for x in (0..artwork.width).filter(|&x| artwork.is_black(x, y)) {
+                        canvas.set_black(left + x, 4 + y);
+                    }
+                }
+                left - 8
+            },
+            None => WIDTH - 8,
+        };
+        canvas.text(4, 4, 3, &content.clock);
+        if let Some(station) = &content.station {
+            let station: String = display::to_ascii(station).chars().take(text_width / 12).collect();
+            canvas.text(4, 40, 2, &station);
+        }
+        if let Some(title) = &content.title {
+            // Wrap the title over up to 4 lines
+            let title: Vec<char> = display::to_ascii(title).chars().collect();
+            let columns = text_width / 6;
+            for (i, line) in title.chunks(columns).take(4).enumerate() {
+                canvas.text(4, 68 + i * 14, 1, &line.iter().collect::<String>());
+            }
+        }
+        canvas
+    }
+
+    /// Return whether a pixel (in landscape coordinates) is black.
+    #[cfg(test)]
+    pub fn is_black(&self, x: usize, y: usize) -> bool {
+        let (index, bit) = Self::position(x, y);
+        self.0[index] & bit == 0
+    }
+
+    fn position(x: usize, y: usize) -> (usize, u8) {
+        // Rotate by 90 degrees
+        let (ram_x, ram_y) = (HEIGHT - 1 - y, x);
+        (ram_y * HEIGHT / 8 + ram_x / 8, 0x80 >> (ram_x % 8))
+    }
+
+    fn set_black(&mut self, x: usize, y: usize) {
+        if x < WIDTH && y < HEIGHT {
+            let (index, bit) = Self::position(x, y);
+            self.0[index] &= !bit;
+        }
+    }
+
+    /// Draw text at the specified position, scaled by `scale`.
+    fn text(&mut self, x: usize, y: usize, scale: usize, text: &str) {
+        for (i, c) in text.chars().enumerate() {
+            for (column, bits) in font::glyph(c).iter().enumerate() {
+                for row in 0..8 {
+                    if bits & (1 << row) == 0 {
+                        continue;
+                    }
+                    for dx in 0..scale {
+                        for dy in 0..scale {
+                            self.set_black(x + (i * 6 + column) * scale + dx, y + row * scale + dy);
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
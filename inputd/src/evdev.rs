@@ -0,0 +1,56 @@
+use std::{
+    fs::File,
+    io::{self, Read},
+    mem,
+};
+
+/// Event type of key presses.
+pub const EV_KEY: u16 = 0x01;
+
+/// Event values of key events.
+pub const KEY_PRESS: i32 = 1;
+pub const KEY_REPEAT: i32 = 2;
+
+/// Size of a `struct input_event`: A `struct timeval` (two longs) followed by
+/// the type, the code and the value.
+const EVENT_SIZE: usize = 2 * mem::size_of::<usize>() + 8;
+
+/// An event read from an evdev input device.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InputEvent {
+    pub kind: u16,
+    pub code: u16,
+    pub value: i32,
+}
+
+impl InputEvent {
+    /// Parse an event from its in-memory representation.
+    pub fn parse(buf: &[u8; EVENT_SIZE]) -> Self {
+        let offset = EVENT_SIZE - 8;
+        Self {
+            kind: u16::from_ne_bytes([buf[offset], buf[offset + 1]]),
+            code: u16::from_ne_bytes([buf[offset + 2], buf[offset + 3]]),
+            value: i32::from_ne_bytes([buf[offset + 4], buf[offset + 5], buf[offset + 6], buf[offset + 7]]),
+        }
+    }
+}
+
+/// An evdev input device, e.g. an IR receiver handled by rc-core.
+pub struct Device {
+    file: File,
+}
+
+impl Device {
+    pub fn open(path: &str) -> io::Result<Self> {
+        Ok(Self {
+            file: File::open(path)?,
+        })
+    }
+
+    /// Read the next event, blocking until one is available.
+    pub fn read_event(&mut self) -> io::Result<InputEvent> {
+        let mut buf = [0; EVENT_SIZE];
+        self.file.read_exact(&mut buf)?;
+        Ok(InputEvent::parse(&buf))
+    }
+}
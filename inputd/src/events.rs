@@ -0,0 +1,95 @@
+use std::{
+    collections::VecDeque,
+    fmt,
+    sync::Mutex,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::log;
+
+/// Number of events that are kept.
+const CAPACITY: usize = 500;
+
+/// The most recent events.
+static EVENTS: Mutex<VecDeque<Event>> = Mutex::new(VecDeque::new());
+
+/// What happened.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EventKind {
+    /// A button or key was pressed.
+    Press,
+    /// A button was released.
+    Release,
+    /// A playlist was started.
+    Play,
+    /// The stream of the playlist is playing.
+    StreamStarted,
+    /// Playback was stopped.
+    Stop,
+    /// The volume was changed.
+    Volume,
+    /// An error was logged, e.g. because no stream of a playlist started.
+    Error,
+    /// The system is shut down.
+    Shutdown,
+}
+
+impl fmt::Display for EventKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            EventKind::Press => "press",
+            EventKind::Release => "release",
+            EventKind::Play => "play",
+            EventKind::StreamStarted => "stream_started",
+            EventKind::Stop => "stop",
+            EventKind::Volume => "volume",
+            EventKind::Error => "error",
+            EventKind::Shutdown => "shutdown",
+        };
+        f.write_str(name)
+    }
+}
+
+/// Something that happened, e.g. a button press or a stream start.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Event {
+    /// Milliseconds since the Unix epoch.
+    pub timestamp_ms: u64,
+    pub kind: EventKind,
+    /// E.g. the name of the button or the playlist.
+    pub detail: String,
+}
+
+impl fmt::Display for Event {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {}", log::rfc3339(self.timestamp_ms), self.kind)?;
+        if !self.detail.is_empty() {
+            write!(f, " {}", self.detail)?;
+        }
+        Ok(())
+    }
+}
+
+/// Remember an event, dropping the oldest one if the buffer is full.
+pub fn record(kind: EventKind, detail: impl Into<String>) {
+    let timestamp_ms = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_millis() as u64;
+    let event = Event {
+        timestamp_ms,
+        kind,
+        detail: detail.into(),
+    };
+    if let Ok(mut events) = EVENTS.lock() {
+        if events.len() == CAPACITY {
+            events.pop_front();
+        }
+        events.push_back(event);
+    }
+}
+
+/// Return the recent events, oldest first.
+pub fn recent() -> Vec<Event> {
+    match EVENTS.lock() {
+        Ok(events) => events.iter().cloned().collect(),
+        Err(_) => vec![],
+    }
+}
@@ -0,0 +1,52 @@
+/// The fan only slows down once the temperature dropped this many degrees
+/// below the point of the curve that sped it up.
+const HYSTERESIS: f64 = 2.0;
+
+/// Return the duty cycle in percent for a temperature, interpolated linearly
+/// between the `(temperature, duty cycle)` points of the curve.
+///
+/// Below the first point, the duty cycle of the first point is used, above
+/// the last point the one of the last point.
+pub fn duty_cycle(curve: &[(f64, u8)], temperature: f64) -> u8 {
+    let (first, last) = match (curve.first(), curve.last()) {
+        (Some(first), Some(last)) => (first, last),
+        _ => return 0,
+    };
+    if temperature <= first.0 {
+        return first.1;
+    }
+    for pair in curve.windows(2) {
+        let ((low_temp, low_duty), (high_temp, high_duty)) = (pair[0], pair[1]);
+        if temperature <= high_temp {
+            let ratio = (temperature - low_temp) / (high_temp - low_temp);
+            return (low_duty as f64 + ratio * (high_duty as f64 - low_duty as f64)).round() as u8;
+        }
+    }
+    last.1
+}
+
+/// Follows the temperature with the duty cycle of the fan.
+pub struct FanController {
+    curve: Vec<(f64, u8)>,
+    duty: u8,
+}
+
+impl FanController {
+    pub fn new(curve: Vec<(f64, u8)>) -> Self {
+        Self { curve, duty: 0 }
+    }
+
+    /// Return the duty cycle for the current temperature.
+    ///
+    /// The fan speeds up right away, but slows down with a hysteresis, so
+    /// that it doesn't keep switching on and off at a point of the curve.
+    pub fn update(&mut self, temperature: f64) -> u8 {
+        let duty = duty_cycle(&self.curve, temperature);
+        self.duty = if duty >= self.duty {
+            duty
+        } else {
+            self.duty.min(duty_cycle(&self.curve, temperature + HYSTERESIS))
+        };
+        self.duty
+    }
+}
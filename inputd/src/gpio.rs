@@ -0,0 +1,151 @@
+use std::{mem, sync::Mutex};
+
+use gpio_cdev::{Chip, LineHandle, LineRequestFlags};
+use rppal::gpio::Level;
+
+use crate::config::{GpioBackend, GpioConfig};
+
+/// Name of the consumer of the requested lines of a GPIO character device.
+const CONSUMER: &str = "inputd";
+
+/// The GPIO pins, accessed with the backend selected in the configuration.
+pub enum Gpio {
+    /// Memory-mapped registers of the Raspberry Pi.
+    Rppal(rppal::gpio::Gpio),
+    /// GPIO character device of the kernel, which works on other boards as
+    /// well.
+    Cdev(Mutex<Chip>),
+}
+
+impl Gpio {
+    pub fn open(config: &GpioConfig) -> Result<Self, String> {
+        match config.backend {
+            GpioBackend::Rppal => rppal::gpio::Gpio::new()
+                .map(Gpio::Rppal)
+                .map_err(|e| format!("Could not initialize GPIO: {}", e)),
+            GpioBackend::Cdev => Chip::new(&config.chip)
+                .map(|chip| Gpio::Cdev(Mutex::new(chip)))
+                .map_err(|e| format!("Could not open {}: {}", config.chip, e)),
+        }
+    }
+
+    /// Request a pin as input with the pull-up enabled.
+    ///
+    /// The character device can't enable pull-ups, they must be set up in
+    /// the device tree or with resistors.
+    pub fn input(&self, pin: u8) -> Result<InputPin, String> {
+        match self {
+            Gpio::Rppal(gpio) => gpio
+                .get(pin)
+                .map(|pin| InputPin::Rppal(pin.into_input_pullup()))
+                .map_err(|e| format!("Could not init GPIO pin {}: {}", pin, e)),
+            Gpio::Cdev(chip) => request(chip, pin, LineRequestFlags::INPUT, 0).map(InputPin::Cdev),
+        }
+    }
+
+    /// Request a pin as output, starting at the given level.
+    pub fn output(&self, pin: u8, level: Level) -> Result<OutputPin, String> {
+        match self {
+            Gpio::Rppal(gpio) => gpio
+                .get(pin)
+                .map(|pin| {
+                    let mut pin = pin.into_output();
+                    pin.write(level);
+                    OutputPin::Rppal(pin)
+                })
+                .map_err(|e| format!("Could not init GPIO pin {}: {}", pin, e)),
+            Gpio::Cdev(chip) => {
+                let handle = request(chip, pin, LineRequestFlags::OUTPUT, (level == Level::High) as u8)?;
+                Ok(OutputPin::Cdev(pin, handle))
+            },
+        }
+    }
+}
+
+/// Request a line of a GPIO character device.
+fn request(chip: &Mutex<Chip>, pin: u8, flags: LineRequestFlags, default: u8) -> Result<LineHandle, String> {
+    chip.lock()
+        .unwrap()
+        .get_line(pin.into())
+        .and_then(|line| line.request(flags, default, CONSUMER))
+        .map_err(|e| format!("Could not init GPIO pin {}: {}", pin, e))
+}
+
+/// A GPIO pin used as input.
+pub enum InputPin {
+    Rppal(rppal::gpio::InputPin),
+    Cdev(LineHandle),
+}
+
+impl InputPin {
+    /// Read the level of the pin. Read errors are treated as high, the level
+    /// of an open contact.
+    pub fn read(&self) -> Level {
+        match self {
+            InputPin::Rppal(pin) => pin.read(),
+            InputPin::Cdev(handle) => match handle.get_value() {
+                Ok(0) => Level::Low,
+                _ => Level::High,
+            },
+        }
+    }
+}
+
+/// A GPIO pin used as output.
+pub enum OutputPin {
+    Rppal(rppal::gpio::OutputPin),
+    /// The BCM number or line offset, for logging, and the requested line.
+    Cdev(u8, LineHandle),
+}
+
+impl OutputPin {
+    pub fn write(&mut self, level: Level) {
+        match self {
+            OutputPin::Rppal(pin) => pin.write(level),
+            OutputPin::Cdev(pin, handle) => {
+                if let Err(e) = handle.set_value((level == Level::High) as u8) {
+                    error!("gpio", "Could not set GPIO pin {}: {}", pin, e);
+                }
+            },
+        }
+    }
+
+    pub fn set_high(&mut self) {
+        self.write(Level::High);
+    }
+
+    pub fn set_low(&mut self) {
+        self.write(Level::Low);
+    }
+
+    /// Drive the pin with software PWM.
+    ///
+    /// The character device has no software PWM, so the pin is only switched
+    /// on from a duty cycle of 50%.
+    pub fn set_pwm_frequency(&mut self, frequency: f64, duty_cycle: f64) -> Result<(), String> {
+        match self {
+            OutputPin::Rppal(pin) => pin.set_pwm_frequency(frequency, duty_cycle).map_err(|e| e.to_string()),
+            OutputPin::Cdev(..) => {
+                self.write(if duty_cycle >= 0.5 { Level::High } else { Level::Low });
+                Ok(())
+            },
+        }
+    }
+
+    pub fn clear_pwm(&mut self) -> Result<(), String> {
+        match self {
+            OutputPin::Rppal(pin) => pin.clear_pwm().map_err(|e| e.to_string()),
+            OutputPin::Cdev(..) => Ok(()),
+        }
+    }
+
+    /// Keep the level of the pin until the daemon exits, instead of releasing
+    /// the pin when it's dropped.
+    pub fn keep(self) {
+        match self {
+            OutputPin::Rppal(mut pin) => pin.set_reset_on_drop(false),
+            // The line is released when the daemon exits
+            OutputPin::Cdev(..) => mem::forget(self),
+        }
+    }
+}
@@ -0,0 +1,178 @@
+use rppal::gpio::Level;
+
+use crate::{config::Channel, gpio::InputPin, open_adc, read_channel, Adc};
+
+#[cfg(test)]
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+};
+
+/// A digital input, e.g. the GPIO pin of a button.
+pub trait DigitalInput: Send {
+    fn is_low(&self) -> bool;
+}
+
+impl DigitalInput for InputPin {
+    fn is_low(&self) -> bool {
+        self.read() == Level::Low
+    }
+}
+
+/// The ADC is re-initialized after this many consecutive failed reads.
+const ADC_REOPEN_AFTER_ERRORS: u32 = 5;
+
+/// An analog input with multiple channels, e.g. the ADS1115.
+pub trait AnalogInput: Send {
+    /// Read the raw value of a channel.
+    fn read_raw(&mut self, channel: Channel) -> Result<i16, String>;
+
+    /// Open the device again and re-apply its settings, e.g. after
+    /// interference wedged the bus.
+    fn reopen(&mut self) -> Result<(), String>;
+}
+
+/// An ADS1115 on an I2C bus.
+pub struct Ads1115 {
+    i2c: String,
+    address: u8,
+    /// The device, unless re-opening it failed.
+    adc: Option<Adc>,
+}
+
+impl Ads1115 {
+    pub fn new(i2c: &str, address: u8, adc: Adc) -> Self {
+        Self {
+            i2c: i2c.to_string(),
+            address,
+            adc: Some(adc),
+        }
+    }
+}
+
+impl AnalogInput for Ads1115 {
+    fn read_raw(&mut self, channel: Channel) -> Result<i16, String> {
+        match &mut self.adc {
+            Some(adc) => read_channel(adc, channel),
+            None => Err("ADC not initialized".into()),
+        }
+    }
+
+    fn reopen(&mut self) -> Result<(), String> {
+        // Close the bus first, in case the driver only resets it on open
+        self.adc = None;
+        self.adc = Some(open_adc(&self.i2c, self.address)?);
+        Ok(())
+    }
+}
+
+/// An ADC that the analog controls are connected to.
+struct BankAdc {
+    /// The name, `None` for the default ADC.
+    name: Option<String>,
+    input: Box<dyn AnalogInput>,
+    /// Number of failed reads since the last successful one.
+    errors: u32,
+}
+
+/// The ADCs that the analog controls are connected to, by name. The default
+/// ADC has no name.
+///
+/// An ADC that keeps failing is re-initialized.
+#[derive(Default)]
+pub struct AdcBank {
+    adcs: Vec<BankAdc>,
+    /// Number of successful re-initializations.
+    recoveries: u32,
+}
+
+impl AdcBank {
+    pub fn add(&mut self, name: Option<&str>, adc: impl AnalogInput + 'static) {
+        self.adcs.push(BankAdc {
+            name: name.map(str::to_string),
+            input: Box::new(adc),
+            errors: 0,
+        });
+    }
+
+    /// Read the raw value of a channel of an ADC.
+    pub fn read_raw(&mut self, adc: Option<&str>, channel: Channel) -> Result<i16, String> {
+        let bank_adc = match self.adcs.iter_mut().find(|bank_adc| bank_adc.name.as_deref() == adc) {
+            Some(bank_adc) => bank_adc,
+            None => return Err("ADC not initialized".into()),
+        };
+        let result = bank_adc.input.read_raw(channel);
+        if result.is_ok() {
+            bank_adc.errors = 0;
+            return result;
+        }
+        bank_adc.errors += 1;
+        if bank_adc.errors % ADC_REOPEN_AFTER_ERRORS == 0 {
+            let name = adc.map_or("default ADC".into(), |adc| format!("ADC \"{}\"", adc));
+            warn!("adc", { errors: bank_adc.errors }, "Re-initializing {} after {} errors", name, bank_adc.errors);
+            match bank_adc.input.reopen() {
+                Ok(()) => {
+                    self.recoveries += 1;
+                    info!("adc", "Re-initialized {}", name);
+                },
+                Err(e) => error!("adc", "Could not re-initialize {}: {}", name, e),
+            }
+        }
+        result
+    }
+
+    /// Return the number of successful re-initializations.
+    pub fn recoveries(&self) -> u32 {
+        self.recoveries
+    }
+}
+
+/// A pin whose level is set by a test. Clones share the level.
+#[cfg(test)]
+#[derive(Clone)]
+pub struct FakePin(Arc<AtomicBool>);
+
+#[cfg(test)]
+impl FakePin {
+    /// Create a pin that is high, like an open contact with a pull-up.
+    pub fn new() -> Self {
+        Self(Arc::new(AtomicBool::new(false)))
+    }
+
+    pub fn set_low(&self, low: bool) {
+        self.0.store(low, Ordering::SeqCst);
+    }
+}
+
+#[cfg(test)]
+impl DigitalInput for FakePin {
+    fn is_low(&self) -> bool {
+        self.0.load(Ordering::SeqCst)
+    }
+}
+
+/// An ADC whose readings are set by a test. Clones share the readings.
+#[cfg(test)]
+#[derive(Clone, Default)]
+pub struct FakeAdc(Arc<Mutex<HashMap<Channel, i16>>>);
+
+#[cfg(test)]
+impl FakeAdc {
+    pub fn set(&self, channel: Channel, raw: i16) {
+        self.0.lock().unwrap().insert(channel, raw);
+    }
+}
+
+#[cfg(test)]
+impl AnalogInput for FakeAdc {
+    fn read_raw(&mut self, channel: Channel) -> Result<i16, String> {
+        self.0.lock().unwrap().get(&channel).copied().ok_or_else(|| "Not connected".into())
+    }
+
+    fn reopen(&mut self) -> Result<(), String> {
+        Ok(())
+    }
+}
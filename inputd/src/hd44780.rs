@@ -0,0 +1,131 @@
+use std::{thread, time::Duration};
+
+use embedded_hal::blocking::i2c::Write;
+use linux_embedded_hal::I2cdev;
+use serde::Deserialize;
+
+use crate::{
+    display::{self, Display, Screen},
+    i18n::{tr, Message},
+};
+
+/// Bits of the PCF8574 I2C backpack.
+const RS: u8 = 0x01;
+const ENABLE: u8 = 0x04;
+const BACKLIGHT: u8 = 0x08;
+
+/// DDRAM addresses of the first character of every row.
+const ROW_OFFSETS: [u8; 4] = [0x00, 0x40, 0x14, 0x54];
+
+/// What is shown on a line of a character LCD.
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum LcdLine {
+    Station,
+    Title,
+    Volume,
+    Clock,
+    Weather,
+    Empty,
+}
+
+/// A HD44780 character LCD (16x2, 20x4, ...) with a PCF8574 I2C backpack.
+pub struct Hd44780 {
+    dev: I2cdev,
+    address: u8,
+    columns: usize,
+    layout: Vec<LcdLine>,
+    /// The lines that are currently shown.
+    shown: Vec<Option<String>>,
+    /// Whether the backlight is on.
+    backlight: bool,
+}
+
+impl Hd44780 {
+    pub fn new(dev: I2cdev, address: u8, columns: usize, layout: Vec<LcdLine>) -> Result<Self, String> {
+        let mut display = Self {
+            dev,
+            address,
+            columns,
+            shown: vec![None; layout.len()],
+            layout,
+            backlight: true,
+        };
+
+        // Switch to 4-bit mode (see figure 24 of the datasheet)
+        for nibble in &[0x30, 0x30, 0x30, 0x20] {
+            display.write_nibble(*nibble, 0)?;
+            thread::sleep(Duration::from_millis(5));
+        }
+        display.command(0x28)?; // 2 lines, 5x8 font
+        display.command(0x0c)?; // Display on, no cursor
+        display.command(0x01)?; // Clear
+        thread::sleep(Duration::from_millis(2));
+        display.command(0x06)?; // Entry mode: Increment
+        Ok(display)
+    }
+
+    fn write_nibble(&mut self, nibble: u8, mode: u8) -> Result<(), String> {
+        let byte = nibble | mode | if self.backlight { BACKLIGHT } else { 0 };
+        self.dev
+            .write(self.address, &[byte | ENABLE, byte])
+            .map_err(|e| format!("Could not write to HD44780: {}", e))
+    }
+
+    fn write_byte(&mut self, byte: u8, mode: u8) -> Result<(), String> {
+        self.write_nibble(byte & 0xf0, mode)?;
+        self.write_nibble(byte << 4, mode)
+    }
+
+    fn command(&mut self, command: u8) -> Result<(), String> {
+        self.write_byte(command, 0)
+    }
+}
+
+impl Display for Hd44780 {
+    fn show(&mut self, screen: &Screen, tick: usize) -> Result<(), String> {
+        let lines = render_lines(screen, &self.layout, self.columns, tick);
+        for (row, line) in lines.into_iter().enumerate() {
+            if self.shown[row].as_ref() == Some(&line) {
+                continue;
+            }
+            self.command(0x80 | ROW_OFFSETS[row % ROW_OFFSETS.len()])?;
+            for byte in line.bytes() {
+                self.write_byte(byte, RS)?;
+            }
+            self.shown[row] = Some(line);
+        }
+        Ok(())
+    }
+
+    fn set_blanked(&mut self, blanked: bool) -> Result<(), String> {
+        self.backlight = !blanked;
+        self.command(if blanked { 0x08 } else { 0x0c }) // Display off or on
+    }
+}
+
+/// Render the lines of a character LCD, every line padded to `columns`
+/// characters.
+pub fn render_lines(screen: &Screen, layout: &[LcdLine], columns: usize, tick: usize) -> Vec<String> {
+    layout
+        .iter()
+        .map(|line| {
+            let text = match line {
+                LcdLine::Station => screen.station.clone().unwrap_or_default(),
+                LcdLine::Title => display::scroll(screen.title.as_deref().unwrap_or(""), columns, tick),
+                LcdLine::Volume if screen.muted => tr(Message::Mute).to_string(),
+                LcdLine::Volume => {
+                    let label = format!("{} {:>3}% ", tr(Message::Volume), screen.volume);
+                    let width = columns.saturating_sub(label.len());
+                    let filled = screen.volume.min(100) as usize * width / 100;
+                    format!("{}{}", label, "#".repeat(filled))
+                },
+                LcdLine::Clock => screen.clock.clone(),
+                LcdLine::Weather => screen.weather.clone().unwrap_or_default(),
+                LcdLine::Empty => String::new(),
+            };
+            let text: String = display::to_ascii(&text).chars().take(columns).collect();
+            format!("{:width$}", text, width = columns)
+        })
+        .collect()
+}
@@ -0,0 +1,57 @@
+use std::{
+    fs,
+    process::{Command, Stdio},
+};
+
+/// Temperature of the SoC in millidegrees Celsius.
+const THERMAL_ZONE: &str = "/sys/class/thermal/thermal_zone0/temp";
+
+/// Read the temperature of the SoC in degrees Celsius.
+pub fn read_temperature() -> Option<f64> {
+    read_sensor(THERMAL_ZONE)
+}
+
+/// Read a file with a temperature in millidegrees Celsius, like a thermal
+/// zone or a hwmon sensor, in degrees Celsius.
+pub fn read_sensor(path: &str) -> Option<f64> {
+    fs::read_to_string(path).ok().and_then(|contents| parse_temperature(&contents))
+}
+
+/// Parse the contents of a thermal zone.
+pub fn parse_temperature(contents: &str) -> Option<f64> {
+    contents.trim().parse::<i32>().ok().map(|millidegrees| millidegrees as f64 / 1000.0)
+}
+
+/// The current throttling state reported by the firmware.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Throttling {
+    /// The supply voltage is too low.
+    pub under_voltage: bool,
+    /// The CPU frequency is capped or the CPU is throttled, because of a low
+    /// supply voltage or a high temperature.
+    pub throttled: bool,
+}
+
+/// Read the throttling state with `vcgencmd`.
+pub fn read_throttling() -> Option<Throttling> {
+    let output = Command::new("vcgencmd")
+        .arg("get_throttled")
+        .stderr(Stdio::null())
+        .output()
+        .ok()
+        .filter(|output| output.status.success())?;
+    parse_throttling(&String::from_utf8_lossy(&output.stdout))
+}
+
+/// Parse the output of `vcgencmd get_throttled`, e.g. `throttled=0x50005`.
+///
+/// Only the bits for the current state are used, not the ones telling
+/// whether something occurred since boot.
+pub fn parse_throttling(output: &str) -> Option<Throttling> {
+    let flags = output.trim().strip_prefix("throttled=0x")?;
+    let flags = u32::from_str_radix(flags, 16).ok()?;
+    Some(Throttling {
+        under_voltage: flags & 0x1 != 0,
+        throttled: flags & 0xe != 0,
+    })
+}
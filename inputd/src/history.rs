@@ -0,0 +1,85 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    process::{Command, Stdio},
+    time::Duration,
+};
+
+/// Listening time per day and station.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct History {
+    /// Seconds per date (YYYY-MM-DD) and playlist.
+    seconds: BTreeMap<(String, String), u64>,
+}
+
+impl History {
+    /// Add listening time to a station on a date.
+    pub fn add(&mut self, date: &str, playlist: &str, duration: Duration) {
+        *self.seconds.entry((date.to_string(), playlist.to_string())).or_default() += duration.as_secs();
+    }
+
+    /// Render the history as lines of date, seconds and playlist.
+    pub fn render(&self) -> String {
+        self.seconds
+            .iter()
+            .map(|((date, playlist), seconds)| format!("{} {} {}\n", date, seconds, playlist))
+            .collect()
+    }
+
+    /// Parse a history written by `render`.
+    pub fn parse(contents: &str) -> Result<Self, String> {
+        let mut history = History::default();
+        for line in contents.lines().filter(|line| !line.trim().is_empty()) {
+            let mut parts = line.splitn(3, ' ');
+            match (parts.next(), parts.next().and_then(|seconds| seconds.parse().ok()), parts.next()) {
+                (Some(date), Some(seconds), Some(playlist)) => {
+                    history.add(date, playlist, Duration::from_secs(seconds));
+                },
+                _ => return Err(format!("Invalid line \"{}\"", line)),
+            }
+        }
+        Ok(history)
+    }
+
+    /// Return the listening time per station on the dates, the most listened
+    /// station first.
+    pub fn summary(&self, dates: &[String]) -> Vec<(String, Duration)> {
+        let mut totals: HashMap<&str, u64> = HashMap::new();
+        for ((date, playlist), seconds) in &self.seconds {
+            if dates.contains(date) {
+                *totals.entry(playlist).or_default() += seconds;
+            }
+        }
+        let mut summary: Vec<_> = totals
+            .into_iter()
+            .map(|(playlist, seconds)| (playlist.to_string(), Duration::from_secs(seconds)))
+            .collect();
+        summary.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        summary
+    }
+}
+
+/// Format a summary as a table with hours and minutes.
+pub fn render_summary(title: &str, summary: &[(String, Duration)]) -> String {
+    let mut report = format!("{}\n", title);
+    if summary.is_empty() {
+        report.push_str("  nothing played\n");
+    }
+    for (playlist, duration) in summary {
+        let minutes = duration.as_secs() / 60;
+        report.push_str(&format!("  {:>3}h {:02}m  {}\n", minutes / 60, minutes % 60, playlist));
+    }
+    report
+}
+
+/// Return the local date `days_ago` days ago as YYYY-MM-DD.
+pub fn local_date(days_ago: usize) -> Option<String> {
+    let output = Command::new("date")
+        .arg("-d")
+        .arg(format!("-{} days", days_ago))
+        .arg("+%F")
+        .stderr(Stdio::null())
+        .output()
+        .ok()
+        .filter(|output| output.status.success())?;
+    Some(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
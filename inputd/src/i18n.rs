@@ -0,0 +1,137 @@
+use std::sync::atomic::{AtomicU8, Ordering};
+
+use serde::Deserialize;
+
+use crate::config::Weekday;
+
+/// The language of the texts shown to the listener.
+static LANGUAGE: AtomicU8 = AtomicU8::new(Language::En as u8);
+
+/// A language of the texts shown to the listener.
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Language {
+    #[default]
+    En,
+    De,
+}
+
+/// A text shown to the listener, e.g. on the display.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Message {
+    NoNetwork,
+    NoDns,
+    NetworkLogin,
+    StationDown,
+    Buffering,
+    Mute,
+    /// Label of the volume, followed by the percentage.
+    Volume,
+    /// Label of the next scheduled playback, followed by the time.
+    Alarm,
+    /// Unit of the temperature in announcements.
+    Degrees,
+    RainExpected,
+    Clear,
+    PartlyCloudy,
+    Overcast,
+    Fog,
+    Drizzle,
+    Rain,
+    Snow,
+    Thunderstorm,
+    UnknownWeather,
+    /// Abbreviation of a weekday.
+    Weekday(Weekday),
+}
+
+impl Message {
+    /// Return the text in a language.
+    pub fn text(self, language: Language) -> &'static str {
+        match language {
+            Language::En => self.english(),
+            Language::De => self.german(),
+        }
+    }
+
+    fn english(self) -> &'static str {
+        match self {
+            Message::NoNetwork => "No network",
+            Message::NoDns => "No DNS",
+            Message::NetworkLogin => "Network login required",
+            Message::StationDown => "Station down",
+            Message::Buffering => "Buffering...",
+            Message::Mute => "Mute",
+            Message::Volume => "Vol",
+            Message::Alarm => "Alarm",
+            Message::Degrees => "degrees",
+            Message::RainExpected => "rain expected",
+            Message::Clear => "clear",
+            Message::PartlyCloudy => "partly cloudy",
+            Message::Overcast => "overcast",
+            Message::Fog => "fog",
+            Message::Drizzle => "drizzle",
+            Message::Rain => "rain",
+            Message::Snow => "snow",
+            Message::Thunderstorm => "thunderstorm",
+            Message::UnknownWeather => "unknown",
+            Message::Weekday(weekday) => match weekday {
+                Weekday::Mon => "Mon",
+                Weekday::Tue => "Tue",
+                Weekday::Wed => "Wed",
+                Weekday::Thu => "Thu",
+                Weekday::Fri => "Fri",
+                Weekday::Sat => "Sat",
+                Weekday::Sun => "Sun",
+            },
+        }
+    }
+
+    fn german(self) -> &'static str {
+        match self {
+            Message::NoNetwork => "Kein Netzwerk",
+            Message::NoDns => "Kein DNS",
+            Message::NetworkLogin => "Netzwerk-Anmeldung nötig",
+            Message::StationDown => "Sender gestört",
+            Message::Buffering => "Puffern...",
+            Message::Mute => "Stumm",
+            Message::Volume => "Lautst.",
+            Message::Alarm => "Wecker",
+            Message::Degrees => "Grad",
+            Message::RainExpected => "Regen erwartet",
+            Message::Clear => "klar",
+            Message::PartlyCloudy => "teilweise bewölkt",
+            Message::Overcast => "bedeckt",
+            Message::Fog => "Nebel",
+            Message::Drizzle => "Niesel",
+            Message::Rain => "Regen",
+            Message::Snow => "Schnee",
+            Message::Thunderstorm => "Gewitter",
+            Message::UnknownWeather => "unbekannt",
+            Message::Weekday(weekday) => match weekday {
+                Weekday::Mon => "Mo",
+                Weekday::Tue => "Di",
+                Weekday::Wed => "Mi",
+                Weekday::Thu => "Do",
+                Weekday::Fri => "Fr",
+                Weekday::Sat => "Sa",
+                Weekday::Sun => "So",
+            },
+        }
+    }
+}
+
+/// Set the language of the texts shown to the listener.
+pub fn set_language(language: Language) {
+    LANGUAGE.store(language as u8, Ordering::SeqCst);
+}
+
+/// Return a text in the configured language.
+pub fn tr(message: Message) -> &'static str {
+    let language = if LANGUAGE.load(Ordering::SeqCst) == Language::De as u8 {
+        Language::De
+    } else {
+        Language::En
+    };
+    message.text(language)
+}
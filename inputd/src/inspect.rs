@@ -0,0 +1,72 @@
+use std::{
+    thread,
+    time::{Duration, Instant},
+};
+
+use rppal::gpio::Level;
+
+use crate::{
+    config::{channel_name, AnalogControl},
+    hardware::AdcBank,
+    map_potentiometer_value, GpioPinState, Opts,
+};
+
+/// Format the time since `started` like the kernel log, e.g. `[   12.345]`.
+pub fn timestamp(since_start: Duration) -> String {
+    format!("[{:>5}.{:03}]", since_start.as_secs(), since_start.subsec_millis())
+}
+
+/// Print every change of the inputs without triggering any actions.
+///
+/// Raw pin levels are printed as they are sampled, followed by the debounced
+/// presses and releases, so that bouncing contacts show up as level changes
+/// without an edge.
+pub fn run(mut state: GpioPinState, mut adcs: AdcBank, controls: Vec<AnalogControl>, opts: Opts) -> ! {
+    let started = Instant::now();
+    let adc_interval = Duration::from_millis(opts.adc_active_interval_ms);
+    let mut next_adc_reading = started;
+    let mut levels: Vec<Option<Level>> = vec![None; state.inputs.len()];
+    let mut values: Vec<Option<u8>> = vec![None; controls.len()];
+
+    println!("Printing input changes, press Ctrl+C to stop");
+    loop {
+        let now = Instant::now();
+        let ts = timestamp(now.duration_since(started));
+
+        for (input, last_level) in state.inputs.iter().zip(levels.iter_mut()) {
+            let level = if input.pin.is_low() { Level::Low } else { Level::High };
+            if *last_level != Some(level) {
+                println!("{} {} (GPIO {}): {}", ts, input.name, input.gpio, level);
+                *last_level = Some(level);
+            }
+        }
+        let (pressed, released) = state.update(now);
+        for name in pressed {
+            println!("{} {} pressed", ts, name);
+        }
+        for name in released {
+            println!("{} {} released", ts, name);
+        }
+
+        if now >= next_adc_reading {
+            next_adc_reading = now + adc_interval;
+            for (control, last_value) in controls.iter().zip(values.iter_mut()) {
+                // Only changes of the mapped value are printed, the raw
+                // value is too noisy
+                let channel = channel_name(control.adc.as_deref(), control.channel);
+                match adcs.read_raw(control.adc.as_deref(), control.channel) {
+                    Ok(raw) => {
+                        let value = map_potentiometer_value(&control.lookup_table, raw.max(0) as u16);
+                        if *last_value != Some(value) {
+                            println!("{} {} ({:?}): raw={} value={}", ts, channel, control.role, raw, value);
+                            *last_value = Some(value);
+                        }
+                    },
+                    Err(e) => println!("{} {}: read error: {}", ts, channel, e),
+                }
+            }
+        }
+
+        thread::sleep(Duration::from_millis(1));
+    }
+}
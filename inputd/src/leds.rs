@@ -0,0 +1,76 @@
+use std::time::Duration;
+
+use serde::Deserialize;
+
+use crate::outputs::{RadioState, ERROR_BLINK_DURATION};
+
+/// State of the daemon that is shown on the status LEDs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DaemonState {
+    /// Waiting for volumio to start.
+    Booting,
+    /// Ready, but not playing.
+    Ready,
+    /// A playlist was just started.
+    Buffering,
+    Playing,
+    /// A playlist could not be started.
+    Error,
+}
+
+impl DaemonState {
+    /// Return the state of the daemon.
+    pub fn of(state: &RadioState) -> Self {
+        if !state.ready {
+            DaemonState::Booting
+        } else if state.since_error.is_some_and(|since| since < ERROR_BLINK_DURATION) {
+            DaemonState::Error
+        } else if !state.playing {
+            DaemonState::Ready
+        } else if state.buffering {
+            DaemonState::Buffering
+        } else {
+            DaemonState::Playing
+        }
+    }
+
+    /// Return the pattern that is used if none is configured.
+    pub fn default_pattern(self) -> Pattern {
+        match self {
+            DaemonState::Booting => Pattern::SlowBlink,
+            DaemonState::Ready => Pattern::On,
+            DaemonState::Buffering => Pattern::FastBlink,
+            DaemonState::Playing => Pattern::Heartbeat,
+            DaemonState::Error => Pattern::FastBlink,
+        }
+    }
+}
+
+/// A blink pattern of a status LED.
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Pattern {
+    Off,
+    On,
+    /// On and off for half a second each.
+    SlowBlink,
+    /// On and off for 125 milliseconds each.
+    FastBlink,
+    /// Two short flashes every 1.5 seconds.
+    Heartbeat,
+}
+
+impl Pattern {
+    /// Return whether the LED is on at the specified time since the pattern
+    /// was started.
+    pub fn is_on(self, elapsed: Duration) -> bool {
+        let ms = elapsed.as_millis();
+        match self {
+            Pattern::Off => false,
+            Pattern::On => true,
+            Pattern::SlowBlink => ms % 1000 < 500,
+            Pattern::FastBlink => ms % 250 < 125,
+            Pattern::Heartbeat => matches!(ms % 1500, 0..=99 | 200..=299),
+        }
+    }
+}
@@ -0,0 +1,93 @@
+use embedded_hal::blocking::i2c::{Read, Write, WriteRead};
+use linux_embedded_hal::I2cdev;
+
+use crate::config::{AmbientLight, LightSensorType};
+
+/// An ambient light sensor on the I2C bus.
+pub struct LightSensor {
+    dev: I2cdev,
+    address: u8,
+    sensor: LightSensorType,
+}
+
+impl LightSensor {
+    /// Open the sensor and start continuous measurements.
+    pub fn open(i2c: &str, sensor: LightSensorType, address: u8) -> Result<Self, String> {
+        let dev = I2cdev::new(i2c).map_err(|e| format!("Could not open {}: {}", i2c, e))?;
+        let mut light_sensor = Self { dev, address, sensor };
+        let commands: &[&[u8]] = match sensor {
+            // Power on, continuous measurements with a resolution of 1 lx
+            LightSensorType::Bh1750 => &[&[0x01], &[0x10]],
+            // Power on, the default integration time of 402ms and gain of 1x
+            // are used
+            LightSensorType::Tsl2561 => &[&[0x80, 0x03]],
+        };
+        for command in commands {
+            light_sensor.write(command)?;
+        }
+        Ok(light_sensor)
+    }
+
+    fn write(&mut self, bytes: &[u8]) -> Result<(), String> {
+        self.dev
+            .write(self.address, bytes)
+            .map_err(|e| format!("Could not write to {:?}: {}", self.sensor, e))
+    }
+
+    /// Read the latest measurement in lux.
+    pub fn read_lux(&mut self) -> Result<f64, String> {
+        let sensor = self.sensor;
+        let error = |e| format!("Could not read from {:?}: {}", sensor, e);
+        match sensor {
+            LightSensorType::Bh1750 => {
+                let mut buffer = [0; 2];
+                self.dev.read(self.address, &mut buffer).map_err(error)?;
+                Ok(u16::from_be_bytes(buffer) as f64 / 1.2)
+            },
+            LightSensorType::Tsl2561 => {
+                // Word reads of the data registers of both channels
+                let (mut ch0, mut ch1) = ([0; 2], [0; 2]);
+                self.dev.write_read(self.address, &[0xac], &mut ch0).map_err(error)?;
+                self.dev.write_read(self.address, &[0xae], &mut ch1).map_err(error)?;
+                Ok(tsl2561_lux(u16::from_le_bytes(ch0), u16::from_le_bytes(ch1)))
+            },
+        }
+    }
+}
+
+/// Calculate the illuminance from the raw values of the broadband (`ch0`)
+/// and the infrared channel (`ch1`) of a TSL2561, measured with a gain of 1x
+/// and an integration time of 402ms.
+///
+/// The coefficients are the ones of the datasheet for the T package, which
+/// assume a gain of 16x.
+pub fn tsl2561_lux(ch0: u16, ch1: u16) -> f64 {
+    if ch0 == 0 {
+        return 0.0;
+    }
+    let (ch0, ch1) = (ch0 as f64 * 16.0, ch1 as f64 * 16.0);
+    let ratio = ch1 / ch0;
+    let lux = if ratio <= 0.5 {
+        0.0304 * ch0 - 0.062 * ch0 * ratio.powf(1.4)
+    } else if ratio <= 0.61 {
+        0.0224 * ch0 - 0.031 * ch1
+    } else if ratio <= 0.8 {
+        0.0128 * ch0 - 0.0153 * ch1
+    } else if ratio <= 1.3 {
+        0.00146 * ch0 - 0.00112 * ch1
+    } else {
+        0.0
+    };
+    lux.max(0.0)
+}
+
+/// Return the brightness in percent for an illuminance.
+///
+/// The brightness rises logarithmically between the dark and the bright
+/// illuminance, like the eye perceives it.
+pub fn brightness(lux: f64, config: &AmbientLight) -> u8 {
+    let (dark, bright) = (config.dark_lux.ln(), config.bright_lux.ln());
+    let position = ((lux.max(config.dark_lux).ln() - dark) / (bright - dark)).min(1.0);
+    let (min, max) = (config.min_brightness as f64, config.max_brightness as f64);
+    (min + position * (max - min)).round() as u8
+}
@@ -0,0 +1,235 @@
+use std::{
+    collections::VecDeque,
+    fmt,
+    str::FromStr,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{self, Receiver, Sender},
+        Mutex,
+    },
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::events::{self, EventKind};
+
+/// Whether log records are written as JSON.
+static JSON: AtomicBool = AtomicBool::new(false);
+
+/// Number of recent log records that are kept for crash reports.
+const RECENT_CAPACITY: usize = 100;
+
+/// The most recent log records.
+static RECENT: Mutex<VecDeque<String>> = Mutex::new(VecDeque::new());
+
+/// Receivers of new log records, see `follow`.
+static FOLLOWERS: Mutex<Vec<Sender<String>>> = Mutex::new(Vec::new());
+
+/// Format of the log output.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    /// Plain text lines, warnings and errors are written to stderr.
+    Text,
+    /// One JSON record per line on stdout.
+    Json,
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(Format::Text),
+            "json" => Ok(Format::Json),
+            _ => Err(format!("Unknown log format \"{}\" (expected \"text\" or \"json\")", s)),
+        }
+    }
+}
+
+pub fn set_format(format: Format) {
+    JSON.store(format == Format::Json, Ordering::SeqCst);
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Level {
+    Info,
+    Warning,
+    Error,
+}
+
+impl Level {
+    fn name(self) -> &'static str {
+        match self {
+            Level::Info => "info",
+            Level::Warning => "warning",
+            Level::Error => "error",
+        }
+    }
+}
+
+/// Write a log record.
+///
+/// The fields (e.g. the playlist or an exit status) are only part of JSON
+/// records, text lines just contain the message.
+pub fn write(level: Level, subsystem: &str, fields: &[(&str, &dyn fmt::Display)], message: fmt::Arguments) {
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+    let record = record(level, subsystem, fields, &message.to_string(), now.as_millis() as u64);
+    if let Ok(mut recent) = RECENT.lock() {
+        if recent.len() == RECENT_CAPACITY {
+            recent.pop_front();
+        }
+        recent.push_back(record.clone());
+        // Followers whose receiver was dropped are removed
+        if let Ok(mut followers) = FOLLOWERS.lock() {
+            followers.retain(|follower| follower.send(record.clone()).is_ok());
+        }
+    }
+
+    if level == Level::Error {
+        events::record(EventKind::Error, format!("{}: {}", subsystem, message));
+    }
+
+    if JSON.load(Ordering::SeqCst) {
+        println!("{}", record);
+    } else {
+        match level {
+            Level::Info => println!("{}", message),
+            Level::Warning => eprintln!("Warning: {}", message),
+            Level::Error => eprintln!("Error: {}", message),
+        }
+    }
+}
+
+/// Return the most recent log records as JSON, oldest first.
+pub fn recent() -> Vec<String> {
+    match RECENT.lock() {
+        Ok(recent) => recent.iter().cloned().collect(),
+        Err(_) => vec![],
+    }
+}
+
+/// Return the most recent log records as JSON, oldest first, and a receiver
+/// of the records that are written from now on.
+pub fn follow() -> (Vec<String>, Receiver<String>) {
+    let (sender, receiver) = mpsc::channel();
+    match RECENT.lock() {
+        Ok(recent) => {
+            // Register while holding the lock, so that no record is missed
+            if let Ok(mut followers) = FOLLOWERS.lock() {
+                followers.push(sender);
+            }
+            (recent.iter().cloned().collect(), receiver)
+        },
+        Err(_) => (vec![], receiver),
+    }
+}
+
+/// Format a JSON log record.
+pub fn record(
+    level: Level,
+    subsystem: &str,
+    fields: &[(&str, &dyn fmt::Display)],
+    message: &str,
+    timestamp_ms: u64,
+) -> String {
+    let mut record = format!(
+        "{{\"timestamp\":\"{}\",\"level\":\"{}\",\"subsystem\":\"{}\",\"message\":\"{}\"",
+        rfc3339(timestamp_ms),
+        level.name(),
+        escape(subsystem),
+        escape(message)
+    );
+    for (key, value) in fields {
+        record.push_str(&format!(",\"{}\":\"{}\"", escape(key), escape(&value.to_string())));
+    }
+    record.push('}');
+    record
+}
+
+/// Format milliseconds since the Unix epoch as an RFC 3339 timestamp in UTC.
+pub fn rfc3339(timestamp_ms: u64) -> String {
+    let secs = timestamp_ms / 1000;
+    let days = (secs / 86400) as i64;
+
+    // Convert days to a civil date (http://howardhinnant.github.io/date_algorithms.html)
+    let z = days + 719_468;
+    let era = z / 146_097;
+    let day_of_era = z - era * 146_097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + (month <= 2) as i64;
+
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}Z",
+        year,
+        month,
+        day,
+        secs / 3600 % 24,
+        secs / 60 % 60,
+        secs % 60,
+        timestamp_ms % 1000
+    )
+}
+
+/// Escape a string for use in JSON.
+pub(crate) fn escape(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            c if (c as u32) < 0x20 => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Log an info message: `info!("subsystem", { field: value }, "format", args)`.
+/// The fields are optional.
+macro_rules! info {
+    ($subsystem:expr, { $($key:ident: $value:expr),* $(,)? }, $($arg:tt)+) => {
+        $crate::log::write(
+            $crate::log::Level::Info,
+            $subsystem,
+            &[$((stringify!($key), &$value as &dyn std::fmt::Display)),*],
+            format_args!($($arg)+),
+        )
+    };
+    ($subsystem:expr, $($arg:tt)+) => {
+        $crate::log::write($crate::log::Level::Info, $subsystem, &[], format_args!($($arg)+))
+    };
+}
+
+/// Log a warning, see `info!`.
+macro_rules! warn {
+    ($subsystem:expr, { $($key:ident: $value:expr),* $(,)? }, $($arg:tt)+) => {
+        $crate::log::write(
+            $crate::log::Level::Warning,
+            $subsystem,
+            &[$((stringify!($key), &$value as &dyn std::fmt::Display)),*],
+            format_args!($($arg)+),
+        )
+    };
+    ($subsystem:expr, $($arg:tt)+) => {
+        $crate::log::write($crate::log::Level::Warning, $subsystem, &[], format_args!($($arg)+))
+    };
+}
+
+/// Log an error, see `info!`.
+macro_rules! error {
+    ($subsystem:expr, { $($key:ident: $value:expr),* $(,)? }, $($arg:tt)+) => {
+        $crate::log::write(
+            $crate::log::Level::Error,
+            $subsystem,
+            &[$((stringify!($key), &$value as &dyn std::fmt::Display)),*],
+            format_args!($($arg)+),
+        )
+    };
+    ($subsystem:expr, $($arg:tt)+) => {
+        $crate::log::write($crate::log::Level::Error, $subsystem, &[], format_args!($($arg)+))
+    };
+}
@@ -1,19 +1,111 @@
 use std::{
-    process::{exit, Command, Stdio},
+    collections::{HashMap, HashSet},
+    fs,
+    io,
+    os::unix::process::ExitStatusExt,
+    process::{exit, Child, Command, ExitStatus, Stdio},
+    sync::{
+        atomic::{AtomicBool, AtomicI8, AtomicU32, AtomicU8, Ordering},
+        mpsc, Arc, Mutex,
+    },
     thread,
-    time::Duration,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
 };
 
 use ads1x1x::{channel, Ads1x1x, DataRate16Bit, FullScaleRange, SlaveAddr};
 use clap::Clap;
-use debouncr::{debounce_stateful_16, DebouncerStateful, Edge, Repeat16};
-use embedded_hal::adc::OneShot;
+use debouncr::{debounce_stateful_16, Edge};
+use embedded_hal::{adc::OneShot, blocking::i2c::Write};
 use linux_embedded_hal::I2cdev;
 use nb::block;
-use rppal::gpio::{Gpio, InputPin, Level};
+use rppal::gpio::Level;
 
+#[macro_use]
+mod log;
+
+mod alsa;
+mod artwork;
+mod backup;
+mod battery;
+mod config;
+mod connectivity;
+mod control;
+mod crash;
+mod debounce;
+mod detect;
+mod dx;
+mod display;
+mod encoder;
+mod epaper;
+mod events;
+mod evdev;
+mod fan;
+mod font;
+mod gpio;
+mod hd44780;
+mod hardware;
+mod health;
+mod history;
+mod i18n;
+mod inspect;
+mod leds;
+mod light;
+mod notifier;
+mod outputs;
+mod persist;
+mod recording;
+mod remote_config;
+mod rtc;
+mod setup;
+mod sniff;
+mod snapshot;
+mod ssd1306;
+mod status;
 #[cfg(test)]
 mod tests;
+mod tuning;
+mod update;
+mod volume;
+mod weather;
+mod wifi;
+
+use crate::{
+    alsa::Mixer,
+    artwork::ArtworkCache,
+    battery::{BatteryEvent, BatteryMonitor},
+    config::{
+        channel_name, Alsa, AmbientLight, AnalogControl, Battery, Button, ButtonAction, Channel, Config, DisplayConfig,
+        Dx, Encoder, EncoderRole, EvdevDevice, Fan, GpioConfig, Headphones, Health, Idle, KeyAction, Led, LineOut,
+        LockConfig, MagicEye, Output, Role, ScheduleEntry, Notify, ScheduledAction, Station, Streams, Tuning,
+        TuningMode, WeatherConfig, Weekday,
+    },
+    connectivity::Connectivity,
+    debounce::{Debouncer, SwitchWear},
+    display::{Display, Screen},
+    encoder::QuadratureDecoder,
+    epaper::Epaper,
+    events::EventKind,
+    fan::FanController,
+    gpio::{Gpio, InputPin, OutputPin},
+    hardware::{AdcBank, Ads1115, DigitalInput},
+    hd44780::Hd44780,
+    health::Throttling,
+    history::History,
+    i18n::{tr, Message},
+    leds::DaemonState,
+    light::LightSensor,
+    notifier::Notifier,
+    outputs::RadioState,
+    persist::PersistentFile,
+    recording::{Record, Recorder, Sample},
+    rtc::Ds3231,
+    snapshot::Snapshot,
+    ssd1306::Ssd1306,
+    status::Status,
+    tuning::{DialPosition, Seeker, Tuner},
+    volume::{VolumeSink, Volumio},
+    weather::Weather,
+};
 
 #[derive(Clap, Debug, Clone)]
 struct Opts {
@@ -21,6 +113,177 @@ struct Opts {
     i2c: String,
     #[clap(default_value = "volumio")]
     volumio_command: String,
+    /// Treat the last N degrees before the counter-clockwise end stop of the
+    /// volume knob as "off" (0 disables the power switch)
+    #[clap(long, default_value = "0")]
+    off_degrees: u16,
+    /// Hysteresis in degrees around the power switch threshold
+    #[clap(long, default_value = "5")]
+    off_hysteresis: u16,
+    /// Time in milliseconds that the band buttons must be stable before a
+    /// band change is applied
+    #[clap(long, default_value = "50")]
+    band_settle_ms: u64,
+    /// Time in milliseconds after all band buttons were released before
+    /// playback is stopped. Pressing a band button within this time selects
+    /// the new band without stopping playback.
+    #[clap(long, default_value = "250")]
+    stop_grace_ms: u64,
+    /// Toggle mute when the active band button is released and pressed
+    /// again within this many milliseconds (0 disables the gesture)
+    #[clap(long, default_value = "0")]
+    mute_gesture_ms: u64,
+    /// Play a random station when two band buttons are held down together
+    /// for this many milliseconds (0 disables the gesture)
+    #[clap(long, default_value = "0")]
+    lucky_gesture_ms: u64,
+    /// Interval between two ADC measurements in milliseconds while the
+    /// volume knob is not being turned
+    #[clap(long, default_value = "250")]
+    adc_interval_ms: u64,
+    /// Interval between two ADC measurements in milliseconds while the
+    /// volume knob is being turned
+    #[clap(long, default_value = "50")]
+    adc_active_interval_ms: u64,
+    /// Interval between two steps of a volume ramp in milliseconds
+    /// (0 applies volume changes in a single step)
+    #[clap(long, default_value = "50")]
+    volume_ramp_ms: u64,
+    /// Volumes up to this percentage are clamped to 0%
+    #[clap(long, default_value = "0")]
+    dead_band_low: u8,
+    /// Volumes down to 100% minus this percentage are clamped to 100%
+    #[clap(long, default_value = "0")]
+    dead_band_high: u8,
+    /// Measure the volume knob differentially between A0 and A1 and the tone
+    /// knob between A2 and A3 (ignored if the analog controls are configured
+    /// in the configuration file)
+    #[clap(long)]
+    differential: bool,
+    /// Path to the configuration file
+    #[clap(long)]
+    config: Option<String>,
+    /// Fetch configuration overrides from this URL at startup. Their
+    /// top-level keys and sections replace those of the configuration file.
+    #[clap(long)]
+    remote_config: Option<String>,
+    /// Use the profile with this name from the configuration file, instead of
+    /// the one selected with a jumper
+    #[clap(long)]
+    profile: Option<String>,
+    /// File that the remote configuration overrides are cached in
+    #[clap(long, default_value = "/var/lib/weltempfaenger/remote-config.toml")]
+    remote_config_cache: String,
+    /// Periodically write the status report (see `inputd status`) to this
+    /// file
+    #[clap(long)]
+    status_file: Option<String>,
+    /// Periodically save the station, volume and sleep timer to this file and
+    /// restore them on startup
+    #[clap(long)]
+    state_file: Option<String>,
+    /// Log format, either "text" or "json" (one record per line with a
+    /// timestamp, level, subsystem and event fields)
+    #[clap(long, default_value = "text")]
+    log_format: log::Format,
+    /// Only log commands that change the volume, control playback or shut
+    /// down the system instead of running them
+    #[clap(long)]
+    dry_run: bool,
+    /// Record the raw samples of the buttons and the analog controls to this
+    /// file, so that they can be replayed later
+    #[clap(long)]
+    record: Option<String>,
+    /// Record the listening time per station and day in this file, see
+    /// `inputd history`
+    #[clap(long)]
+    history_file: Option<String>,
+    /// Unix socket that the daemon accepts commands on, e.g. from `inputd
+    /// logs`
+    #[clap(long, default_value = "/tmp/inputd.sock")]
+    control_socket: String,
+    /// Directory that crash reports are written to
+    #[clap(long, default_value = "/var/lib/weltempfaenger/crash")]
+    crash_dir: String,
+    #[clap(subcommand)]
+    command: Option<Subcommand>,
+}
+
+#[derive(Clap, Debug, Clone)]
+enum Subcommand {
+    /// Replace the binary with the latest release after verifying its
+    /// checksum, and restart the service
+    SelfUpdate,
+    /// Detect the buttons, calibrate the volume knob, choose the mixer
+    /// control and write an initial configuration file
+    Setup {
+        /// Path of the configuration file to write
+        #[clap(default_value = "config.toml")]
+        path: String,
+    },
+    /// List the devices on the I2C bus, the GPIO chips and the ALSA cards,
+    /// and print a configuration for them
+    Detect,
+    /// Print every raw pin level change, debounced press and release, and
+    /// change of the analog controls, without triggering any actions
+    TestInputs,
+    /// Process a recording made with `--record` and print the resulting
+    /// presses, releases and analog values
+    Replay {
+        /// Path of the recording
+        path: String,
+    },
+    /// Print the recent log records of the running daemon
+    Logs {
+        /// Keep printing new records as they are written
+        #[clap(long)]
+        follow: bool,
+    },
+    /// Print the recent button presses, stream starts and stops, volume
+    /// changes and errors of the running daemon
+    Events,
+    /// Print the status report of the running daemon for remote debugging
+    Status,
+    /// Print or set the brightness of the dial lamp and the display of the
+    /// running daemon
+    Brightness {
+        /// Brightness in percent, or "auto" to follow the ambient light
+        value: Option<String>,
+    },
+    /// Print whether the running daemon is locked against small children, or
+    /// lock or unlock it
+    Lock {
+        /// "on" or "off"
+        value: Option<String>,
+    },
+    /// Print the listening time per station of today and the last days,
+    /// recorded with `--history-file`
+    History {
+        /// Number of days of the longer summary
+        #[clap(long, default_value = "7")]
+        days: usize,
+    },
+    /// Export or import the configuration and the state files
+    Config {
+        #[clap(subcommand)]
+        command: ConfigCommand,
+    },
+}
+
+#[derive(Clap, Debug, Clone)]
+enum ConfigCommand {
+    /// Write the configuration file, the cached remote configuration, the
+    /// state file and the history file to a gzipped tar archive
+    Export {
+        /// Path of the archive to write
+        archive: String,
+    },
+    /// Restore the files of an exported archive to their paths and restart
+    /// the service
+    Import {
+        /// Path of the archive
+        archive: String,
+    },
 }
 
 const LOOKUP_TABLE_VOL: [(u16, u16); 28] = [
@@ -54,36 +317,152 @@ const LOOKUP_TABLE_VOL: [(u16, u16); 28] = [
     (250, 26226),
     (280, 26227),
 ];
-const MIN_ANGLE: u16 = LOOKUP_TABLE_VOL[0].0;
-const MAX_ANGLE: u16 = LOOKUP_TABLE_VOL[LOOKUP_TABLE_VOL.len() - 1].0;
-const MIN_VALUE: u16 = LOOKUP_TABLE_VOL[0].1;
-const MAX_VALUE: u16 = LOOKUP_TABLE_VOL[LOOKUP_TABLE_VOL.len() - 1].1;
+/// The volume that is set when volumio is ready.
+const INITIAL_VOLUME: u8 = 30;
+/// The volume step in percent of the volume keys of input devices.
+const VOLUME_STEP: u8 = 5;
+
+/// Frequency of the software PWM used to dim outputs.
+const OUTPUT_PWM_FREQUENCY: f64 = 200.0;
+
+/// Frequency of the software PWM that drives the magic eye through a low-pass
+/// filter.
+const MAGIC_EYE_PWM_FREQUENCY: f64 = 1000.0;
+
+/// Interval in which the track title and the clock on the display are
+/// refreshed.
+const DISPLAY_REFRESH_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Interval between two checks of the CPU temperature and the supply voltage.
+const HEALTH_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Interval between two updates of the fan speed.
+const FAN_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Frequency of the software PWM that drives the fan.
+const FAN_PWM_FREQUENCY: f64 = 100.0;
+
+/// Interval between two checks whether a DX recording must be started or
+/// stopped.
+const DX_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Interval between two readings of the ambient light sensor.
+const AMBIENT_LIGHT_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Interval between two updates of the status file.
+const STATUS_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Interval between two checks whether the state file must be updated.
+const SNAPSHOT_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Interval between two checks of the network connection.
+const CONNECTIVITY_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Interval between two checks whether the radio is idle.
+const IDLE_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Interval between two checks of the schedule. Shorter than a minute, so that
+/// no minute is missed.
+const SCHEDULE_INTERVAL: Duration = Duration::from_secs(20);
+
+/// Interval between two checks whether the system clock is synchronized, so
+/// that the time of the real-time clock can be set.
+const RTC_CHECK_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Interval at which the real-time clock is set from the synchronized system
+/// clock.
+const RTC_SYNC_INTERVAL: Duration = Duration::from_secs(6 * 60 * 60);
+
+/// Interval between two checks whether a notification is due.
+const NOTIFY_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Interval at which listening time is added to the history.
+const HISTORY_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Minimum interval between two writes of the history file, to spare the SD
+/// card.
+const HISTORY_WRITE_INTERVAL: Duration = Duration::from_secs(10 * 60);
+
+/// Interval between two readings of the Wi-Fi link quality.
+const WIFI_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Link quality in percent below which a weak Wi-Fi signal is reported.
+const WEAK_WIFI_QUALITY: u8 = 30;
+
+/// Interval between two checks for plugged in or removed sound cards.
+const ALSA_HOTPLUG_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Interval between two readings of the battery voltage.
+const BATTERY_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Time after the last change of an analog control before the ADC falls back to the idle
+/// sampling interval.
+const ADC_IDLE_AFTER: Duration = Duration::from_secs(1);
 
 /// Convert a 12-bit input measurement to a value between 0 and 100.
-fn map_potentiometer_value(val: u16) -> u8 {
-    let angle = measurement_to_angle(val);
-    let percent = (angle - MIN_ANGLE) * 100 / (MAX_ANGLE - MIN_ANGLE);
+///
+/// The lookup table must have been validated with
+/// `config::validate_lookup_table`.
+fn map_potentiometer_value(table: &[(u16, u16)], val: u16) -> u8 {
+    let (min_angle, max_angle) = (table[0].0, table[table.len() - 1].0);
+    let angle = measurement_to_angle(table, val);
+    let percent = (angle - min_angle) as u32 * 100 / (max_angle - min_angle) as u32;
     assert!(percent <= 100);
     100 - percent as u8
 }
 
-fn measurement_to_angle(val: u16) -> u16 {
+/// Clamp volumes within the dead bands at both ends of the range to exactly
+/// 0% or 100%.
+fn apply_dead_bands(volume: u8, low: u8, high: u8) -> u8 {
+    if volume <= low {
+        0
+    } else if volume >= 100u8.saturating_sub(high) {
+        100
+    } else {
+        volume
+    }
+}
+
+/// Convert the value of the balance knob to a balance between -100 (left
+/// channel only) and 100 (right channel only).
+///
+/// Values within `dead_zone` percent of the centre are treated as centred.
+fn balance_from_value(value: u8, dead_zone: u8) -> i8 {
+    let offset = value.min(100) as i16 - 50;
+    let dead_zone = dead_zone.min(49) as i16;
+    if offset.abs() <= dead_zone {
+        0
+    } else {
+        (offset.signum() * (offset.abs() - dead_zone) * 100 / (50 - dead_zone)) as i8
+    }
+}
+
+/// Return the volumes of the left and the right channel for a balance.
+fn channel_volumes(volume: u8, balance: i8) -> (u8, u8) {
+    let attenuate = |amount: i8| (volume as i16 * (100 - amount.max(0) as i16) / 100) as u8;
+    (attenuate(balance), attenuate(balance.saturating_neg()))
+}
+
+fn measurement_to_angle(table: &[(u16, u16)], val: u16) -> u16 {
+    let (min_angle, min_value) = table[0];
+    let (max_angle, max_value) = table[table.len() - 1];
+
     // Lower and upper bounds
-    if val <= MIN_VALUE {
-        return MIN_ANGLE;
+    if val <= min_value {
+        return min_angle;
     }
-    if val >= MAX_VALUE {
-        return MAX_ANGLE;
+    if val >= max_value {
+        return max_angle;
     }
 
-    for i in 0..LOOKUP_TABLE_VOL.len() {
-        if LOOKUP_TABLE_VOL[i].1 == val {
+    for i in 0..table.len() {
+        if table[i].1 == val {
             // We found an exact match
-            return LOOKUP_TABLE_VOL[i].0;
-        } else if LOOKUP_TABLE_VOL[i].1 > val {
+            return table[i].0;
+        } else if table[i].1 > val {
             // The measurement is between the previous and the current entry.
-            let lower = LOOKUP_TABLE_VOL[i - 1];
-            let upper = LOOKUP_TABLE_VOL[i];
+            let lower = table[i - 1];
+            let upper = table[i];
 
             // Interpolate between the two angles.
             return ((upper.0 - lower.0) as u32 * (val - lower.1) as u32
@@ -91,62 +470,191 @@ fn measurement_to_angle(val: u16) -> u16 {
                 + lower.0 as u32) as u16;
         }
     }
-    MAX_ANGLE
+    max_angle
+}
+
+/// Return the intermediate volumes for a linear ramp from `from` to `to`.
+///
+/// The ramp consists of at most `max_steps` steps and always ends with `to`.
+/// If no volume has been applied yet, the target is applied directly.
+fn volume_ramp(from: Option<u8>, to: u8, max_steps: u32) -> Vec<u8> {
+    let from = match from {
+        Some(from) if from == to => return vec![],
+        Some(from) => from,
+        None => return vec![to],
+    };
+    let delta = to as i32 - from as i32;
+    let steps = std::cmp::min(delta.unsigned_abs(), std::cmp::max(max_steps, 1)) as i32;
+    (1..=steps)
+        .map(|step| (from as i32 + delta * step / steps) as u8)
+        .collect()
+}
+
+/// Emulates the power switch of the original volume knob.
+///
+/// The counter-clockwise end stop of the knob corresponds to the maximum angle
+/// of the lookup table. If the knob is turned into the last `degrees` before
+/// the end stop, the radio is switched off. It is only switched on again once the knob has been turned
+/// back by another `hysteresis` degrees.
+struct PowerSwitch {
+    off_angle: u16,
+    on_angle: u16,
+    off: bool,
+}
+
+impl PowerSwitch {
+    fn new(max_angle: u16, degrees: u16, hysteresis: u16) -> Self {
+        let off_angle = max_angle.saturating_sub(degrees);
+        Self {
+            off_angle,
+            on_angle: off_angle.saturating_sub(hysteresis),
+            off: false,
+        }
+    }
+
+    /// Update the switch with the current knob angle.
+    ///
+    /// Returns `Some(true)` if the radio was just switched off and
+    /// `Some(false)` if it was just switched on again.
+    fn update(&mut self, angle: u16) -> Option<bool> {
+        if !self.off && angle >= self.off_angle {
+            self.off = true;
+            Some(true)
+        } else if self.off && angle < self.on_angle {
+            self.off = false;
+            Some(false)
+        } else {
+            None
+        }
+    }
+}
+
+/// Whether commands with side effects are only logged instead of run.
+static DRY_RUN: AtomicBool = AtomicBool::new(false);
+
+/// Commands that were only logged in dry-run mode, checked by the scenario
+/// tests.
+#[cfg(test)]
+static SKIPPED_COMMANDS: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+/// Run commands with side effects (e.g. changing the volume, starting
+/// playback or shutting down).
+trait Execute {
+    /// Run the command and wait for it to finish. In dry-run mode, the
+    /// command is only logged and reported as successful.
+    fn execute(&mut self) -> io::Result<ExitStatus>;
+}
+
+impl Execute for Command {
+    fn execute(&mut self) -> io::Result<ExitStatus> {
+        if DRY_RUN.load(Ordering::SeqCst) {
+            info!("dry_run", "Not running {:?}", self);
+            #[cfg(test)]
+            SKIPPED_COMMANDS.lock().unwrap().push(format!("{:?}", self));
+            return Ok(ExitStatus::from_raw(0));
+        }
+        self.status()
+    }
 }
 
 /// Wait for volumio to be started.
-fn wait_for_volumio(cmd: &str) {
+fn wait_for_volumio(cmd: &str, volume: u8) {
     loop {
-        // To test whether volumio is working, try setting the volume to "30".
+        // To test whether volumio is working, try setting the initial volume.
         let status_res = Command::new(cmd)
             .arg("volume")
-            .arg("30")
+            .arg(volume.to_string())
             .stdout(Stdio::null())
             .stderr(Stdio::null())
-            .status();
+            .execute();
         match status_res {
             Ok(status) if status.success() => {
-                println!("Volumio is ready!");
+                info!("player", "Volumio is ready!");
                 return;
             },
-            Ok(status) => eprintln!("Waiting for volumio, exit status {}", status),
-            Err(e) => eprintln!("Waiting for volumio, {}", e),
+            Ok(status) => info!("player", { exit_status: status }, "Waiting for volumio, exit status {}", status),
+            Err(e) => info!("player", "Waiting for volumio, {}", e),
         };
         std::thread::sleep(std::time::Duration::from_secs(1));
     }
 }
 
 /// Set the volume using the volumio command with the specified name.
-fn set_volume(cmd: &str, volume: u8) {
+///
+/// Returns whether the volume was set.
+fn set_volume(cmd: &str, volume: u8) -> bool {
     // Clamp volume to 0-100
     let volume = std::cmp::min(volume, 100);
 
     // Set volume
     let status_res = Command::new(cmd)
         .arg("volume")
-        .arg(&volume.to_string())
+        .arg(volume.to_string())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .execute();
+    match status_res {
+        Ok(status) if status.success() => {
+            info!("player", { volume: volume }, "Set volume to {}%", volume);
+            return true;
+        },
+        Ok(status) => error!("player", { exit_status: status }, "Exit status {} when setting volume", status),
+        Err(e) => error!("player", "Could not set volume: {}", e),
+    };
+    false
+}
+
+/// Mute or unmute the output using the volumio command with the specified name.
+fn set_mute(cmd: &str, muted: bool) {
+    let action = if muted { "mute" } else { "unmute" };
+    let status_res = Command::new(cmd)
+        .arg("volume")
+        .arg(action)
         .stdout(Stdio::null())
         .stderr(Stdio::null())
-        .status();
+        .execute();
+    match status_res {
+        Ok(status) if status.success() => info!("player", "Output {}d", action),
+        Ok(status) => error!("player", { exit_status: status }, "Exit status {} when trying to {}", status, action),
+        Err(e) => error!("player", "Could not {}: {}", action, e),
+    };
+}
+
+/// Pause or resume playback.
+fn toggle_playback(cmd: &str) {
+    let status_res = Command::new(cmd).arg("toggle").stdout(Stdio::null()).stderr(Stdio::null()).execute();
     match status_res {
-        Ok(status) if status.success() => println!("Set volume to {}%", volume),
-        Ok(status) => eprintln!("Error: Exit status {} when setting volume", status),
-        Err(e) => eprintln!("Error: Could not set volume: {}", e),
+        Ok(status) if status.success() => info!("player", "Toggled playback"),
+        Ok(status) => error!("player", { exit_status: status }, "Exit status {} when toggling playback", status),
+        Err(e) => error!("player", "Could not toggle playback: {}", e),
     };
 }
 
 /// Play a playlist through the API.
-fn play_playlist(name: &str) {
+///
+/// Returns whether the playlist was started.
+fn play_playlist(name: &str) -> bool {
     let status_res = Command::new("/usr/bin/curl")
         .arg(format!("http://127.0.0.1:3000/api/v1/commands/?cmd=playplaylist&name={}", name))
         .stdout(Stdio::null())
         .stderr(Stdio::null())
-        .status();
+        .execute();
     match status_res {
-        Ok(status) if status.success() => println!("Started playlist {}", name),
-        Ok(status) => eprintln!("Error: Exit status {} when starting playlist {}", status, name),
-        Err(e) => eprintln!("Error: Could not play playlist {}: {}", name, e),
+        Ok(status) if status.success() => {
+            info!("player", { playlist: name }, "Started playlist {}", name);
+            events::record(EventKind::Play, name);
+            return true;
+        },
+        Ok(status) => error!(
+            "player",
+            { playlist: name, exit_status: status },
+            "Exit status {} when starting playlist {}",
+            status,
+            name
+        ),
+        Err(e) => error!("player", { playlist: name }, "Could not play playlist {}: {}", name, e),
     };
+    false
 }
 
 /// Stop playback.
@@ -155,223 +663,2892 @@ fn stop_playback() {
         .arg("http://127.0.0.1:3000/api/v1/commands/?cmd=stop")
         .stdout(Stdio::null())
         .stderr(Stdio::null())
-        .status();
+        .execute();
+    match status_res {
+        Ok(status) if status.success() => {
+            info!("player", "Stopped playback");
+            events::record(EventKind::Stop, "");
+        },
+        Ok(status) => error!("player", { exit_status: status }, "Exit status {} when stopping playback", status),
+        Err(e) => error!("player", "Could not stop playback: {}", e),
+    };
+}
+
+/// Return the artist and title of the status of volumio.
+fn now_playing(status: &str) -> Option<String> {
+    let title = json_string(status, "title").filter(|title| !title.is_empty())?;
+    match json_string(status, "artist") {
+        Some(artist) if !artist.is_empty() => Some(format!("{} - {}", artist, title)),
+        _ => Some(title),
+    }
+}
+
+/// Skip to the next track of the playlist.
+fn play_next(cmd: &str) -> bool {
+    let status_res = Command::new(cmd).arg("next").stdout(Stdio::null()).stderr(Stdio::null()).execute();
+    match status_res {
+        Ok(status) if status.success() => return true,
+        Ok(status) => error!("player", { exit_status: status }, "Exit status {} when skipping the stream", status),
+        Err(e) => error!("player", "Could not skip the stream: {}", e),
+    };
+    false
+}
+
+/// Return the JSON status printed by volumio.
+fn volumio_status(cmd: &str) -> Option<String> {
+    let output_res = Command::new(cmd).arg("status").stderr(Stdio::null()).output();
+    match output_res {
+        Ok(output) if output.status.success() => Some(String::from_utf8_lossy(&output.stdout).into_owned()),
+        Ok(output) => {
+            error!("player", { exit_status: output.status }, "Exit status {} when querying status", output.status);
+            None
+        },
+        Err(e) => {
+            error!("player", "Could not query status: {}", e);
+            None
+        },
+    }
+}
+
+/// Extract the string value of a top level key from a JSON object.
+///
+/// This is just enough JSON to read the status printed by volumio.
+fn json_string(json: &str, key: &str) -> Option<String> {
+    let start = json.find(&format!("\"{}\"", key))? + key.len() + 2;
+    let rest = json[start..].trim_start().strip_prefix(':')?.trim_start().strip_prefix('"')?;
+    let mut value = String::new();
+    let mut chars = rest.chars();
+    loop {
+        match chars.next()? {
+            '"' => return Some(value),
+            '\\' => match chars.next()? {
+                'n' => value.push('\n'),
+                't' => value.push('\t'),
+                'u' => {
+                    let code: String = chars.by_ref().take(4).collect();
+                    let c = u32::from_str_radix(&code, 16).ok().and_then(char::from_u32);
+                    value.push(c.unwrap_or('?'));
+                },
+                c => value.push(c),
+            },
+            c => value.push(c),
+        }
+    }
+}
+
+/// Extract the integer value of a top level key from a JSON object.
+fn json_integer(json: &str, key: &str) -> Option<i64> {
+    let start = json.find(&format!("\"{}\"", key))? + key.len() + 2;
+    let rest = json[start..].trim_start().strip_prefix(':')?.trim_start();
+    let end = rest
+        .char_indices()
+        .find(|(i, c)| !(c.is_ascii_digit() || (*i == 0 && *c == '-')))
+        .map_or(rest.len(), |(i, _)| i);
+    rest[..end].parse().ok()
+}
+
+/// Return the local time, formatted as HH:MM.
+fn local_time() -> String {
+    match Command::new("date").arg("+%H:%M").stderr(Stdio::null()).output() {
+        Ok(output) if output.status.success() => String::from_utf8_lossy(&output.stdout).trim().to_string(),
+        _ => String::new(),
+    }
+}
+
+/// Return the local date in the configured language, e.g. "Fri 16.10.".
+fn local_date() -> String {
+    match Command::new("date").arg("+%u %d.%m.").stderr(Stdio::null()).output() {
+        Ok(output) if output.status.success() => format_date(&String::from_utf8_lossy(&output.stdout)),
+        _ => String::new(),
+    }
+}
+
+/// Replace the weekday number in the output of `date +"%u %d.%m."` with its
+/// abbreviation.
+fn format_date(output: &str) -> String {
+    let output = output.trim();
+    match output.split_once(' ') {
+        Some((weekday, date)) => match weekday.parse().ok().and_then(Weekday::from_iso) {
+            Some(weekday) => format!("{} {}", tr(Message::Weekday(weekday)), date),
+            None => date.to_string(),
+        },
+        None => output.to_string(),
+    }
+}
+
+/// Return the local weekday and the minutes after midnight.
+fn local_weekday_time() -> Option<(Weekday, u16)> {
+    match Command::new("date").arg("+%u %H:%M").stderr(Stdio::null()).output() {
+        Ok(output) if output.status.success() => parse_weekday_time(&String::from_utf8_lossy(&output.stdout)),
+        _ => None,
+    }
+}
+
+/// Parse the output of `date +"%u %H:%M"`.
+fn parse_weekday_time(output: &str) -> Option<(Weekday, u16)> {
+    let (weekday, time) = output.trim().split_once(' ')?;
+    let (hours, minutes) = time.split_once(':')?;
+    let minutes = hours.parse::<u16>().ok()? * 60 + minutes.parse::<u16>().ok()?;
+    Some((Weekday::from_iso(weekday.parse().ok()?)?, minutes))
+}
+
+/// Set the system clock to seconds since the Unix epoch.
+///
+/// Returns whether the clock was set.
+fn set_system_time(timestamp: u64) -> bool {
+    let status_res = Command::new("/usr/bin/sudo")
+        .arg("date")
+        .arg("--utc")
+        .arg("--set")
+        .arg(format!("@{}", timestamp))
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .execute();
+    match status_res {
+        Ok(status) if status.success() => {
+            info!("rtc", { timestamp: timestamp }, "Set the system clock");
+            return true;
+        },
+        Ok(status) => error!("rtc", { exit_status: status }, "Exit status {} when setting the system clock", status),
+        Err(e) => error!("rtc", "Could not set the system clock: {}", e),
+    };
+    false
+}
+
+/// Return the entry of the schedule that starts playback next, within a week.
+fn next_alarm(schedule: &[ScheduleEntry], weekday: Weekday, minutes: u16) -> Option<&ScheduleEntry> {
+    const WEEK: u32 = 7 * 24 * 60;
+    let now = u32::from(weekday.iso() - 1) * 24 * 60 + u32::from(minutes);
+    schedule
+        .iter()
+        .filter(|entry| entry.action == ScheduledAction::Play)
+        .filter_map(|entry| {
+            let time = u32::from(entry.minutes()?);
+            (1..=7)
+                .filter_map(Weekday::from_iso)
+                .filter(|day| entry.days.is_empty() || entry.days.contains(day))
+                .map(|day| (u32::from(day.iso() - 1) * 24 * 60 + time + WEEK - now) % WEEK)
+                .filter(|until| *until > 0)
+                .min()
+                .map(|until| (until, entry))
+        })
+        .min_by_key(|(until, _)| *until)
+        .map(|(_, entry)| entry)
+}
+
+/// Run a shell command from the configuration.
+fn run_shell_command(command: &str) {
+    let status_res = Command::new("/bin/sh")
+        .arg("-c")
+        .arg(command)
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .execute();
     match status_res {
-        Ok(status) if status.success() => println!("Stopped playback"),
-        Ok(status) => eprintln!("Error: Exit status {} when stopping playback", status),
-        Err(e) => eprintln!("Error: Could not stop playback: {}", e),
+        Ok(status) if status.success() => info!("system", "Ran command \"{}\"", command),
+        Ok(status) => error!("system", { exit_status: status }, "Exit status {} when running \"{}\"", status, command),
+        Err(e) => error!("system", "Could not run \"{}\": {}", command, e),
     };
 }
 
 /// Shut down the system.
 fn shutdown() {
+    persist::flush();
     let status_res = Command::new("/usr/bin/sudo")
         .arg("shutdown")
         .arg("now")
         .stdout(Stdio::null())
         .stderr(Stdio::null())
-        .status();
+        .execute();
     match status_res {
-        Ok(status) if status.success() => println!("Shutting down"),
-        Ok(status) => eprintln!("Error: Exit status {} when shutting down", status),
-        Err(e) => eprintln!("Error: Could not shut down: {}", e),
+        Ok(status) if status.success() => {
+            info!("system", "Shutting down");
+            events::record(EventKind::Shutdown, "");
+        },
+        Ok(status) => error!("system", { exit_status: status }, "Exit status {} when shutting down", status),
+        Err(e) => error!("system", "Could not shut down: {}", e),
     };
 }
 
-/// GPIO input pins.
-struct GpioPins {
-    aus: InputPin,
-    tonabn: InputPin,
-    ukw: InputPin,
-    kurz: InputPin,
-    mittel: InputPin,
-    lang: InputPin,
+/// Return the profile whose jumper pulls its GPIO pin low.
+fn jumper_profile(jumpers: &HashMap<String, u8>, gpio: &GpioConfig) -> Option<String> {
+    if jumpers.is_empty() {
+        return None;
+    }
+    let gpio = match Gpio::open(gpio) {
+        Ok(gpio) => gpio,
+        Err(e) => {
+            error!("config", "Could not read profile jumpers: {}", e);
+            return None;
+        },
+    };
+    let mut jumpers: Vec<_> = jumpers.iter().collect();
+    jumpers.sort();
+    jumpers
+        .into_iter()
+        .find(|(_, pin)| gpio.input(**pin).is_ok_and(|pin| pin.read() == Level::Low))
+        .map(|(profile, _)| profile.clone())
 }
 
-type Repetitions = Repeat16;
-
-/// A debouncer for every input pin.
-struct Measurements {
-    aus: DebouncerStateful<u16, Repetitions>,
-    tonabn: DebouncerStateful<u16, Repetitions>,
-    ukw: DebouncerStateful<u16, Repetitions>,
-    kurz: DebouncerStateful<u16, Repetitions>,
-    mittel: DebouncerStateful<u16, Repetitions>,
-    lang: DebouncerStateful<u16, Repetitions>,
+/// State shared between the threads.
+#[derive(Default)]
+struct SharedState {
+    /// Whether volumio is ready.
+    ready: AtomicBool,
+    /// Whether the volume knob is turned into the "off" position.
+    switched_off: AtomicBool,
+    /// Whether the line output is used instead of the speakers.
+    line_out: AtomicBool,
+    /// Whether the output was muted with the mute gesture.
+    ///
+    /// While muted, the volume knob is ignored. On unmute, the ADC thread
+    /// restores the volume from the current knob position.
+    muted: AtomicBool,
+    /// The name of the band button that is currently pressed.
+    band: Mutex<Option<String>>,
+    /// The playlist that is currently selected.
+    playlist: Mutex<Option<String>>,
+    /// The volume that was last set.
+    ///
+    /// This is the volume selected with the volume control. The volume of the
+    /// output may differ, e.g. while headphones are plugged in.
+    volume: AtomicU8,
+    /// The maximum volume while headphones are plugged in.
+    headphones: Mutex<Option<u8>>,
+    /// The maximum volume of the selected band.
+    band_max_volume: Mutex<Option<u8>>,
+    /// The maximum volume of the selected station.
+    station_max_volume: Mutex<Option<u8>>,
+    /// Names and settings of the stations.
+    stations: Vec<Station>,
+    /// The maximum volume while the volume is ducked, e.g. because the
+    /// doorbell rang.
+    duck_volume: Mutex<Option<u8>>,
+    /// The ALSA mixer that is used instead of volumio to set the volume.
+    mixer: Option<Mixer>,
+    /// Balance between the left (-100) and the right (100) channel.
+    balance: AtomicI8,
+    /// The time the last playlist was started.
+    playback_started: Mutex<Option<Instant>>,
+    /// The time of the last playback error.
+    playback_error: Mutex<Option<Instant>>,
+    /// Whether the stream that was started last is not playing yet.
+    buffering: AtomicBool,
+    /// Number of streams that started playing.
+    streams_started: AtomicU32,
+    /// Number of streams that were skipped because they didn't start in time.
+    streams_skipped: AtomicU32,
+    /// Time the last stream took from being started until it played.
+    stream_latency: Mutex<Option<Duration>>,
+    /// How accurately the tuning dial points at a station, in percent.
+    tuning_accuracy: AtomicU8,
+    /// The weather, once fetched.
+    weather: Mutex<Option<Weather>>,
+    /// Link quality of the Wi-Fi connection in percent, if connected.
+    wifi_quality: Mutex<Option<u8>>,
+    /// The state of the network connection, once checked.
+    connectivity: Mutex<Option<Connectivity>>,
+    /// Whether no stream of the last playlist started although the network
+    /// is online.
+    station_down: AtomicBool,
+    /// Why the last stream that didn't start can't be played, if its
+    /// content type tells.
+    stream_problem: Mutex<Option<String>>,
+    /// Volume in percent that is not exceeded while the radio is locked, or
+    /// `None` if it isn't locked. Band buttons are ignored while locked.
+    lock_volume: Mutex<Option<u8>>,
+    /// Press and bounce counts of every button.
+    switch_wear: Mutex<Vec<(String, SwitchWear)>>,
+    /// Whether the analog controls are polled less often, because the CPU is
+    /// throttled or too hot.
+    slowed_down: AtomicBool,
+    /// Whether the system clock was set from the real-time clock.
+    clock_from_rtc: AtomicBool,
+    /// The time a control was last used.
+    last_input: Mutex<Option<Instant>>,
+    /// Whether the radio hasn't been used for a while.
+    idle: AtomicBool,
+    /// The time every thread last reported being alive.
+    heartbeats: Mutex<HashMap<&'static str, Instant>>,
+    /// The time the daemon was started.
+    started: Option<Instant>,
+    /// Hash of the configuration file, if it was read from one.
+    config_hash: Option<u64>,
+    /// The time the ADC was last read successfully.
+    adc_last_read: Mutex<Option<Instant>>,
+    /// Number of failed ADC reads.
+    adc_errors: AtomicU32,
+    /// Number of times an ADC was re-initialized after failed reads.
+    adc_recoveries: AtomicU32,
+    /// Duty cycle of the fan in percent, once it was set.
+    fan_duty: Mutex<Option<u8>>,
+    /// Brightness in percent following the ambient light, once measured.
+    ambient_brightness: Mutex<Option<u8>>,
+    /// Brightness in percent set through the control socket, which replaces
+    /// the one following the ambient light.
+    brightness_override: Mutex<Option<u8>>,
+    /// Sends notifications about failures, if configured.
+    notifier: Option<Notifier>,
+    /// Records the raw input samples, if set.
+    recorder: Option<Arc<Recorder>>,
+    /// The time at which the sleep timer stops playback.
+    sleep_deadline: Mutex<Option<Instant>>,
+    /// The playlist that was still playing when the daemon was restarted.
+    ///
+    /// Selecting it again right after startup does not restart playback.
+    resumed_playlist: Mutex<Option<String>>,
 }
 
-struct GpioPinState {
-    pins: GpioPins,
-    measurements: Measurements,
-}
+impl SharedState {
+    /// Return whether the system clock can be trusted: It is synchronized
+    /// with NTP or was set from the real-time clock.
+    fn clock_trusted(&self) -> bool {
+        self.clock_from_rtc.load(Ordering::SeqCst) || rtc::clock_synchronized()
+    }
 
-#[derive(Debug, PartialEq, Eq)]
-enum Button {
-    Aus,
-    Tonabnehmer,
-    Ukw,
-    Kurz,
-    Mittel,
-    Lang,
-}
+    /// Report that a control was used, which ends the idle state.
+    fn touch(&self) {
+        *self.last_input.lock().unwrap() = Some(Instant::now());
+        if self.idle.swap(false, Ordering::SeqCst) {
+            info!("idle", "Waking up");
+        }
+    }
 
-impl GpioPinState {
-    fn new(pins: GpioPins) -> Self {
-        Self {
-            pins,
-            measurements: Measurements {
-                aus: debounce_stateful_16(false),
-                tonabn: debounce_stateful_16(false),
-                ukw: debounce_stateful_16(false),
-                kurz: debounce_stateful_16(false),
-                mittel: debounce_stateful_16(false),
-                lang: debounce_stateful_16(false),
-            },
+    /// Send a notification about a failure, if configured.
+    fn notify(&self, message: &str) {
+        if let Some(notifier) = &self.notifier {
+            notifier.send(message);
         }
     }
 
-    /// Update state by reading all inputs.
-    fn update(&mut self) -> (Vec<Button>, Vec<Button>) {
-        let mut pressed = vec![];
-        let mut released = vec![];
+    /// Report that a thread is alive.
+    fn heartbeat(&self, thread: &'static str) {
+        self.heartbeats.lock().unwrap().insert(thread, Instant::now());
+    }
+
+    /// Collect the status report for remote debugging.
+    fn status(&self, cmd: &str) -> Status {
+        let now = Instant::now();
+        let mut threads: Vec<_> = self
+            .heartbeats
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(thread, heartbeat)| (*thread, now.duration_since(*heartbeat)))
+            .collect();
+        threads.sort();
+        let player_status = volumio_status(cmd);
+        Status {
+            uptime: self.started.map_or(Duration::ZERO, |started| now.duration_since(started)),
+            threads,
+            adc_last_read: self.adc_last_read.lock().unwrap().map(|read| now.duration_since(read)),
+            adc_errors: self.adc_errors.load(Ordering::SeqCst),
+            adc_recoveries: self.adc_recoveries.load(Ordering::SeqCst),
+            fan_duty: *self.fan_duty.lock().unwrap(),
+            player: player_status.as_deref().and_then(|status| json_string(status, "status")),
+            playing_since: self.playback_started.lock().unwrap().map(|started| now.duration_since(started)),
+            bitrate: player_status
+                .as_deref()
+                .and_then(|status| json_string(status, "bitrate"))
+                .filter(|bitrate| !bitrate.is_empty()),
+            streams_started: self.streams_started.load(Ordering::SeqCst),
+            streams_skipped: self.streams_skipped.load(Ordering::SeqCst),
+            stream_latency: *self.stream_latency.lock().unwrap(),
+            wifi_quality: *self.wifi_quality.lock().unwrap(),
+            network: *self.connectivity.lock().unwrap(),
+            station_down: self.station_down.load(Ordering::SeqCst),
+            stream_problem: self.stream_problem.lock().unwrap().clone(),
+            switches: self.switch_wear.lock().unwrap().clone(),
+            config_hash: self.config_hash,
+        }
+    }
 
-        macro_rules! process_pin {
-            ($pin:expr, $measurement:expr, $button:expr, $inverted:expr) => {
-                match $measurement.update($pin.read() == Level::Low) {
-                    Some(Edge::Rising) => if $inverted { released.push($button) } else { pressed.push($button) },
-                    Some(Edge::Falling) => if $inverted { pressed.push($button) } else { released.push($button) },
-                    None => {}
+    /// Record the result of an ADC read, logging errors.
+    fn adc_read(&self, adc: Option<&str>, channel: Channel, result: Result<i16, String>) -> Option<i16> {
+        match result {
+            Ok(raw) => {
+                *self.adc_last_read.lock().unwrap() = Some(Instant::now());
+                if let Some(recorder) = &self.recorder {
+                    let adc = adc.map(str::to_string);
+                    recorder.record(Sample::Adc { adc, channel, raw });
                 }
-            };
+                Some(raw)
+            },
+            Err(e) => {
+                self.adc_errors.fetch_add(1, Ordering::SeqCst);
+                let channel = channel_name(adc, channel);
+                error!("adc", { channel: channel }, "Could not read ADC channel {}: {}", channel, e);
+                None
+            },
         }
+    }
 
-        process_pin!(self.pins.aus, self.measurements.aus, Button::Aus, true);
-        process_pin!(self.pins.tonabn, self.measurements.tonabn, Button::Tonabnehmer, false);
-        process_pin!(self.pins.ukw, self.measurements.ukw, Button::Ukw, false);
-        process_pin!(self.pins.kurz, self.measurements.kurz, Button::Kurz, false);
-        process_pin!(self.pins.mittel, self.measurements.mittel, Button::Mittel, false);
-        process_pin!(self.pins.lang, self.measurements.lang, Button::Lang, false);
+    /// Play a playlist, remembering playback errors.
+    fn play(&self, playlist: &str) {
+        if play_playlist(playlist) {
+            *self.playback_started.lock().unwrap() = Some(Instant::now());
+            self.buffering.store(true, Ordering::SeqCst);
+            self.station_down.store(false, Ordering::SeqCst);
+        } else {
+            *self.playback_error.lock().unwrap() = Some(Instant::now());
+        }
+    }
 
-        (pressed, released)
+    /// Call a function with the sink whose volume is set: the ALSA mixer if
+    /// one is configured, volumio otherwise.
+    fn with_volume_sink<R>(&self, cmd: &str, f: impl FnOnce(&dyn VolumeSink) -> R) -> R {
+        match &self.mixer {
+            Some(mixer) => f(mixer),
+            None => f(&Volumio(cmd)),
+        }
     }
-}
 
-type Adc = Ads1x1x<
-    ads1x1x::interface::I2cInterface<linux_embedded_hal::I2cdev>,
-    ads1x1x::ic::Ads1115,
-    ads1x1x::ic::Resolution16Bit,
-    ads1x1x::mode::OneShot,
->;
+    /// Set the volume and remember it.
+    fn set_volume(&self, cmd: &str, volume: u8) {
+        let output_volume = self.output_volume(volume);
+        let volume_set = self.with_volume_sink(cmd, |sink| match self.balance.load(Ordering::SeqCst) {
+            0 => sink.set_volume(output_volume),
+            balance => {
+                let (left, right) = channel_volumes(output_volume, balance);
+                sink.set_channel_volumes(left, right)
+            },
+        });
+        if volume_set && self.volume.swap(volume, Ordering::SeqCst) != volume {
+            events::record(EventKind::Volume, format!("{}%", volume));
+        }
+    }
 
-fn adc_loop(mut adc: Adc, opts: Opts) -> ! {
-    // Do measurement
-    loop {
-        // Analog input 0 ("Lautstärke")
-        let a0 = block!(adc.read(&mut channel::SingleA0)).unwrap();
-        let volume = map_potentiometer_value(a0 as u16);
+    /// Return the volume of the output for the volume selected with the
+    /// volume control.
+    fn output_volume(&self, volume: u8) -> u8 {
+        let max_volumes = [
+            *self.headphones.lock().unwrap(),
+            *self.band_max_volume.lock().unwrap(),
+            *self.station_max_volume.lock().unwrap(),
+        ];
+        let volume = max_volumes
+            .iter()
+            .flatten()
+            .fold(volume.min(100), |volume, max_volume| (volume as u16 * *max_volume as u16 / 100) as u8);
+        // Unlike the other maximum volumes, ducking and the lock limit the
+        // volume instead of scaling it
+        let limits = [*self.duck_volume.lock().unwrap(), *self.lock_volume.lock().unwrap()];
+        limits.iter().flatten().fold(volume, |volume, limit| volume.min(*limit))
+    }
 
-        // Analog input 1 ("Klangfarbe")
-        let a1 = block!(adc.read(&mut channel::SingleA1)).unwrap();
+    /// Return whether the radio is locked against small children.
+    fn is_locked(&self) -> bool {
+        self.lock_volume.lock().unwrap().is_some()
+    }
 
-        // Print values
-        println!("a0={} a1={} vol={}", a0, a1, volume);
+    /// Lock or unlock the radio.
+    fn set_locked(&self, cmd: &str, lock: &LockConfig, locked: bool) {
+        if locked {
+            info!("lock", "Locked the radio, limiting the volume to {}%", lock.max_volume);
+        } else {
+            info!("lock", "Unlocked the radio");
+        }
+        self.set_max_volume(cmd, &self.lock_volume, Some(lock.max_volume).filter(|_| locked));
+    }
 
-        // Set volume
-        set_volume(&opts.volumio_command, volume);
+    /// Return the state shown on the outputs.
+    fn radio_state(&self) -> RadioState {
+        RadioState {
+            ready: self.ready.load(Ordering::SeqCst),
+            playing: self.is_playing(),
+            volume: self.volume.load(Ordering::SeqCst),
+            since_error: self.playback_error.lock().unwrap().map(|error| error.elapsed()),
+            buffering: self.buffering.load(Ordering::SeqCst),
+            tuning_accuracy: self.tuning_accuracy.load(Ordering::SeqCst),
+            wifi_quality: *self.wifi_quality.lock().unwrap(),
+            idle: self.idle.load(Ordering::SeqCst),
+            brightness: self.brightness().unwrap_or(100),
+        }
+    }
 
-        // Sleep for some milliseconds
-        thread::sleep(Duration::from_millis(250));
+    /// Return the brightness of the dial lamp and the display in percent, if
+    /// they are dimmed.
+    fn brightness(&self) -> Option<u8> {
+        self.brightness_override.lock().unwrap().or(*self.ambient_brightness.lock().unwrap())
     }
-}
 
-fn gpio_loop(pins: GpioPins, opts: Opts) -> ! {
-    let mut state = GpioPinState::new(pins);
-    loop {
-        // Update measurements
-        let (pressed, released) = state.update();
+    /// Return whether a playlist is playing.
+    fn is_playing(&self) -> bool {
+        !self.switched_off.load(Ordering::SeqCst) && self.playlist.lock().unwrap().is_some()
+    }
 
-        if !pressed.is_empty() {
-            println!("Pressed: {:?}", pressed);
+    /// Return the state that is restored after a restart.
+    fn snapshot(&self) -> Snapshot {
+        let sleep_timer_until = self.sleep_deadline.lock().unwrap().map(|deadline| {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            (SystemTime::now() + remaining).duration_since(UNIX_EPOCH).unwrap_or_default().as_secs()
+        });
+        Snapshot {
+            playlist: self.playlist.lock().unwrap().clone(),
+            volume: self.volume.load(Ordering::SeqCst),
+            muted: self.muted.load(Ordering::SeqCst),
+            line_out: self.line_out.load(Ordering::SeqCst),
+            locked: self.is_locked(),
+            sleep_timer_until,
+        }
+    }
 
-            match pressed[0] {
-                Button::Aus => shutdown(),
-                Button::Tonabnehmer => play_playlist("jazz"),
-                Button::Ukw => play_playlist("mellow"),
-                Button::Kurz => play_playlist("world"),
-                Button::Mittel => play_playlist("rockblues"),
-                Button::Lang => play_playlist("progrock"),
-            }
+    /// Restore the state after a restart, once volumio is ready.
+    fn restore(&self, cmd: &str, snapshot: &Snapshot, config: &Config) {
+        if let Some(line_out) = &config.line_out {
+            self.set_line_out(line_out, snapshot.line_out);
         }
-        if !released.is_empty() {
-            println!("Released: {:?}", released);
-            if pressed.is_empty() {
-                stop_playback();
+        // Restarting must not be a way around the lock
+        if snapshot.locked {
+            self.set_locked(cmd, &config.lock, true);
+        }
+        // The band keys select the playlist again. If it is still playing,
+        // playback continues without interruption.
+        if let Some(playlist) = &snapshot.playlist {
+            let player = volumio_status(cmd).and_then(|status| json_string(&status, "status"));
+            if player.as_deref() == Some("play") {
+                *self.resumed_playlist.lock().unwrap() = Some(playlist.clone());
             }
         }
+        if snapshot.muted {
+            self.toggle_mute(cmd);
+        }
+        if let Some(until) = snapshot.sleep_timer_until {
+            let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+            let remaining = Duration::from_secs(until.saturating_sub(now));
+            info!("state", "Restored the sleep timer, stopping playback in {} minutes", remaining.as_secs() / 60);
+            *self.sleep_deadline.lock().unwrap() = Some(Instant::now() + remaining);
+        }
+    }
 
-        // Sleep for 10 milliseconds.
-        // The debounce count is 16, that means that
-        // a signal must be stable for 160ms to trigger
-        // the interrupt.
-        thread::sleep(Duration::from_millis(10));
+    /// Return the configured station of a playlist.
+    fn station(&self, playlist: &str) -> Option<&Station> {
+        self.stations.iter().find(|station| station.id == playlist)
+    }
+
+    /// Return the name of the station of a playlist.
+    fn station_name(&self, playlist: &str) -> String {
+        self.station(playlist).map_or(playlist, Station::name).to_string()
+    }
+
+    /// Set a maximum volume and apply it, if it changed.
+    fn set_max_volume(&self, cmd: &str, max_volume: &Mutex<Option<u8>>, value: Option<u8>) {
+        let changed = {
+            let mut max_volume = max_volume.lock().unwrap();
+            let changed = *max_volume != value;
+            *max_volume = value;
+            changed
+        };
+        if changed && !self.muted.load(Ordering::SeqCst) && !self.switched_off.load(Ordering::SeqCst) {
+            self.set_volume(cmd, self.volume.load(Ordering::SeqCst));
+        }
+    }
+
+    /// Select a playlist and play it, unless the volume knob is switched off.
+    fn select_playlist(&self, cmd: &str, playlist: String) {
+        // Apply the maximum volume of the station before starting playback
+        let max_volume = self.station(&playlist).and_then(|station| station.max_volume);
+        self.set_max_volume(cmd, &self.station_max_volume, max_volume);
+
+        if self.resumed_playlist.lock().unwrap().take().as_ref() == Some(&playlist) {
+            info!("player", { playlist: playlist }, "Playlist {} is still playing", playlist);
+        } else if self.switched_off.load(Ordering::SeqCst) {
+            info!("player", { playlist: playlist }, "Volume knob is switched off, not starting playlist {}", playlist);
+        } else {
+            self.play(&playlist);
+        }
+        *self.playlist.lock().unwrap() = Some(playlist);
+    }
+
+    /// Select a band and play its playlist.
+    fn select_band(&self, cmd: &str, band: &str, config: &Config) {
+        *self.band.lock().unwrap() = Some(band.to_string());
+
+        // Apply the maximum volume of the band before starting playback
+        self.set_max_volume(cmd, &self.band_max_volume, config.band_max_volume(band));
+
+        // On bands with tuning stations, the tuning control selects the
+        // station.
+        if let Some(playlist) = config.band_playlist(band, local_weekday_time()) {
+            self.select_playlist(cmd, playlist.to_string());
+        } else if config.is_dx_band(band) {
+            // The recordings are played by the DX loop
+            *self.playlist.lock().unwrap() = None;
+            stop_playback();
+        }
+    }
+
+    /// Play a random station of the configuration, which doesn't belong to a
+    /// band.
+    fn play_random_station(&self, cmd: &str, config: &Config, shuffle: &mut dx::Shuffle) {
+        let stations = config.all_stations();
+        let playlist = match shuffle.pick(stations.len()) {
+            Some(index) => stations[index].to_string(),
+            None => {
+                warn!("player", "There are no stations to pick a random one from");
+                return;
+            },
+        };
+        info!("player", { playlist: playlist }, "Playing random station {}", self.station_name(&playlist));
+        *self.band.lock().unwrap() = None;
+        self.set_max_volume(cmd, &self.band_max_volume, None);
+        self.select_playlist(cmd, playlist);
+    }
+
+    /// Deselect the current band and stop playback.
+    fn stop(&self) {
+        *self.band.lock().unwrap() = None;
+        *self.playlist.lock().unwrap() = None;
+        *self.resumed_playlist.lock().unwrap() = None;
+        self.buffering.store(false, Ordering::SeqCst);
+        stop_playback();
+    }
+
+    /// Toggle the mute state.
+    ///
+    /// With an ALSA mixer, muting sets the volume to 0. It is restored by the
+    /// volume control on unmute.
+    fn toggle_mute(&self, cmd: &str) {
+        let muted = !self.muted.load(Ordering::SeqCst);
+        self.muted.store(muted, Ordering::SeqCst);
+        self.with_volume_sink(cmd, |sink| sink.set_mute(muted));
+    }
+
+    /// Switch between the speakers and the line output.
+    fn set_line_out(&self, line_out: &LineOut, enabled: bool) {
+        if enabled {
+            info!("line_out", "Switching to the line output");
+            run_shell_command(&line_out.line_out_command);
+        } else {
+            info!("line_out", "Switching to the speakers");
+            run_shell_command(&line_out.speakers_command);
+        }
+        self.line_out.store(enabled, Ordering::SeqCst);
+    }
+
+    /// Toggle between the speakers and the line output.
+    fn toggle_line_out(&self, line_out: Option<&LineOut>) {
+        if let Some(line_out) = line_out {
+            self.set_line_out(line_out, !self.line_out.load(Ordering::SeqCst));
+        }
+    }
+
+    /// Limit the volume while ducked, or lift the limit.
+    fn duck(&self, cmd: &str, duck_volume: Option<u8>) {
+        *self.duck_volume.lock().unwrap() = duck_volume;
+        if !self.muted.load(Ordering::SeqCst) && !self.switched_off.load(Ordering::SeqCst) {
+            self.set_volume(cmd, self.volume.load(Ordering::SeqCst));
+        }
+    }
+
+    /// Change the volume by one step, starting from the current volume, so
+    /// that the maximum volumes and limits (like the lock) apply.
+    fn step_volume(&self, cmd: &str, up: bool) {
+        if self.muted.load(Ordering::SeqCst) || self.switched_off.load(Ordering::SeqCst) {
+            return;
+        }
+        let volume = self.volume.load(Ordering::SeqCst);
+        let volume = if up { (volume + VOLUME_STEP).min(100) } else { volume.saturating_sub(VOLUME_STEP) };
+        self.set_volume(cmd, volume);
     }
 }
 
-fn main() {
-    let opts: Opts = Opts::parse();
+/// A button connected to a GPIO input pin.
+struct GpioInput {
+    name: String,
+    /// BCM number of the pin.
+    gpio: u8,
+    pin: Box<dyn DigitalInput>,
+    inverted: bool,
+    debouncer: Debouncer,
+    /// Press and bounce counts.
+    wear: SwitchWear,
+    /// Interval between two samples of the pin
+    interval: Duration,
+    next_sample: Instant,
+}
 
-    // Initialize ADC
-    let dev = I2cdev::new(&opts.i2c).unwrap();
-    let address = SlaveAddr::default();
-    let mut adc = Ads1x1x::new_ads1115(dev, address);
+struct GpioPinState {
+    inputs: Vec<GpioInput>,
+    /// Records every sample, if set.
+    recorder: Option<Arc<Recorder>>,
+    /// Buttons that became worn since this was last taken.
+    worn: Vec<String>,
+}
 
-    // Initialize GPIO
-    let gpio = Gpio::new().expect("Could not initialize GPIO");
-    let gpio_pins = GpioPins {
-        aus: gpio
-            .get(17)
-            .expect("Could not init GPIO pin 17")
-            .into_input_pullup(),
-        tonabn: gpio
-            .get(27)
-            .expect("Could not init GPIO pin 27")
-            .into_input_pullup(),
-        ukw: gpio
-            .get(22)
-            .expect("Could not init GPIO pin 22")
-            .into_input_pullup(),
-        kurz: gpio
-            .get(5)
-            .expect("Could not init GPIO pin 5")
-            .into_input_pullup(),
-        mittel: gpio
-            .get(6)
-            .expect("Could not init GPIO pin 6")
-            .into_input_pullup(),
-        lang: gpio
-            .get(13)
-            .expect("Could not init GPIO pin 13")
-            .into_input_pullup(),
-    };
+impl GpioPinState {
+    fn new(inputs: Vec<GpioInput>) -> Self {
+        Self {
+            inputs,
+            recorder: None,
+            worn: vec![],
+        }
+    }
 
-    // Configure PGA (gain)
-    if let Err(e) = adc.set_full_scale_range(FullScaleRange::Within4_096V) {
-        eprintln!("Could not set full scale range: {:?}", e);
-        exit(1);
+    /// Return the press and bounce counts of every button.
+    fn wear(&self) -> Vec<(String, SwitchWear)> {
+        self.inputs.iter().map(|input| (input.name.clone(), input.wear)).collect()
     }
 
-    // Configure sample rate
-    if let Err(e) = adc.set_data_rate(DataRate16Bit::Sps32) {
-        eprintln!("Warning: Could not set data rate: {:?}", e);
+    /// Return the names of the buttons whose pins currently read pressed.
+    fn held(&self) -> Vec<String> {
+        self.inputs
+            .iter()
+            .filter(|input| input.pin.is_low() != input.inverted)
+            .map(|input| input.name.clone())
+            .collect()
     }
 
-    // Wait for volumio
-    wait_for_volumio(&opts.volumio_command);
+    /// Return the interval in which the inputs must be polled.
+    fn poll_interval(&self) -> Duration {
+        self.inputs
+            .iter()
+            .map(|input| input.interval)
+            .min()
+            .unwrap_or_else(|| Duration::from_millis(10))
+    }
 
-    // Start threads
-    let opts_clone = opts.clone();
-    let adc_thread = thread::spawn(move || adc_loop(adc, opts_clone));
-    let gpio_thread = thread::spawn(move || gpio_loop(gpio_pins, opts));
+    /// Update state by reading all inputs that are due to be sampled.
+    ///
+    /// Returns the names of the pressed and the released buttons.
+    fn update(&mut self, now: Instant) -> (Vec<String>, Vec<String>) {
+        let mut pressed = vec![];
+        let mut released = vec![];
+
+        for input in &mut self.inputs {
+            if now < input.next_sample {
+                continue;
+            }
+            input.next_sample += input.interval;
+            if input.next_sample < now {
+                // Don't try to catch up after a stall
+                input.next_sample = now + input.interval;
+            }
+
+            let low = input.pin.is_low();
+            if let Some(recorder) = &self.recorder {
+                recorder.record(Sample::Gpio {
+                    button: input.name.clone(),
+                    low,
+                });
+            }
+            let edge = input.debouncer.update(low);
+            let is_press = matches!((edge, input.inverted), (Some(Edge::Rising), false) | (Some(Edge::Falling), true));
+            if input.wear.update(low, edge, is_press) {
+                warn!(
+                    "gpio",
+                    { button: input.name, bounce_rate: format!("{:.2}", input.wear.bounce_rate) },
+                    "Button {} bounces {:.1} times per press or release, its contacts may need cleaning",
+                    input.name,
+                    input.wear.bounce_rate
+                );
+                self.worn.push(input.name.clone());
+            }
+            match (edge, input.inverted) {
+                (Some(Edge::Rising), false) | (Some(Edge::Falling), true) => pressed.push(input.name.clone()),
+                (Some(Edge::Falling), false) | (Some(Edge::Rising), true) => released.push(input.name.clone()),
+                (None, _) => {},
+            }
+        }
+
+        (pressed, released)
+    }
+}
+
+/// Emulates the latching mechanism of the piano keys for buttons that are
+/// triggered by input devices.
+///
+/// Pressing a latching button releases the previously pressed one, pressing it
+/// again releases it. Buttons that don't latch are only pressed.
+#[derive(Default)]
+struct ButtonLatch {
+    latched: Option<String>,
+}
+
+impl ButtonLatch {
+    /// Press a button, returning the pressed and the released buttons.
+    fn press(&mut self, button: &str, latching: bool) -> (Vec<String>, Vec<String>) {
+        if !latching {
+            return (vec![button.to_string()], vec![]);
+        }
+        match self.latched.take() {
+            Some(latched) if latched == button => (vec![], vec![latched]),
+            Some(latched) => {
+                self.latched = Some(button.to_string());
+                (vec![button.to_string()], vec![latched])
+            },
+            None => {
+                self.latched = Some(button.to_string());
+                (vec![button.to_string()], vec![])
+            },
+        }
+    }
+}
+
+/// Ignores buttons that are held implausibly long, e.g. because a contact is
+/// stuck, until they are released.
+#[derive(Default)]
+struct StuckButtons {
+    /// The time at which every watched button was pressed.
+    held: HashMap<String, Instant>,
+    /// Buttons that are ignored until they are released.
+    stuck: HashSet<String>,
+}
+
+impl StuckButtons {
+    /// Ignore a button until it is released.
+    fn ignore(&mut self, button: &str) {
+        self.stuck.insert(button.to_string());
+    }
+
+    /// Remove the presses and releases of stuck buttons.
+    ///
+    /// Buttons held longer than their limit become stuck and are released.
+    /// Returns the names of the buttons that became stuck.
+    fn update(
+        &mut self,
+        pressed: &mut Vec<String>,
+        released: &mut Vec<String>,
+        now: Instant,
+        stuck_after: impl Fn(&str) -> Option<Duration>,
+    ) -> Vec<String> {
+        for button in released.iter() {
+            self.held.remove(button);
+        }
+        released.retain(|button| !self.stuck.remove(button));
+        pressed.retain(|button| !self.stuck.contains(button));
+        for button in pressed.iter() {
+            if stuck_after(button).is_some() {
+                self.held.insert(button.clone(), now);
+            }
+        }
+
+        let mut became_stuck: Vec<String> = self
+            .held
+            .iter()
+            .filter(|(button, since)| stuck_after(button).is_some_and(|after| now.duration_since(**since) >= after))
+            .map(|(button, _)| button.clone())
+            .collect();
+        became_stuck.sort();
+        for button in &became_stuck {
+            self.held.remove(button);
+            self.stuck.insert(button.clone());
+        }
+        released.extend(became_stuck.iter().cloned());
+        became_stuck
+    }
+}
+
+/// A change of the selected band.
+#[derive(Debug, PartialEq, Eq)]
+enum BandChange {
+    /// The band with the specified name was selected.
+    Select(String),
+    /// All band buttons were released, the specified band was deselected.
+    Release(String),
+    /// Two band buttons were held down together, a random station is played.
+    Lucky,
+}
+
+/// Decides which band is selected when band buttons are pressed and released.
+///
+/// The piano key mechanism can momentarily report two band buttons as pressed,
+/// or none while sliding from one key to the next. Of the band buttons that are
+/// held down, the most recently pressed one wins, and a change is only applied
+/// once the buttons have been stable for the settle time. When all band buttons
+/// are released, the band is only deselected after the (usually longer) stop
+/// grace period.
+///
+/// Holding two band buttons down together for the lucky time (unless it's 0)
+/// plays a random station, which stays on until all of them are released or
+/// another one is pressed.
+struct BandSelector {
+    settle: Duration,
+    stop_grace: Duration,
+    lucky_hold: Duration,
+    /// Band buttons that are held down, in the order they were pressed
+    held: Vec<String>,
+    selected: Option<String>,
+    deadline: Option<Instant>,
+    /// Since when two band buttons are held down together
+    together_since: Option<Instant>,
+    /// Whether a random station is played
+    lucky: bool,
+}
+
+impl BandSelector {
+    fn new(settle: Duration, stop_grace: Duration, lucky_hold: Duration) -> Self {
+        Self {
+            settle,
+            stop_grace,
+            lucky_hold,
+            held: vec![],
+            selected: None,
+            deadline: None,
+            together_since: None,
+            lucky: false,
+        }
+    }
+
+    fn press(&mut self, band: &str, now: Instant) {
+        self.held.retain(|held| held != band);
+        self.held.push(band.to_string());
+        self.deadline = Some(now + self.settle);
+        self.lucky = false;
+        if self.held.len() >= 2 {
+            self.together_since = Some(now);
+        }
+    }
+
+    fn release(&mut self, band: &str, now: Instant) {
+        self.held.retain(|held| held != band);
+        if self.held.len() < 2 {
+            self.together_since = None;
+        }
+        let delay = if self.held.is_empty() {
+            self.settle.max(self.stop_grace)
+        } else {
+            self.settle
+        };
+        self.deadline = Some(now + delay);
+    }
+
+    /// Start over from the band buttons that are held down now, taking the
+    /// most recently pressed one as selected without reporting a change.
+    fn reset(&mut self, held: Vec<String>) {
+        self.selected = held.last().cloned();
+        self.held = held;
+        self.deadline = None;
+        self.together_since = None;
+        self.lucky = false;
+    }
+
+    /// Return the band change once the buttons have settled.
+    fn update(&mut self, now: Instant) -> Option<BandChange> {
+        if let Some(since) = self.together_since {
+            if self.lucky_hold > Duration::ZERO && now >= since + self.lucky_hold {
+                self.together_since = None;
+                self.lucky = true;
+                return Some(BandChange::Lucky);
+            }
+        }
+        match self.deadline {
+            Some(deadline) if now >= deadline => self.deadline = None,
+            _ => return None,
+        }
+        match (self.held.last(), &self.selected) {
+            // Releasing one of the buttons keeps the random station
+            (Some(_), _) if self.lucky => None,
+            (Some(band), selected) if selected.as_ref() != Some(band) => {
+                self.selected = Some(band.clone());
+                Some(BandChange::Select(band.clone()))
+            },
+            (None, Some(_)) => {
+                self.lucky = false;
+                self.selected.take().map(BandChange::Release)
+            },
+            _ => None,
+        }
+    }
+}
+
+type Adc = Ads1x1x<
+    ads1x1x::interface::I2cInterface<linux_embedded_hal::I2cdev>,
+    ads1x1x::ic::Ads1115,
+    ads1x1x::ic::Resolution16Bit,
+    ads1x1x::mode::OneShot,
+>;
+
+/// Read the raw value of an ADC channel.
+fn read_channel(adc: &mut Adc, ch: Channel) -> Result<i16, String> {
+    match ch {
+        Channel::A0 => block!(adc.read(&mut channel::SingleA0)),
+        Channel::A1 => block!(adc.read(&mut channel::SingleA1)),
+        Channel::A2 => block!(adc.read(&mut channel::SingleA2)),
+        Channel::A3 => block!(adc.read(&mut channel::SingleA3)),
+        Channel::A0A1 => block!(adc.read(&mut channel::DifferentialA0A1)),
+        Channel::A0A3 => block!(adc.read(&mut channel::DifferentialA0A3)),
+        Channel::A1A3 => block!(adc.read(&mut channel::DifferentialA1A3)),
+        Channel::A2A3 => block!(adc.read(&mut channel::DifferentialA2A3)),
+    }
+    .map_err(|e| format!("{:?}", e))
+}
+
+/// Open an ADS1115 and configure it for the analog controls.
+fn open_adc(i2c: &str, address: u8) -> Result<Adc, String> {
+    let dev = I2cdev::new(i2c).map_err(|e| format!("Could not open {}: {}", i2c, e))?;
+    // The last two bits of the address depend on the ADDR pin
+    let address = SlaveAddr::Alternative(address & 0b10 != 0, address & 0b01 != 0);
+    let mut adc = Ads1x1x::new_ads1115(dev, address);
+
+    // Configure PGA (gain)
+    adc.set_full_scale_range(FullScaleRange::Within4_096V)
+        .map_err(|e| format!("Could not set full scale range: {:?}", e))?;
+
+    // Configure sample rate. A single conversion takes about 8ms at this rate,
+    // which leaves enough headroom for the active sampling interval.
+    if let Err(e) = adc.set_data_rate(DataRate16Bit::Sps128) {
+        warn!("adc", "Could not set data rate: {:?}", e);
+    }
+    Ok(adc)
+}
+
+fn adc_loop(
+    mut adcs: AdcBank,
+    opts: Opts,
+    controls: Vec<AnalogControl>,
+    tuning: Tuning,
+    battery: Option<Battery>,
+    shared: Arc<SharedState>,
+) -> ! {
+    let volume_max_angle = controls
+        .iter()
+        .find(|control| control.role == Role::Volume)
+        .map(|control| control.lookup_table[control.lookup_table.len() - 1].0);
+    let mut power_switch = match volume_max_angle {
+        Some(max_angle) if opts.off_degrees > 0 => {
+            Some(PowerSwitch::new(max_angle, opts.off_degrees, opts.off_hysteresis))
+        },
+        _ => None,
+    };
+
+    let ramp_interval = Duration::from_millis(opts.volume_ramp_ms);
+
+    let mut tuner = Tuner::new(tuning.gap, tuning.hysteresis);
+    let mut seeker = Seeker::new(Duration::from_millis(tuning.seek_static_ms), tuning.hysteresis);
+    let mut tuned_band: Option<String> = None;
+
+    // The volume that was last applied, if any
+    let mut applied_volume: Option<u8> = None;
+
+    // The last measured value of every control and the time any of them
+    // last changed
+    let mut last_values: Vec<Option<u8>> = vec![None; controls.len()];
+    let mut last_change = Instant::now();
+
+    let mut battery_monitor = battery
+        .as_ref()
+        .map(|battery| BatteryMonitor::new(battery.warn_voltage, battery.shutdown_voltage));
+    let mut next_battery_reading = Instant::now();
+
+    // Do measurement
+    loop {
+        shared.heartbeat("adc");
+        let started = Instant::now();
+
+        let mut volume = None;
+        let mut dial = None;
+        let mut balance = None;
+        for (control, last_value) in controls.iter().zip(last_values.iter_mut()) {
+            // Negative readings (noise around 0V or a differential input that
+            // is slightly below its reference) are treated as zero.
+            let adc = control.adc.as_deref();
+            let raw = match shared.adc_read(adc, control.channel, adcs.read_raw(adc, control.channel)) {
+                Some(raw) => raw.max(0) as u16,
+                None => continue,
+            };
+            let value = map_potentiometer_value(&control.lookup_table, raw);
+            // Noise can change the value by one step, which doesn't count as
+            // using the control
+            if last_value.is_some_and(|last_value| last_value.abs_diff(value) > 1) {
+                shared.touch();
+            }
+            if *last_value != Some(value) {
+                let channel = channel_name(adc, control.channel);
+                info!(
+                    "adc",
+                    { channel: channel, raw: raw, value: value },
+                    "{} ({:?}): raw={} value={}",
+                    channel,
+                    control.role,
+                    raw,
+                    value
+                );
+                *last_value = Some(value);
+                last_change = started;
+            }
+
+            if control.role == Role::Volume {
+                let angle = measurement_to_angle(&control.lookup_table, raw);
+                volume = Some((apply_dead_bands(value, opts.dead_band_low, opts.dead_band_high), angle));
+            } else if control.role == Role::Tuning {
+                dial = Some(value);
+            } else if control.role == Role::Balance {
+                balance = Some(balance_from_value(value, control.center_dead_zone));
+            }
+        }
+
+        // Check the battery
+        if let (Some(battery), Some(monitor)) = (&battery, &mut battery_monitor) {
+            if started >= next_battery_reading {
+                next_battery_reading = started + BATTERY_INTERVAL;
+                let adc = battery.adc.as_deref();
+                let raw = shared.adc_read(adc, battery.channel, adcs.read_raw(adc, battery.channel));
+                let volts = raw.map(|raw| battery::voltage(raw, battery.divider));
+                match volts.and_then(|volts| monitor.update(volts).map(|event| (event, volts))) {
+                    Some((BatteryEvent::Low, volts)) => {
+                        warn!("battery", { volts: volts }, "Battery low ({:.2}V)", volts);
+                        shared.notify(&format!("Battery low ({:.2}V)", volts));
+                    },
+                    Some((BatteryEvent::Recovered, volts)) => {
+                        info!("battery", { volts: volts }, "Battery recovered ({:.2}V)", volts)
+                    },
+                    Some((BatteryEvent::Critical, volts)) => {
+                        error!("battery", { volts: volts }, "Battery critical ({:.2}V), shutting down", volts);
+                        shared.notify(&format!("Battery critical ({:.2}V), shutting down", volts));
+                        stop_playback();
+                        shutdown();
+                    },
+                    None => {},
+                }
+            }
+        }
+
+        shared.adc_recoveries.store(adcs.recoveries(), Ordering::SeqCst);
+
+        // Sample faster while a knob is being turned
+        let interval_ms = if started.duration_since(last_change) < ADC_IDLE_AFTER
+            && !shared.slowed_down.load(Ordering::SeqCst)
+        {
+            opts.adc_active_interval_ms
+        } else {
+            opts.adc_interval_ms
+        };
+        let interval = Duration::from_millis(interval_ms);
+        let ramp_steps = match opts.volume_ramp_ms {
+            0 => 1,
+            ramp_ms => (interval_ms / ramp_ms) as u32,
+        };
+
+        // Apply a changed balance with the current volume. Volume changes
+        // below apply it as well.
+        if let Some(balance) = balance {
+            let changed = shared.balance.swap(balance, Ordering::SeqCst) != balance;
+            if changed && applied_volume.is_some() && !shared.muted.load(Ordering::SeqCst) {
+                shared.set_volume(&opts.volumio_command, shared.volume.load(Ordering::SeqCst));
+            }
+        }
+
+        if let Some((volume, angle)) = volume {
+            // Handle the power switch region of the volume knob
+            let switched = power_switch.as_mut().and_then(|switch| switch.update(angle));
+            if switched == Some(true) {
+                info!("adc", "Volume knob switched off");
+                shared.switched_off.store(true, Ordering::SeqCst);
+                stop_playback();
+            }
+
+            // Set volume, ramping towards the target volume
+            if shared.switched_off.load(Ordering::SeqCst) || shared.muted.load(Ordering::SeqCst) {
+                applied_volume = None;
+            } else {
+                for step in volume_ramp(applied_volume, volume, ramp_steps) {
+                    if applied_volume.is_some() {
+                        thread::sleep(ramp_interval);
+                    }
+                    shared.set_volume(&opts.volumio_command, step);
+                    applied_volume = Some(step);
+                }
+            }
+
+            // Resume playback after setting the volume
+            if switched == Some(false) {
+                info!("adc", "Volume knob switched on");
+                shared.switched_off.store(false, Ordering::SeqCst);
+                if let Some(playlist) = shared.playlist.lock().unwrap().clone() {
+                    shared.play(&playlist);
+                }
+            }
+        }
+
+        // Select the station on the current band
+        if let Some(dial) = dial {
+            let band = shared.band.lock().unwrap().clone();
+            if band != tuned_band {
+                tuner.reset();
+                seeker.reset();
+                tuned_band = band.clone();
+            }
+            let stations = band.as_ref().map(|band| tuning.stations(band)).unwrap_or(&[]);
+            let (accuracy, position) = match tuning.mode {
+                TuningMode::Slots => (tuning::accuracy(dial, stations.len()), tuner.update(dial, stations.len())),
+                TuningMode::Seek => {
+                    let positions = band.as_ref().map(|band| tuning.positions(band)).unwrap_or_default();
+                    (tuning::seek_accuracy(dial, &positions), seeker.update(dial, &positions, started))
+                },
+            };
+            shared.tuning_accuracy.store(accuracy, Ordering::SeqCst);
+            let playlist = match position {
+                Some(DialPosition::Station(i)) => Some(stations[i].clone()),
+                Some(DialPosition::Between) => tuning.static_playlist.clone(),
+                None => None,
+            };
+            if let Some(playlist) = playlist {
+                shared.select_playlist(&opts.volumio_command, playlist);
+            }
+        }
+
+        // Sleep for the rest of the interval
+        if let Some(remaining) = interval.checked_sub(started.elapsed()) {
+            thread::sleep(remaining);
+        }
+    }
+}
+
+fn gpio_loop(
+    mut state: GpioPinState,
+    opts: Opts,
+    config: Arc<Config>,
+    shared: Arc<SharedState>,
+    emulated_buttons: mpsc::Receiver<String>,
+) -> ! {
+    let mut latch = ButtonLatch::default();
+    let stuck_after = |name: &str| config.button(name).and_then(|button| button.stuck_after());
+    let mut stuck = StuckButtons::default();
+    // A button that is held at startup may be stuck, e.g. the shutdown button
+    // would otherwise shut down the system again after every boot
+    for name in state.held() {
+        if stuck_after(&name).is_some() {
+            warn!("gpio", { button: name }, "Button {} is held at startup, ignoring it until it is released", name);
+            stuck.ignore(&name);
+        }
+    }
+    let mut bands = BandSelector::new(
+        Duration::from_millis(opts.band_settle_ms),
+        Duration::from_millis(opts.stop_grace_ms),
+        Duration::from_millis(opts.lucky_gesture_ms),
+    );
+    let mut shuffle = dx::Shuffle::from_time();
+
+    // When the mute gesture is enabled, stopping playback after releasing a
+    // band button is delayed until the gesture window has passed.
+    let mut pending_stop: Option<(String, Instant)> = None;
+
+    // The time at which ducking of the volume ends
+    let mut duck_deadline: Option<Instant> = None;
+
+    // The time since which the lock button is held down
+    let mut lock_held_since: Option<Instant> = None;
+    let mut was_locked = shared.is_locked();
+
+    // The time since which the radio is on, i.e. the shutdown button is
+    // released
+    let mut on_since = Instant::now();
+
+    let poll_interval = state.poll_interval();
+
+    loop {
+        shared.heartbeat("gpio");
+        // Update measurements
+        let (mut pressed, mut released) = state.update(Instant::now());
+        if !pressed.is_empty() || !released.is_empty() {
+            *shared.switch_wear.lock().unwrap() = state.wear();
+        }
+        for name in stuck.update(&mut pressed, &mut released, Instant::now(), stuck_after) {
+            warn!("gpio", { button: name }, "Button {} seems to be stuck, ignoring it until it is released", name);
+            shared.notify(&format!("Button {} seems to be stuck", name));
+        }
+        for name in state.worn.drain(..) {
+            shared.notify(&format!("Button {} bounces a lot, its contacts may need cleaning", name));
+        }
+
+        // Add buttons pressed on input devices. Band buttons latch like the
+        // piano keys.
+        for name in emulated_buttons.try_iter() {
+            let latching = config.button(&name).and_then(Button::band).is_some();
+            let (latch_pressed, latch_released) = latch.press(&name, latching);
+            pressed.extend(latch_pressed);
+            released.extend(latch_released);
+        }
+
+        let now = Instant::now();
+        if !pressed.is_empty() || !released.is_empty() {
+            shared.touch();
+        }
+        if !pressed.is_empty() {
+            info!("gpio", "Pressed: {:?}", pressed);
+        }
+        for name in &pressed {
+            events::record(EventKind::Press, name.clone());
+            if config.lock.button.as_ref() == Some(name) {
+                lock_held_since = Some(now);
+            }
+            match config.button(name).map(|button| &button.action) {
+                Some(ButtonAction::Playlist(_) | ButtonAction::Band(_)) if shared.is_locked() => {
+                    info!("lock", { button: name }, "Ignoring band button {}, the radio is locked", name);
+                },
+                Some(ButtonAction::Playlist(_) | ButtonAction::Band(_)) => bands.press(name, now),
+                Some(ButtonAction::Stop) => shared.stop(),
+                Some(ButtonAction::Mute) => shared.toggle_mute(&opts.volumio_command),
+                Some(ButtonAction::SleepTimer(minutes)) => {
+                    let mut sleep_deadline = shared.sleep_deadline.lock().unwrap();
+                    if sleep_deadline.take().is_some() {
+                        info!("gpio", "Sleep timer cancelled");
+                    } else {
+                        info!("gpio", "Stopping playback in {} minutes", minutes);
+                        *sleep_deadline = Some(now + Duration::from_secs(minutes * 60));
+                    }
+                },
+                Some(ButtonAction::LineOut) => shared.toggle_line_out(config.line_out.as_ref()),
+                Some(ButtonAction::Duck(duck)) => {
+                    info!("gpio", "Ducking the volume to {}% for {}s", duck.volume, duck.duration_s);
+                    duck_deadline = Some(now + Duration::from_secs(duck.duration_s));
+                    shared.duck(&opts.volumio_command, Some(duck.volume));
+                },
+                Some(ButtonAction::Pause) => toggle_playback(&opts.volumio_command),
+                Some(ButtonAction::Shutdown) => {
+                    let on_for = now.duration_since(on_since);
+                    if config.shutdown.halts(on_for) {
+                        shutdown();
+                    } else if config.shutdown.stop_only {
+                        info!("gpio", "Stopping playback instead of shutting down");
+                        shared.stop();
+                    } else {
+                        warn!(
+                            "gpio",
+                            { button: name },
+                            "Ignoring shutdown, the radio was only on for {}s, stopping playback instead",
+                            on_for.as_secs()
+                        );
+                        shared.stop();
+                    }
+                },
+                None => {},
+            }
+        }
+        if !released.is_empty() {
+            info!("gpio", "Released: {:?}", released);
+        }
+        for name in &released {
+            events::record(EventKind::Release, name.clone());
+            if config.lock.button.as_ref() == Some(name) {
+                lock_held_since = None;
+            }
+            match config.button(name).map(|button| &button.action) {
+                Some(ButtonAction::Playlist(_) | ButtonAction::Band(_)) if shared.is_locked() => {},
+                Some(ButtonAction::Playlist(_) | ButtonAction::Band(_)) => bands.release(name, now),
+                Some(ButtonAction::Shutdown) => on_since = now,
+                _ => {},
+            }
+        }
+
+        // Band buttons are ignored while the radio is locked, so the selector
+        // starts over from the band buttons held down when it is locked or
+        // unlocked (also remotely)
+        let locked = shared.is_locked();
+        if locked != was_locked {
+            was_locked = locked;
+            let is_band = |name: &String| {
+                matches!(
+                    config.button(name).map(|button| &button.action),
+                    Some(ButtonAction::Playlist(_) | ButtonAction::Band(_))
+                )
+            };
+            bands.reset(state.held().into_iter().chain(latch.latched.clone()).filter(is_band).collect());
+            pending_stop = None;
+        }
+        let change = if locked { None } else { bands.update(now) };
+        match change {
+            Some(BandChange::Select(band)) => {
+                if pending_stop.as_ref().map(|(released, _)| released) == Some(&band) {
+                    shared.toggle_mute(&opts.volumio_command);
+                } else if let Some(band) = config.button(&band).and_then(Button::band) {
+                    shared.select_band(&opts.volumio_command, band, &config);
+                }
+                pending_stop = None;
+            },
+            Some(BandChange::Release(band)) => {
+                // Releasing all band buttons stops playback
+                if opts.mute_gesture_ms > 0 {
+                    pending_stop = Some((band, now + Duration::from_millis(opts.mute_gesture_ms)));
+                } else {
+                    shared.stop();
+                }
+            },
+            Some(BandChange::Lucky) => {
+                shared.play_random_station(&opts.volumio_command, &config, &mut shuffle);
+                pending_stop = None;
+            },
+            None => {},
+        }
+        if let Some((_, deadline)) = &pending_stop {
+            if now >= *deadline {
+                pending_stop = None;
+                shared.stop();
+            }
+        }
+        let sleep_timer_expired = {
+            let mut sleep_deadline = shared.sleep_deadline.lock().unwrap();
+            let expired = sleep_deadline.is_some_and(|deadline| now >= deadline);
+            if expired {
+                *sleep_deadline = None;
+            }
+            expired
+        };
+        if sleep_timer_expired {
+            info!("gpio", "Sleep timer expired");
+            shared.stop();
+        }
+        // Holding down the lock button locks or unlocks the radio
+        if lock_held_since.is_some_and(|since| now >= since + Duration::from_secs(config.lock.hold_s)) {
+            lock_held_since = None;
+            shared.set_locked(&opts.volumio_command, &config.lock, !shared.is_locked());
+        }
+        if duck_deadline.is_some_and(|deadline| now >= deadline) {
+            info!("gpio", "Restoring the volume after ducking");
+            duck_deadline = None;
+            shared.duck(&opts.volumio_command, None);
+        }
+
+        // Sleep until the next input is due. With the default debounce
+        // parameters (16 samples every 10 milliseconds), a signal must be
+        // stable for 160ms to trigger a press or release.
+        thread::sleep(poll_interval);
+    }
+}
+
+/// Drive the outputs according to the state of the radio.
+fn outputs_loop(mut outputs: Vec<(Output, OutputPin)>, shared: Arc<SharedState>) -> ! {
+    let mut levels: Vec<Option<f64>> = vec![None; outputs.len()];
+    loop {
+        shared.heartbeat("outputs");
+        let state = shared.radio_state();
+        for ((output, pin), last_level) in outputs.iter_mut().zip(levels.iter_mut()) {
+            let level = outputs::level(output, &state);
+            if *last_level == Some(level) {
+                continue;
+            }
+            *last_level = Some(level);
+
+            let duty_cycle = if output.inverted { 1.0 - level } else { level };
+            if duty_cycle == 0.0 || duty_cycle == 1.0 {
+                if let Err(e) = pin.clear_pwm() {
+                    error!("outputs", "Could not stop PWM on GPIO pin {}: {}", output.pin, e);
+                }
+                pin.write(if duty_cycle == 1.0 { Level::High } else { Level::Low });
+            } else if let Err(e) = pin.set_pwm_frequency(OUTPUT_PWM_FREQUENCY, duty_cycle) {
+                error!("outputs", "Could not set PWM on GPIO pin {}: {}", output.pin, e);
+            }
+        }
+        thread::sleep(Duration::from_millis(50));
+    }
+}
+
+/// Log warnings about the CPU temperature and the supply voltage.
+fn health_loop(health: Health, shared: Arc<SharedState>) -> ! {
+    let mut hot = false;
+    let mut last_throttling = Throttling::default();
+    loop {
+        shared.heartbeat("health");
+        if let Some(temperature) = health::read_temperature() {
+            if !hot && temperature > health.warn_temperature {
+                warn!("health", { temperature: temperature }, "CPU temperature is {:.1}°C", temperature);
+            } else if hot && temperature <= health.warn_temperature {
+                info!("health", { temperature: temperature }, "CPU temperature is back to {:.1}°C", temperature);
+            }
+            hot = temperature > health.warn_temperature;
+        }
+
+        let throttling = health::read_throttling().unwrap_or_default();
+        if throttling.under_voltage && !last_throttling.under_voltage {
+            warn!("health", "Undervoltage detected, check the power supply");
+            shared.notify("Undervoltage detected, check the power supply");
+        }
+        if throttling.throttled && !last_throttling.throttled {
+            warn!("health", "CPU is throttled");
+        }
+        if last_throttling != Throttling::default() && throttling == Throttling::default() {
+            info!("health", "CPU is no longer throttled");
+        }
+        last_throttling = throttling;
+
+        let slow_down = health.slow_down && (hot || throttling.throttled);
+        if shared.slowed_down.swap(slow_down, Ordering::SeqCst) != slow_down {
+            info!("health", "{} polling of analog controls", if slow_down { "Slowing down" } else { "Resuming" });
+        }
+
+        thread::sleep(HEALTH_INTERVAL);
+    }
+}
+
+/// Play the recordings of the DX mode while the DX band is selected.
+fn dx_loop(dx: Dx, shared: Arc<SharedState>) -> ! {
+    let mut shuffle = dx::Shuffle::from_time();
+    let mut player: Option<Child> = None;
+    loop {
+        shared.heartbeat("dx");
+        let receiving = shared.band.lock().unwrap().as_deref() == Some(dx.band.as_str())
+            && !shared.switched_off.load(Ordering::SeqCst);
+
+        // Start the next recording once the previous one has ended
+        let ended = match &mut player {
+            Some(child) => !matches!(child.try_wait(), Ok(None)),
+            None => true,
+        };
+        if !receiving || ended {
+            if let Some(mut child) = player.take() {
+                let _ = child.kill();
+                let _ = child.wait();
+            }
+        }
+        if receiving && player.is_none() {
+            let pool = dx::pool(&dx.recordings);
+            if let Some(index) = shuffle.pick(pool.len()) {
+                match dx::play(&pool[index], &dx, shuffle.fade_frequency()) {
+                    Ok(child) => {
+                        info!("dx", { recording: pool[index] }, "Receiving {}", pool[index]);
+                        events::record(EventKind::Play, pool[index].clone());
+                        player = Some(child);
+                    },
+                    Err(e) => error!("dx", "{}", e),
+                }
+            }
+        }
+
+        thread::sleep(DX_INTERVAL);
+    }
+}
+
+/// Drive the fan according to the temperature.
+fn fan_loop(fan: Fan, mut pin: OutputPin, shared: Arc<SharedState>) -> ! {
+    let mut controller = FanController::new(fan.curve.clone());
+    let mut last_duty = None;
+    let mut sensor_failing = false;
+    loop {
+        shared.heartbeat("fan");
+        // Without a temperature, the fan runs at full speed to be safe
+        let duty = match health::read_sensor(&fan.sensor) {
+            Some(temperature) => {
+                sensor_failing = false;
+                controller.update(temperature)
+            },
+            None => {
+                if !sensor_failing {
+                    error!("fan", "Could not read the temperature from {}, running the fan at full speed", fan.sensor);
+                    sensor_failing = true;
+                }
+                100
+            },
+        };
+        if last_duty != Some(duty) {
+            info!("fan", { duty: duty }, "Fan duty cycle {}%", duty);
+            let duty_cycle = if fan.inverted { 100 - duty } else { duty } as f64 / 100.0;
+            if duty_cycle == 0.0 || duty_cycle == 1.0 {
+                if let Err(e) = pin.clear_pwm() {
+                    error!("fan", "Could not stop PWM on GPIO pin {}: {}", fan.pin, e);
+                }
+                pin.write(if duty_cycle == 1.0 { Level::High } else { Level::Low });
+            } else if let Err(e) = pin.set_pwm_frequency(FAN_PWM_FREQUENCY, duty_cycle) {
+                error!("fan", "Could not set PWM on GPIO pin {}: {}", fan.pin, e);
+            }
+            last_duty = Some(duty);
+            *shared.fan_duty.lock().unwrap() = Some(duty);
+        }
+        thread::sleep(FAN_INTERVAL);
+    }
+}
+
+/// Follow the ambient light with the brightness of the dial lamp and the
+/// display.
+fn ambient_light_loop(config: AmbientLight, mut sensor: LightSensor, shared: Arc<SharedState>) -> ! {
+    // The brightness follows the light smoothly, so that it doesn't jump when
+    // a shadow falls on the sensor
+    let mut level: Option<f64> = None;
+    let mut failing = false;
+    loop {
+        shared.heartbeat("ambient_light");
+        match sensor.read_lux() {
+            Ok(lux) => {
+                failing = false;
+                let target = light::brightness(lux, &config) as f64;
+                let new_level = level.map_or(target, |level| level + (target - level) * 0.2);
+                level = Some(new_level);
+                *shared.ambient_brightness.lock().unwrap() = Some(new_level.round() as u8);
+            },
+            Err(e) => {
+                if !failing {
+                    error!("ambient_light", "{}", e);
+                    failing = true;
+                }
+            },
+        }
+        thread::sleep(AMBIENT_LIGHT_INTERVAL);
+    }
+}
+
+/// Watch streams that were started until they are playing.
+///
+/// Volumio reports the state "play" as soon as it connects to a stream, so a
+/// stream counts as started once its position advances. Streams that don't
+/// start in time are skipped, until the playlist is given up. The timeout,
+/// the number of attempts and the backoff can be set per station.
+fn stream_watchdog_loop(opts: Opts, streams: Streams, shared: Arc<SharedState>) -> ! {
+    let mut watched: Option<Instant> = None;
+    let mut attempts = 0;
+    // The time at which the next stream is tried
+    let mut retry_at: Option<Instant> = None;
+    loop {
+        shared.heartbeat("stream_watchdog");
+        let started = *shared.playback_started.lock().unwrap();
+        if !shared.buffering.load(Ordering::SeqCst) || started.is_none() {
+            thread::sleep(Duration::from_millis(500));
+            continue;
+        }
+        let started = started.unwrap();
+        if watched != Some(started) {
+            // A new playlist was started
+            watched = Some(started);
+            attempts = 1;
+            retry_at = None;
+            *shared.stream_problem.lock().unwrap() = None;
+        }
+        let playlist = shared.playlist.lock().unwrap().clone().unwrap_or_default();
+        let policy = shared.station(&playlist).map_or(streams.retry_policy(), |station| station.retry_policy(&streams));
+
+        let status = volumio_status(&opts.volumio_command);
+        let playing = status.as_deref().is_some_and(|status| {
+            json_string(status, "status").as_deref() == Some("play") && json_integer(status, "seek").unwrap_or(0) > 0
+        });
+        if playing {
+            let latency = started.elapsed();
+            info!("player", "Stream started after {:.1}s", latency.as_secs_f64());
+            events::record(EventKind::StreamStarted, format!("after {:.1}s", latency.as_secs_f64()));
+            shared.buffering.store(false, Ordering::SeqCst);
+            shared.streams_started.fetch_add(1, Ordering::SeqCst);
+            *shared.stream_latency.lock().unwrap() = Some(latency);
+            *shared.stream_problem.lock().unwrap() = None;
+            retry_at = None;
+        } else if retry_at.is_none() && started.elapsed() >= policy.timeout {
+            // A web page or another format instead of a stream is a problem
+            // of the station's URL, which the content type tells
+            let uri = status.as_deref().and_then(|status| json_string(status, "uri"));
+            if let Some(problem) = uri.and_then(|uri| sniff::diagnose(&shared.station_name(&playlist), &uri)) {
+                warn!("player", { playlist: playlist }, "{}", problem);
+                *shared.stream_problem.lock().unwrap() = Some(problem);
+            }
+            if attempts < policy.attempts {
+                let delay = policy.delay(attempts);
+                warn!(
+                    "player",
+                    { playlist: playlist },
+                    "Stream did not start within {}s, trying the next stream of playlist {} in {}s",
+                    policy.timeout.as_secs(),
+                    playlist,
+                    delay.as_secs()
+                );
+                retry_at = Some(Instant::now() + delay);
+            } else {
+                give_up_playlist(&shared, &playlist);
+            }
+        }
+        if retry_at.is_some_and(|retry_at| Instant::now() >= retry_at) {
+            retry_at = None;
+            if play_next(&opts.volumio_command) {
+                attempts += 1;
+                shared.streams_skipped.fetch_add(1, Ordering::SeqCst);
+                let now = Instant::now();
+                *shared.playback_started.lock().unwrap() = Some(now);
+                watched = Some(now);
+            } else {
+                give_up_playlist(&shared, &playlist);
+            }
+        }
+        thread::sleep(Duration::from_millis(500));
+    }
+}
+
+/// Stop playback of a playlist of which no stream started.
+fn give_up_playlist(shared: &SharedState, playlist: &str) {
+    // Tell a station that is down from a problem with the network
+    let connectivity = *shared.connectivity.lock().unwrap();
+    if connectivity == Some(Connectivity::Online) {
+        shared.station_down.store(true, Ordering::SeqCst);
+        error!("player", { playlist: playlist }, "Giving up on playlist {}, the station is down", playlist);
+    } else {
+        let network = connectivity.map_or("unknown".to_string(), |c| c.to_string());
+        error!(
+            "player",
+            { playlist: playlist, network: network },
+            "Giving up on playlist {}, no stream started (network: {})",
+            playlist,
+            network
+        );
+    }
+    shared.buffering.store(false, Ordering::SeqCst);
+    *shared.playback_error.lock().unwrap() = Some(Instant::now());
+    stop_playback();
+}
+
+/// Check the network connection, logging changes.
+fn connectivity_loop(shared: Arc<SharedState>) -> ! {
+    loop {
+        shared.heartbeat("connectivity");
+        let connectivity = connectivity::check();
+        let last = shared.connectivity.lock().unwrap().replace(connectivity);
+        if last != Some(connectivity) {
+            match connectivity {
+                Connectivity::Online => info!("network", "Network is online"),
+                problem => warn!("network", { network: problem }, "Network problem: {}", problem),
+            }
+        }
+        thread::sleep(CONNECTIVITY_INTERVAL);
+    }
+}
+
+/// Switch the mixer to the preferred sound card when it is plugged in or
+/// removed, and restore the volume on the new card.
+fn alsa_hotplug_loop(alsa: Alsa, opts: Opts, shared: Arc<SharedState>) -> ! {
+    let default_card = alsa.default_card();
+    loop {
+        shared.heartbeat("alsa_hotplug");
+        if let Some(mixer) = &shared.mixer {
+            let card = alsa::select_card(&alsa.preferred, &default_card, &alsa::cards());
+            if *card != mixer.card() {
+                match mixer.switch(card) {
+                    Ok(()) => {
+                        info!("alsa", "Switched to mixer control {} of card {}", card.mixer, card.card);
+                        if !shared.muted.load(Ordering::SeqCst) {
+                            shared.set_volume(&opts.volumio_command, shared.volume.load(Ordering::SeqCst));
+                        }
+                    },
+                    Err(e) => error!("alsa", "Could not switch to card {}: {}", card.card, e),
+                }
+            }
+        }
+        thread::sleep(ALSA_HOTPLUG_INTERVAL);
+    }
+}
+
+/// Switch off the display and the outputs, or shut down, when nothing has
+/// been playing and no control has been used for a while.
+fn idle_loop(idle: Idle, shared: Arc<SharedState>) -> ! {
+    let idle_after = Duration::from_secs(idle.after_minutes * 60);
+    let mut last_active = Instant::now();
+    loop {
+        shared.heartbeat("idle");
+        let now = Instant::now();
+        if shared.is_playing() {
+            last_active = now;
+        }
+        if let Some(last_input) = *shared.last_input.lock().unwrap() {
+            last_active = last_active.max(last_input);
+        }
+        if now.duration_since(last_active) >= idle_after && !shared.idle.swap(true, Ordering::SeqCst) {
+            info!("idle", "Not used for {} minutes", idle.after_minutes);
+            if idle.shutdown {
+                shutdown();
+            }
+        }
+        thread::sleep(IDLE_INTERVAL);
+    }
+}
+
+/// Start and stop playback at the times of the schedule.
+fn schedule_loop(schedule: Vec<ScheduleEntry>, opts: Opts, shared: Arc<SharedState>) -> ! {
+    let mut last = None;
+    let mut trusted = true;
+    loop {
+        shared.heartbeat("schedule");
+
+        // Right after power-on without network, the clock may be far off
+        if shared.clock_trusted() != trusted {
+            trusted = !trusted;
+            if trusted {
+                info!("schedule", "The system clock is synchronized, following the schedule");
+            } else {
+                warn!("schedule", "The system clock is not synchronized, deferring the schedule");
+            }
+        }
+        if !trusted {
+            thread::sleep(SCHEDULE_INTERVAL);
+            continue;
+        }
+
+        let now = local_weekday_time();
+        if let Some((weekday, minutes)) = now.filter(|_| now != last) {
+            for entry in schedule.iter().filter(|entry| entry.is_due(weekday, minutes)) {
+                info!("schedule", { action: format!("{:?}", entry.action) }, "Schedule entry at {} is due", entry.time);
+                match entry.action {
+                    ScheduledAction::Play => {
+                        shared.touch();
+                        if let Some(volume) = entry.volume {
+                            shared.set_volume(&opts.volumio_command, volume);
+                        }
+                        if let Some(playlist) = &entry.playlist {
+                            shared.select_playlist(&opts.volumio_command, playlist.clone());
+                        }
+                    },
+                    ScheduledAction::Stop => shared.stop(),
+                    ScheduledAction::Shutdown => {
+                        shared.stop();
+                        shutdown();
+                    },
+                }
+            }
+            last = now;
+        }
+        thread::sleep(SCHEDULE_INTERVAL);
+    }
+}
+
+/// Set the real-time clock from the system clock while it is synchronized
+/// with NTP.
+fn rtc_loop(mut rtc: Ds3231, shared: Arc<SharedState>) -> ! {
+    let mut last_sync: Option<Instant> = None;
+    loop {
+        shared.heartbeat("rtc");
+        if last_sync.is_none_or(|sync| sync.elapsed() >= RTC_SYNC_INTERVAL) && rtc::clock_synchronized() {
+            let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+            match rtc.write(now) {
+                Ok(()) => {
+                    if last_sync.is_none() {
+                        info!("rtc", "Set the real-time clock from the synchronized system clock");
+                    }
+                    last_sync = Some(Instant::now());
+                },
+                Err(e) => error!("rtc", "{}", e),
+            }
+        }
+        thread::sleep(RTC_CHECK_INTERVAL);
+    }
+}
+
+/// Send a notification when a station has been down for a while.
+fn notify_loop(notify: Notify, shared: Arc<SharedState>) -> ! {
+    let station_down_after = Duration::from_secs(notify.station_down_minutes * 60);
+    let mut down_since: Option<Instant> = None;
+    let mut notified = false;
+    loop {
+        shared.heartbeat("notify");
+        if shared.station_down.load(Ordering::SeqCst) {
+            let since = *down_since.get_or_insert_with(Instant::now);
+            if !notified && since.elapsed() >= station_down_after {
+                let playlist = shared.playlist.lock().unwrap().clone().unwrap_or_default();
+                let station = shared.station_name(&playlist);
+                shared.notify(&format!("Station {} has been down for {} minutes", station, notify.station_down_minutes));
+                notified = true;
+            }
+        } else {
+            down_since = None;
+            notified = false;
+        }
+        thread::sleep(NOTIFY_INTERVAL);
+    }
+}
+
+/// Periodically fetch the weather.
+fn weather_loop(config: WeatherConfig, shared: Arc<SharedState>) -> ! {
+    loop {
+        shared.heartbeat("weather");
+        match weather::fetch(config.latitude, config.longitude) {
+            Ok(weather) => {
+                info!("weather", { temperature: weather.temperature, code: weather.code }, "{}", weather);
+                *shared.weather.lock().unwrap() = Some(weather);
+            },
+            // Keep showing the last forecast
+            Err(e) => warn!("weather", "Could not fetch the weather: {}", e),
+        }
+        thread::sleep(Duration::from_secs(config.interval_minutes * 60));
+    }
+}
+
+/// Monitor the link quality of the Wi-Fi connection.
+fn wifi_loop(shared: Arc<SharedState>) -> ! {
+    let mut weak = false;
+    loop {
+        shared.heartbeat("wifi");
+        let quality = wifi::read_link_quality();
+        if let Some(quality) = quality {
+            if !weak && quality < WEAK_WIFI_QUALITY {
+                warn!("wifi", { quality: quality }, "Weak Wi-Fi signal, link quality is {}%", quality);
+            } else if weak && quality >= WEAK_WIFI_QUALITY {
+                info!("wifi", { quality: quality }, "Wi-Fi signal is back to {}%", quality);
+            }
+            weak = quality < WEAK_WIFI_QUALITY;
+        }
+        *shared.wifi_quality.lock().unwrap() = quality;
+        thread::sleep(WIFI_INTERVAL);
+    }
+}
+
+/// Periodically write a status report to a file.
+fn status_loop(mut file: PersistentFile, opts: Opts, shared: Arc<SharedState>) -> ! {
+    loop {
+        file.update(shared.status(&opts.volumio_command).render());
+        thread::sleep(STATUS_INTERVAL);
+    }
+}
+
+/// Save the state to a file whenever it changes.
+fn snapshot_loop(mut file: PersistentFile, mut last: Option<Snapshot>, shared: Arc<SharedState>) -> ! {
+    loop {
+        shared.heartbeat("snapshot");
+        // Only changes are written, to spare the SD card
+        let snapshot = shared.snapshot();
+        if last.as_ref() != Some(&snapshot) {
+            file.update(snapshot.render());
+            last = Some(snapshot);
+        }
+        file.sync(Instant::now());
+        thread::sleep(SNAPSHOT_INTERVAL);
+    }
+}
+
+/// Record the listening time per station and day.
+fn history_loop(mut file: PersistentFile, mut history: History, shared: Arc<SharedState>) -> ! {
+    loop {
+        thread::sleep(HISTORY_INTERVAL);
+        shared.heartbeat("history");
+        let playlist = shared.playlist.lock().unwrap().clone();
+        if let Some(playlist) = playlist.filter(|_| shared.is_playing() && !shared.muted.load(Ordering::SeqCst)) {
+            if let Some(date) = history::local_date(0) {
+                history.add(&date, &playlist, HISTORY_INTERVAL);
+                file.update(history.render());
+            }
+        }
+        file.sync(Instant::now());
+    }
+}
+
+/// Switch between speakers and headphones when headphones are plugged in or
+/// out.
+/// Shut down the system when the power latch requests it.
+///
+/// Unlike the shutdown button, a request is never turned into stopping
+/// playback, as the latch circuit may cut the power anyway.
+fn power_request_loop(pin: InputPin, shared: Arc<SharedState>) -> ! {
+    let mut debouncer = Debouncer::new(16);
+    loop {
+        shared.heartbeat("power_request");
+        if debouncer.update(pin.read() == Level::Low) == Some(Edge::Rising) {
+            info!("power", "The power latch requests to switch off");
+            events::record(EventKind::Press, "power request");
+            shutdown();
+        }
+        thread::sleep(Duration::from_millis(10));
+    }
+}
+
+fn headphones_loop(
+    detect_pin: InputPin,
+    mut amp_enable_pin: Option<OutputPin>,
+    headphones: Headphones,
+    opts: Opts,
+    shared: Arc<SharedState>,
+) -> ! {
+    let mut debouncer = Debouncer::new(16);
+    loop {
+        shared.heartbeat("headphones");
+        let plugged = (detect_pin.read() == Level::Low) != headphones.inverted;
+        let plugged = match debouncer.update(plugged) {
+            Some(Edge::Rising) => true,
+            Some(Edge::Falling) => false,
+            None => {
+                thread::sleep(Duration::from_millis(10));
+                continue;
+            },
+        };
+
+        if plugged {
+            info!("headphones", "Headphones plugged in");
+            *shared.headphones.lock().unwrap() = Some(headphones.max_volume);
+            if let Some(pin) = &mut amp_enable_pin {
+                pin.set_low();
+            }
+            if let Some(command) = &headphones.headphones_command {
+                run_shell_command(command);
+            }
+        } else {
+            info!("headphones", "Headphones unplugged");
+            *shared.headphones.lock().unwrap() = None;
+            if let Some(command) = &headphones.speakers_command {
+                run_shell_command(command);
+            }
+            if let Some(pin) = &mut amp_enable_pin {
+                pin.set_high();
+            }
+        }
+
+        // Apply the volume to the new output
+        if !shared.muted.load(Ordering::SeqCst) && !shared.switched_off.load(Ordering::SeqCst) {
+            shared.set_volume(&opts.volumio_command, shared.volume.load(Ordering::SeqCst));
+        }
+    }
+}
+
+/// Show the state of the daemon on the status LEDs.
+fn leds_loop(mut leds: Vec<(Led, OutputPin)>, shared: Arc<SharedState>) -> ! {
+    let mut state = None;
+    let mut state_changed = Instant::now();
+    loop {
+        shared.heartbeat("leds");
+        let new_state = DaemonState::of(&shared.radio_state());
+        if state != Some(new_state) {
+            info!("leds", "State: {:?}", new_state);
+            state = Some(new_state);
+            state_changed = Instant::now();
+        }
+        for (led, pin) in &mut leds {
+            let on = led.pattern(new_state).is_on(state_changed.elapsed());
+            pin.write(if on != led.inverted { Level::High } else { Level::Low });
+        }
+        thread::sleep(Duration::from_millis(25));
+    }
+}
+
+/// Initialize the configured display.
+fn open_display(config: &DisplayConfig, i2c: &str, gpio: &Gpio) -> Result<Box<dyn Display + Send>, String> {
+    let i2c_dev = || I2cdev::new(i2c).map_err(|e| format!("Could not open {}: {}", i2c, e));
+
+    Ok(match config {
+        DisplayConfig::Ssd1306 { address } => Box::new(Ssd1306::new(i2c_dev()?, *address)?),
+        DisplayConfig::Hd44780 {
+            address,
+            columns,
+            layout,
+        } => Box::new(Hd44780::new(i2c_dev()?, *address, *columns, layout.clone())?),
+        DisplayConfig::Epaper {
+            dc_pin,
+            reset_pin,
+            busy_pin,
+            min_refresh_s,
+            ..
+        } => Box::new(Epaper::new(
+            gpio.output(*dc_pin, Level::High)?,
+            gpio.output(*reset_pin, Level::High)?,
+            gpio.input(*busy_pin)?,
+            Duration::from_secs(*min_refresh_s),
+        )?),
+    })
+}
+
+/// Show what's playing on the display.
+fn display_loop(
+    mut display: Box<dyn Display + Send>,
+    mut artwork_cache: Option<ArtworkCache>,
+    schedule: Vec<ScheduleEntry>,
+    opts: Opts,
+    shared: Arc<SharedState>,
+) -> ! {
+    let mut title = None;
+    let mut artwork = None;
+    let mut clock = String::new();
+    let mut date = String::new();
+    let mut alarm = None;
+    let mut next_refresh = Instant::now();
+    let mut tick = 0;
+    let mut blanked = false;
+    let mut brightness = None;
+    loop {
+        shared.heartbeat("display");
+        let idle = shared.idle.load(Ordering::SeqCst);
+        if idle != blanked {
+            if let Err(e) = display.set_blanked(idle) {
+                error!("display", "{}", e);
+            }
+            blanked = idle;
+        }
+        if blanked {
+            thread::sleep(Duration::from_millis(300));
+            continue;
+        }
+        let new_brightness = shared.brightness().unwrap_or(100);
+        if brightness != Some(new_brightness) {
+            if let Err(e) = display.set_brightness(new_brightness) {
+                error!("display", "{}", e);
+            }
+            brightness = Some(new_brightness);
+        }
+
+        // Querying volumio is slow, so the title and the clock are only
+        // refreshed every few seconds
+        if Instant::now() >= next_refresh {
+            let status = if shared.is_playing() { volumio_status(&opts.volumio_command) } else { None };
+            title = status.as_deref().and_then(now_playing);
+            artwork = match (&mut artwork_cache, status.as_deref()) {
+                (Some(cache), Some(status)) => {
+                    // A logo of the station is preferred to the artwork of
+                    // the stream
+                    let playlist = shared.playlist.lock().unwrap().clone();
+                    let logo = playlist.and_then(|playlist| shared.station(&playlist)?.artwork.clone());
+                    let url = logo.or_else(|| json_string(status, "albumart").filter(|url| !url.is_empty()));
+                    url.and_then(|url| cache.get(&artwork::resolve(&url)))
+                },
+                _ => None,
+            };
+            clock = local_time();
+            date = local_date();
+            alarm = local_weekday_time()
+                .and_then(|(weekday, minutes)| next_alarm(&schedule, weekday, minutes))
+                .map(|entry| entry.time.clone());
+            next_refresh = Instant::now() + DISPLAY_REFRESH_INTERVAL;
+        }
+
+        let screen = Screen {
+            station: shared.playlist.lock().unwrap().as_deref().map(|playlist| shared.station_name(playlist)),
+            title: display_status(&shared).or_else(|| title.clone()),
+            volume: shared.volume.load(Ordering::SeqCst),
+            muted: shared.muted.load(Ordering::SeqCst),
+            clock: clock.clone(),
+            date: date.clone(),
+            next_alarm: alarm.clone(),
+            weather: shared.weather.lock().unwrap().map(|weather| weather.summary()),
+            artwork: artwork.clone(),
+        };
+        if let Err(e) = display.show(&screen, tick) {
+            error!("display", "{}", e);
+        }
+        tick += 1;
+        thread::sleep(Duration::from_millis(300));
+    }
+}
+
+/// Return a message about a problem or the playback state that is shown
+/// instead of the title.
+fn display_status(shared: &SharedState) -> Option<String> {
+    if !shared.is_playing() {
+        return None;
+    }
+    match *shared.connectivity.lock().unwrap() {
+        Some(Connectivity::NoLink) => return Some(tr(Message::NoNetwork).into()),
+        Some(Connectivity::NoDns) => return Some(tr(Message::NoDns).into()),
+        Some(Connectivity::CaptivePortal) => return Some(tr(Message::NetworkLogin).into()),
+        Some(Connectivity::Online) | None => {},
+    }
+    if shared.station_down.load(Ordering::SeqCst) {
+        Some(tr(Message::StationDown).into())
+    } else if shared.buffering.load(Ordering::SeqCst) {
+        Some(tr(Message::Buffering).into())
+    } else {
+        None
+    }
+}
+
+/// The output that drives the magic eye.
+enum MagicEyeOutput {
+    Pwm(OutputPin),
+    Mcp4725(I2cdev, u8),
+}
+
+impl MagicEyeOutput {
+    fn set(&mut self, level: f64) {
+        match self {
+            MagicEyeOutput::Pwm(pin) => {
+                if let Err(e) = pin.set_pwm_frequency(MAGIC_EYE_PWM_FREQUENCY, level) {
+                    error!("magic_eye", "Could not set PWM of magic eye: {}", e);
+                }
+            },
+            MagicEyeOutput::Mcp4725(dev, address) => {
+                // Fast mode write of the 12-bit value
+                let value = (level * 4095.0).round() as u16;
+                if let Err(e) = dev.write(*address, &[(value >> 8) as u8, value as u8]) {
+                    error!("magic_eye", "Could not write to MCP4725: {}", e);
+                }
+            },
+        }
+    }
+}
+
+/// Drive the magic eye according to the state of the radio.
+///
+/// Like the real tube, the eye doesn't snap open but follows its target level
+/// smoothly.
+fn magic_eye_loop(eye: MagicEye, mut output: MagicEyeOutput, shared: Arc<SharedState>) -> ! {
+    let mut level = 0.0;
+    let mut applied_level = None;
+    loop {
+        shared.heartbeat("magic_eye");
+        let target = outputs::magic_eye_level(&eye, &shared.radio_state());
+        level += (target - level) * 0.2;
+        if (level - target).abs() < 0.005 {
+            level = target;
+        }
+        // The DAC has 12 bits, finer changes aren't visible anyway
+        let rounded = (level * 4095.0).round() / 4095.0;
+        if applied_level != Some(rounded) {
+            output.set(rounded);
+            applied_level = Some(rounded);
+        }
+        thread::sleep(Duration::from_millis(20));
+    }
+}
+
+/// GPIO input pins of the rotary encoder.
+struct EncoderPins {
+    a: InputPin,
+    b: InputPin,
+    switch: Option<InputPin>,
+}
+
+/// An action triggered by the rotary encoder.
+enum EncoderAction {
+    /// Change the volume by the specified number of percent. A step of 0
+    /// applies the current volume again, e.g. on unmute.
+    StepVolume(i16),
+    SelectPlaylist(String),
+}
+
+fn encoder_loop(pins: EncoderPins, encoder: Encoder, opts: Opts, tuning: Tuning, shared: Arc<SharedState>) -> ! {
+    let mut decoder = QuadratureDecoder::new(
+        encoder.steps_per_detent,
+        pins.a.read() == Level::Low,
+        pins.b.read() == Level::Low,
+    );
+    let mut switch = debounce_stateful_16(false);
+    let mut last_detent = Instant::now();
+
+    // Running the volumio command takes a while. To not miss any encoder
+    // transitions, actions are executed in a separate thread. If the encoder
+    // is turned faster than the actions can be executed, the volume steps add
+    // up and only the latest station is selected.
+    //
+    // The steps start from the current volume, which is also set by the
+    // schedule, remote controls and the restored state.
+    let (tx, rx) = mpsc::channel();
+    let worker_shared = shared.clone();
+    let cmd = opts.volumio_command.clone();
+    thread::spawn(move || {
+        while let Ok(action) = rx.recv() {
+            let (mut step, mut playlist) = (None, None);
+            for action in std::iter::once(action).chain(rx.try_iter()) {
+                match action {
+                    EncoderAction::StepVolume(volume_step) => step = Some(step.unwrap_or(0) + volume_step),
+                    EncoderAction::SelectPlaylist(selected) => playlist = Some(selected),
+                }
+            }
+            if let Some(step) = step {
+                let volume = (worker_shared.volume.load(Ordering::SeqCst) as i16 + step).clamp(0, 100) as u8;
+                worker_shared.set_volume(&cmd, volume);
+            }
+            if let Some(playlist) = playlist {
+                worker_shared.select_playlist(&cmd, playlist);
+            }
+        }
+    });
+
+    // The selected station index per band
+    let mut stations: HashMap<String, usize> = HashMap::new();
+    let mut tuned_band: Option<String> = None;
+
+    loop {
+        shared.heartbeat("encoder");
+        let detent = decoder.update(pins.a.read() == Level::Low, pins.b.read() == Level::Low);
+        if detent != 0 {
+            shared.touch();
+            let now = Instant::now();
+            if encoder.role == EncoderRole::Volume {
+                let step = encoder::volume_step(now.duration_since(last_detent)) as i16;
+                if !shared.muted.load(Ordering::SeqCst) {
+                    tx.send(EncoderAction::StepVolume(detent as i16 * step)).unwrap();
+                }
+            }
+            last_detent = now;
+        }
+
+        // Select the station on the current band
+        if encoder.role == EncoderRole::Tuning {
+            let band = shared.band.lock().unwrap().clone();
+            let band_stations = band.as_ref().map(|band| tuning.stations(band)).unwrap_or(&[]);
+            if let (Some(band), false) = (&band, band_stations.is_empty()) {
+                let index = stations.entry(band.clone()).or_insert(0);
+                let new_index = (*index as isize + detent as isize).clamp(0, band_stations.len() as isize - 1) as usize;
+                if tuned_band.as_ref() != Some(band) || new_index != *index {
+                    *index = new_index;
+                    tx.send(EncoderAction::SelectPlaylist(band_stations[new_index].clone())).unwrap();
+                }
+            }
+            tuned_band = band;
+        }
+
+        // The push switch toggles mute
+        if let Some(pin) = &pins.switch {
+            if switch.update(pin.read() == Level::Low) == Some(Edge::Rising) {
+                shared.toggle_mute(&opts.volumio_command);
+                if encoder.role == EncoderRole::Volume && !shared.muted.load(Ordering::SeqCst) {
+                    tx.send(EncoderAction::StepVolume(0)).unwrap();
+                }
+            }
+        }
+
+        thread::sleep(Duration::from_millis(1));
+    }
+}
+
+fn evdev_loop(
+    device: EvdevDevice,
+    opts: Opts,
+    config: Arc<Config>,
+    shared: Arc<SharedState>,
+    emulated_buttons: mpsc::Sender<String>,
+) -> ! {
+    loop {
+        // (Re)open the device
+        let mut input = match evdev::Device::open(&device.device) {
+            Ok(input) => input,
+            Err(e) => {
+                error!("evdev", "Could not open input device {}: {}", device.device, e);
+                thread::sleep(Duration::from_secs(5));
+                continue;
+            },
+        };
+        info!("evdev", "Opened input device {}", device.device);
+
+        loop {
+            // Reading blocks until the next event, so the heartbeat only
+            // tells when a key was last pressed (or the device opened)
+            shared.heartbeat("evdev");
+            let event = match input.read_event() {
+                Ok(event) => event,
+                Err(e) => {
+                    error!("evdev", "Could not read from input device {}: {}", device.device, e);
+                    break;
+                },
+            };
+            if event.kind != evdev::EV_KEY {
+                continue;
+            }
+            let repeated = match event.value {
+                evdev::KEY_PRESS => false,
+                evdev::KEY_REPEAT => true,
+                _ => continue,
+            };
+            shared.touch();
+            if let Some(button) = device.button(event.code) {
+                if !repeated {
+                    info!("evdev", { key: event.code }, "Key {}: {} button", event.code, button);
+                    emulated_buttons.send(button.to_string()).unwrap();
+                }
+                continue;
+            }
+            let action = match device.action(event.code) {
+                Some(action) => action,
+                None => continue,
+            };
+
+            // Only volume changes are repeated while a key is held down
+            if repeated && !matches!(action, KeyAction::VolumeUp | KeyAction::VolumeDown) {
+                continue;
+            }
+
+            info!("evdev", { key: event.code }, "Key {}: {:?}", event.code, action);
+            if !repeated {
+                events::record(EventKind::Press, format!("key {} ({:?})", event.code, action));
+            }
+            match action {
+                KeyAction::Band(_) if shared.is_locked() => {
+                    info!("lock", { key: event.code }, "Ignoring key {}, the radio is locked", event.code);
+                },
+                KeyAction::Band(band) => shared.select_band(&opts.volumio_command, band, &config),
+                KeyAction::VolumeUp => shared.step_volume(&opts.volumio_command, true),
+                KeyAction::VolumeDown => shared.step_volume(&opts.volumio_command, false),
+                KeyAction::Mute => shared.toggle_mute(&opts.volumio_command),
+                KeyAction::Stop => shared.stop(),
+                KeyAction::LineOut => shared.toggle_line_out(config.line_out.as_ref()),
+                KeyAction::Shutdown => shutdown(),
+            }
+        }
+
+        thread::sleep(Duration::from_secs(1));
+    }
+}
+
+fn main() {
+    let opts: Opts = Opts::parse();
+    log::set_format(opts.log_format);
+    DRY_RUN.store(opts.dry_run, Ordering::SeqCst);
+
+    match &opts.command {
+        Some(Subcommand::SelfUpdate) => {
+            if let Err(e) = update::self_update() {
+                error!("update", "{}", e);
+                exit(1);
+            }
+            return;
+        },
+        Some(Subcommand::Setup { path }) => {
+            if let Err(e) = setup::run(&opts.i2c, path) {
+                error!("setup", "{}", e);
+                exit(1);
+            }
+            return;
+        },
+        Some(Subcommand::Detect) => {
+            if let Err(e) = detect::run(&opts.i2c) {
+                error!("detect", "{}", e);
+                exit(1);
+            }
+            return;
+        },
+        Some(Subcommand::Logs { .. })
+        | Some(Subcommand::Events)
+        | Some(Subcommand::Status)
+        | Some(Subcommand::Brightness { .. })
+        | Some(Subcommand::Lock { .. }) => {
+            let command = match &opts.command {
+                Some(Subcommand::Logs { follow: true }) => "logs follow".into(),
+                Some(Subcommand::Logs { follow: false }) => "logs".into(),
+                Some(Subcommand::Brightness { value: Some(value) }) => format!("brightness {}", value),
+                Some(Subcommand::Brightness { value: None }) => "brightness".into(),
+                Some(Subcommand::Lock { value: Some(value) }) => format!("lock {}", value),
+                Some(Subcommand::Lock { value: None }) => "lock".into(),
+                Some(Subcommand::Status) => "status".into(),
+                _ => "events".into(),
+            };
+            if let Err(e) = control::request(&opts.control_socket, &command, &mut io::stdout()) {
+                error!("control", "{}", e);
+                exit(1);
+            }
+            return;
+        },
+        Some(Subcommand::History { days }) => {
+            let path = opts.history_file.as_deref().unwrap_or_else(|| {
+                error!("history", "Pass the history file with --history-file");
+                exit(1);
+            });
+            let history = fs::read_to_string(path)
+                .map_err(|e| format!("Could not read {}: {}", path, e))
+                .and_then(|contents| History::parse(&contents));
+            match history {
+                Ok(history) => {
+                    let dates: Vec<String> = (0..(*days).max(1)).filter_map(history::local_date).collect();
+                    print!("{}", history::render_summary("Today", &history.summary(&dates[..1])));
+                    let title = format!("Last {} days", dates.len());
+                    print!("\n{}", history::render_summary(&title, &history.summary(&dates)));
+                },
+                Err(e) => {
+                    error!("history", "{}", e);
+                    exit(1);
+                },
+            }
+            return;
+        },
+        Some(Subcommand::Config { command }) => {
+            // The files of the daemon that are exported, and the only ones that
+            // are imported
+            let paths: Vec<String> = vec![
+                opts.config.clone(),
+                opts.remote_config.as_ref().map(|_| opts.remote_config_cache.clone()),
+                opts.state_file.clone(),
+                opts.history_file.clone(),
+            ]
+            .into_iter()
+            .flatten()
+            .collect();
+            let result = match command {
+                ConfigCommand::Export { archive } => backup::export(archive, &paths),
+                ConfigCommand::Import { archive } => backup::import(archive, opts.config.as_deref(), &paths),
+            };
+            match result {
+                Ok(files) => {
+                    for file in files {
+                        println!("{}", file);
+                    }
+                },
+                Err(e) => {
+                    error!("config", "{}", e);
+                    exit(1);
+                },
+            }
+            if let ConfigCommand::Import { .. } = command {
+                if let Err(e) = update::restart_service() {
+                    error!("config", "{}", e);
+                    exit(1);
+                }
+            }
+            return;
+        },
+        Some(Subcommand::TestInputs) | Some(Subcommand::Replay { .. }) | None => {},
+    }
+
+    // Load config
+    let local = opts.config.as_ref().map_or(Ok(String::new()), |path| {
+        fs::read_to_string(path).map_err(|e| format!("Could not read {}: {}", path, e))
+    });
+    let config = local
+        .and_then(|local| {
+            let overrides = match &opts.remote_config {
+                Some(url) => remote_config::load(&local, url, &opts.remote_config_cache),
+                None => String::new(),
+            };
+            let config = Config::parse_with_overrides(&local, &overrides)?;
+            match opts.profile.clone().or_else(|| jumper_profile(&config.profile_jumpers, &config.gpio)) {
+                Some(profile) => {
+                    info!("config", { profile: profile }, "Using profile {}", profile);
+                    Config::parse_with_profile(&local, &overrides, &profile)
+                },
+                None => Ok(config),
+            }
+            .map_err(|e| match &opts.config {
+                Some(path) => format!("{}: {}", path, e),
+                None => e,
+            })
+        })
+        .unwrap_or_else(|e| {
+            error!("config", "Could not load config: {}", e);
+            exit(1);
+        });
+    i18n::set_language(config.language);
+    if let Some(Subcommand::Replay { path }) = &opts.command {
+        let records = fs::read_to_string(path)
+            .map_err(|e| format!("Could not read {}: {}", path, e))
+            .and_then(|contents| contents.lines().map(str::parse).collect::<Result<Vec<Record>, _>>());
+        match records {
+            Ok(records) => {
+                let dead_bands = (opts.dead_band_low, opts.dead_band_high);
+                for event in recording::replay(&records, &config, opts.differential, dead_bands) {
+                    println!("{}", event);
+                }
+                return;
+            },
+            Err(e) => {
+                error!("replay", "{}", e);
+                exit(1);
+            },
+        }
+    }
+
+    crash::install_panic_hook(opts.crash_dir.clone().into(), format!("{:#?}\n\n{:#?}", opts, config));
+    let notifier = config.notify.as_ref().map(|notify| Notifier::new(notify.target.clone()));
+    if let Some(notifier) = &notifier {
+        notifier.install_panic_hook();
+    }
+    let analog_controls = config.analog_controls(opts.differential);
+
+    // Initialize ADCs. The default one is only needed if a channel is read
+    // from it.
+    let mut adcs = AdcBank::default();
+    let default_adc_used = analog_controls.iter().any(|control| control.adc.is_none())
+        || config.battery.as_ref().is_some_and(|battery| battery.adc.is_none());
+    let adc_devices = config.adcs.iter().map(|adc| (Some(adc.name.as_str()), adc.i2c.as_deref(), adc.address));
+    for (name, i2c, address) in default_adc_used.then_some((None, None, 0x48)).into_iter().chain(adc_devices) {
+        let i2c = i2c.unwrap_or(&opts.i2c);
+        match open_adc(i2c, address) {
+            Ok(adc) => adcs.add(name, Ads1115::new(i2c, address, adc)),
+            Err(e) => {
+                error!("adc", "ADC at 0x{:02x}: {}", address, e);
+                exit(1);
+            },
+        }
+    }
+
+    // Initialize GPIO
+    let gpio = Gpio::open(&config.gpio).unwrap_or_else(|e| panic!("{}", e));
+    let input_pin = |pin: u8| gpio.input(pin).unwrap_or_else(|e| panic!("{}", e));
+    let output_pin = |pin: u8, level: Level| gpio.output(pin, level).unwrap_or_else(|e| panic!("{}", e));
+    let now = Instant::now();
+    let gpio_inputs = config
+        .buttons
+        .iter()
+        .map(|button| {
+            let debounce = button.debounce(&config.debounce);
+            GpioInput {
+                name: button.name.clone(),
+                gpio: button.pin,
+                pin: Box::new(input_pin(button.pin)),
+                inverted: button.inverted,
+                debouncer: Debouncer::new(debounce.samples),
+                wear: SwitchWear::default(),
+                interval: Duration::from_millis(debounce.interval_ms),
+                next_sample: now,
+            }
+        })
+        .collect();
+
+    // Keep the power on
+    if let Some(latch) = &config.power_latch {
+        // The power must stay on until the system is halted, the kernel
+        // releases the latch (gpio-poweroff overlay)
+        output_pin(latch.hold_pin, if latch.inverted { Level::Low } else { Level::High }).keep();
+        info!("power", "Holding the power latch on GPIO pin {}", latch.hold_pin);
+    }
+    let power_request_pin = config.power_latch.as_ref().and_then(|latch| latch.request_pin).map(input_pin);
+
+    // Initialize rotary encoder
+    let encoder_pins = config.encoder.as_ref().map(|encoder| EncoderPins {
+        a: input_pin(encoder.pin_a),
+        b: input_pin(encoder.pin_b),
+        switch: encoder.switch_pin.map(input_pin),
+    });
+
+    // Initialize outputs
+    let output_pins: Vec<(Output, OutputPin)> = config
+        .outputs
+        .iter()
+        .map(|output| {
+            let pin = output_pin(output.pin, if output.inverted { Level::High } else { Level::Low });
+            (output.clone(), pin)
+        })
+        .collect();
+
+    // Initialize magic eye
+    let magic_eye_output = config.magic_eye.as_ref().and_then(|eye| match (eye.pin, eye.mcp4725_address) {
+        (Some(pin), _) => Some(MagicEyeOutput::Pwm(output_pin(pin, Level::Low))),
+        (None, Some(address)) => I2cdev::new(&opts.i2c)
+            .map(|dev| MagicEyeOutput::Mcp4725(dev, address))
+            .map_err(|e| error!("magic_eye", "Could not open {} for the magic eye: {}", opts.i2c, e))
+            .ok(),
+        (None, None) => unreachable!("validated in config"),
+    });
+
+    // Initialize display
+    let display = config.display.as_ref().and_then(|display| {
+        open_display(display, &opts.i2c, &gpio)
+            .map_err(|e| error!("display", "Could not initialize display: {}", e))
+            .ok()
+    });
+
+    if let Some(Subcommand::TestInputs) = opts.command {
+        inspect::run(GpioPinState::new(gpio_inputs), adcs, analog_controls, opts);
+    }
+
+    // Initialize headphone jack
+    let headphone_pins = config.headphones.as_ref().map(|headphones| {
+        let amp_enable_pin = headphones.amp_enable_pin.map(|pin| output_pin(pin, Level::High));
+        (input_pin(headphones.detect_pin), amp_enable_pin)
+    });
+
+    // Initialize ambient light sensor
+    let light_sensor = config.ambient_light.as_ref().and_then(|light| {
+        LightSensor::open(&opts.i2c, light.sensor, light.address())
+            .map_err(|e| error!("ambient_light", "Could not initialize the ambient light sensor: {}", e))
+            .ok()
+    });
+
+    // Initialize fan, stopped until the temperature is known
+    let fan_pin = config
+        .fan
+        .as_ref()
+        .map(|fan| output_pin(fan.pin, if fan.inverted { Level::High } else { Level::Low }));
+
+    // Initialize status LEDs
+    let led_pins: Vec<(Led, OutputPin)> = config
+        .leds
+        .iter()
+        .map(|led| (led.clone(), output_pin(led.pin, Level::Low)))
+        .collect();
+
+    // Check the audio output
+    if let Some((card, path)) = config.alsa.as_ref().and_then(|alsa| Some((&alsa.card, alsa.self_test.as_ref()?))) {
+        match alsa::self_test(card, path) {
+            Ok(()) => info!("alsa", "Audio self-test passed on card {}", card),
+            Err(e) => error!("alsa", "Audio self-test failed: {}", e),
+        }
+    }
+
+    // Open ALSA mixer
+    let mixer = config.alsa.as_ref().and_then(|alsa| {
+        let default_card = alsa.default_card();
+        Mixer::open(alsa::select_card(&alsa.preferred, &default_card, &alsa::cards()))
+            .map_err(|e| error!("alsa", "{}, setting the volume through volumio", e))
+            .ok()
+    });
+
+    let recorder = opts.record.as_ref().and_then(|path| {
+        Recorder::create(path)
+            .map_err(|e| error!("recording", "Could not create {}: {}", path, e))
+            .ok()
+            .map(Arc::new)
+    });
+
+    let shared = Arc::new(SharedState {
+        stations: config.stations.clone(),
+        mixer,
+        notifier,
+        recorder,
+        started: Some(Instant::now()),
+        config_hash: opts
+            .config
+            .as_ref()
+            .and_then(|path| fs::read(path).ok())
+            .map(|contents| status::fnv1a(&contents)),
+        ..Default::default()
+    });
+
+    // Load the state saved before the last restart
+    let snapshot = opts.state_file.as_ref().and_then(|path| match fs::read_to_string(path) {
+        Ok(contents) => Snapshot::parse(&contents)
+            .map_err(|e| warn!("state", "Ignoring state file {}: {}", path, e))
+            .ok(),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => None,
+        Err(e) => {
+            warn!("state", "Could not read state file {}: {}", path, e);
+            None
+        },
+    });
+    let initial_volume = snapshot.as_ref().map_or(INITIAL_VOLUME, |snapshot| snapshot.volume);
+    shared.volume.store(initial_volume, Ordering::SeqCst);
+    if !led_pins.is_empty() {
+        let shared = shared.clone();
+        thread::spawn(move || leds_loop(led_pins, shared));
+    }
+    // Shutting down doesn't need Volumio, so power requests are handled right
+    // away
+    if let Some(pin) = power_request_pin {
+        let shared = shared.clone();
+        thread::spawn(move || power_request_loop(pin, shared));
+    }
+
+    // Wait for volumio
+    wait_for_volumio(&opts.volumio_command, initial_volume);
+    shared.ready.store(true, Ordering::SeqCst);
+    if let Some(snapshot) = &snapshot {
+        shared.restore(&opts.volumio_command, snapshot, &config);
+    }
+
+    // Start threads
+    if !output_pins.is_empty() {
+        let shared = shared.clone();
+        thread::spawn(move || outputs_loop(output_pins, shared));
+    }
+    if let (Some((detect_pin, amp_enable_pin)), Some(headphones)) = (headphone_pins, config.headphones.clone()) {
+        let opts = opts.clone();
+        let shared = shared.clone();
+        thread::spawn(move || headphones_loop(detect_pin, amp_enable_pin, headphones, opts, shared));
+    }
+    {
+        let opts = opts.clone();
+        let shared = shared.clone();
+        let streams = config.streams;
+        thread::spawn(move || stream_watchdog_loop(opts, streams, shared));
+    }
+    if let Some(alsa) = config.alsa.clone().filter(|alsa| !alsa.preferred.is_empty() && shared.mixer.is_some()) {
+        let opts = opts.clone();
+        let shared = shared.clone();
+        thread::spawn(move || alsa_hotplug_loop(alsa, opts, shared));
+    }
+    {
+        let shared = shared.clone();
+        thread::spawn(move || wifi_loop(shared));
+    }
+    {
+        let shared = shared.clone();
+        thread::spawn(move || connectivity_loop(shared));
+    }
+    if let Err(e) = control::serve(&opts.control_socket, &opts.volumio_command, &config.lock, shared.clone()) {
+        error!("control", "{}", e);
+    }
+    // In low-write mode, the files are written at most once per interval to
+    // spare the SD card
+    let write_interval = config
+        .low_write
+        .as_ref()
+        .map_or(Duration::ZERO, |low_write| Duration::from_secs(low_write.interval_minutes * 60));
+    if let Some(path) = opts.status_file.clone() {
+        let file = PersistentFile::new("status", &path, write_interval);
+        let opts = opts.clone();
+        let shared = shared.clone();
+        thread::spawn(move || status_loop(file, opts, shared));
+    }
+    if let Some(path) = opts.history_file.clone() {
+        // A history file that can't be parsed is kept instead of being
+        // overwritten
+        match fs::read_to_string(&path).map_or(Ok(History::default()), |contents| History::parse(&contents)) {
+            Ok(history) => {
+                let file = PersistentFile::new("history", &path, write_interval.max(HISTORY_WRITE_INTERVAL));
+                let shared = shared.clone();
+                thread::spawn(move || history_loop(file, history, shared));
+            },
+            Err(e) => error!("history", "Could not parse history file {}, not recording: {}", path, e),
+        }
+    }
+    if let Some(path) = opts.state_file.clone() {
+        let file = PersistentFile::new("state", &path, write_interval);
+        let shared = shared.clone();
+        thread::spawn(move || snapshot_loop(file, snapshot, shared));
+    }
+    if let Some(idle) = config.idle.clone() {
+        let shared = shared.clone();
+        thread::spawn(move || idle_loop(idle, shared));
+    }
+    if let Some(rtc) = &config.rtc {
+        match I2cdev::new(&opts.i2c) {
+            Ok(dev) => {
+                let mut rtc = Ds3231::new(dev, rtc.address);
+                // Without NTP (yet), the system clock may be far off after
+                // power-on
+                if !rtc::clock_synchronized() {
+                    match rtc.read() {
+                        Ok(timestamp) => shared.clock_from_rtc.store(set_system_time(timestamp), Ordering::SeqCst),
+                        Err(e) => warn!("rtc", "Could not read the real-time clock: {}", e),
+                    }
+                }
+                let shared = shared.clone();
+                thread::spawn(move || rtc_loop(rtc, shared));
+            },
+            Err(e) => error!("rtc", "Could not open {}: {}", opts.i2c, e),
+        }
+    }
+    if !config.schedule.is_empty() {
+        let schedule = config.schedule.clone();
+        let opts = opts.clone();
+        let shared = shared.clone();
+        thread::spawn(move || schedule_loop(schedule, opts, shared));
+    }
+    if let Some(notify) = config.notify.clone() {
+        let shared = shared.clone();
+        thread::spawn(move || notify_loop(notify, shared));
+    }
+    if let Some(weather) = config.weather.clone() {
+        let shared = shared.clone();
+        thread::spawn(move || weather_loop(weather, shared));
+    }
+    if let Some(health) = config.health.clone() {
+        let shared = shared.clone();
+        thread::spawn(move || health_loop(health, shared));
+    }
+    if let (Some(fan), Some(pin)) = (config.fan.clone(), fan_pin) {
+        let shared = shared.clone();
+        thread::spawn(move || fan_loop(fan, pin, shared));
+    }
+    if let Some(dx) = config.dx.clone() {
+        let shared = shared.clone();
+        thread::spawn(move || dx_loop(dx, shared));
+    }
+    if let (Some(light), Some(sensor)) = (config.ambient_light.clone(), light_sensor) {
+        let shared = shared.clone();
+        thread::spawn(move || ambient_light_loop(light, sensor, shared));
+    }
+    if let Some(display) = display {
+        let artwork_cache = match &config.display {
+            Some(DisplayConfig::Epaper {
+                artwork_cache: Some(dir),
+                ..
+            }) => Some(ArtworkCache::new(dir)),
+            _ => None,
+        };
+        let schedule = config.schedule.clone();
+        let opts = opts.clone();
+        let shared = shared.clone();
+        thread::spawn(move || display_loop(display, artwork_cache, schedule, opts, shared));
+    }
+    if let (Some(eye), Some(output)) = (config.magic_eye.clone(), magic_eye_output) {
+        let shared = shared.clone();
+        thread::spawn(move || magic_eye_loop(eye, output, shared));
+    }
+    let adc_shared = shared.clone();
+    let opts_clone = opts.clone();
+    if let (Some(pins), Some(encoder)) = (encoder_pins, config.encoder.clone()) {
+        let opts = opts.clone();
+        let tuning = config.tuning();
+        let shared = shared.clone();
+        thread::spawn(move || encoder_loop(pins, encoder, opts, tuning, shared));
+    }
+    let config = Arc::new(config);
+    let (emulated_buttons_tx, emulated_buttons_rx) = mpsc::channel();
+    for device in config.evdev.iter().cloned() {
+        let opts = opts.clone();
+        let config = config.clone();
+        let shared = shared.clone();
+        let emulated_buttons = emulated_buttons_tx.clone();
+        thread::spawn(move || evdev_loop(device, opts, config, shared, emulated_buttons));
+    }
+    let tuning = config.tuning();
+    let battery = config.battery.clone();
+    let adc_thread =
+        thread::spawn(move || adc_loop(adcs, opts_clone, analog_controls, tuning, battery, adc_shared));
+    let mut gpio_state = GpioPinState::new(gpio_inputs);
+    gpio_state.recorder = shared.recorder.clone();
+    let gpio_thread = thread::spawn(move || gpio_loop(gpio_state, opts, config, shared, emulated_buttons_rx));
     adc_thread.join().unwrap();
     gpio_thread.join().unwrap();
 }
@@ -0,0 +1,79 @@
+use std::{
+    panic,
+    process::{Command, Stdio},
+    thread,
+};
+
+use crate::{config::NotifyTarget, log, Execute};
+
+/// Sends messages about failures to the owner of the radio.
+#[derive(Debug, Clone)]
+pub struct Notifier {
+    target: NotifyTarget,
+}
+
+impl Notifier {
+    pub fn new(target: NotifyTarget) -> Self {
+        Self { target }
+    }
+
+    /// Send a message in the background, so that the caller isn't held up
+    /// by a slow network.
+    pub fn send(&self, message: &str) {
+        let notifier = self.clone();
+        let message = message.to_string();
+        thread::spawn(move || notifier.send_blocking(&message));
+    }
+
+    fn send_blocking(&self, message: &str) {
+        let status_res = Command::new("/usr/bin/curl")
+            .args(curl_args(&self.target, &format!("inputd: {}", message)))
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .execute();
+        match status_res {
+            Ok(status) if status.success() => info!("notify", "Sent notification \"{}\"", message),
+            Ok(status) => error!("notify", { exit_status: status }, "Exit status {} when sending a notification", status),
+            Err(e) => error!("notify", "Could not send a notification: {}", e),
+        };
+    }
+
+    /// Send a message when a thread panics, in addition to the installed
+    /// panic hook.
+    pub fn install_panic_hook(&self) {
+        let notifier = self.clone();
+        let previous_hook = panic::take_hook();
+        panic::set_hook(Box::new(move |info| {
+            previous_hook(info);
+            let thread = thread::current();
+            notifier.send_blocking(&format!("Thread '{}' crashed: {}", thread.name().unwrap_or("unnamed"), info));
+        }));
+    }
+}
+
+/// Return the arguments of the curl command that sends a message.
+pub fn curl_args(target: &NotifyTarget, message: &str) -> Vec<String> {
+    let mut args: Vec<String> = vec!["-sf".into(), "--max-time".into(), "10".into()];
+    match target {
+        NotifyTarget::Webhook { url } => {
+            args.push("-H".into());
+            args.push("Content-Type: application/json".into());
+            args.push("-d".into());
+            args.push(format!("{{\"text\":\"{}\"}}", log::escape(message)));
+            args.push(url.clone());
+        },
+        NotifyTarget::Ntfy { server, topic } => {
+            args.push("-d".into());
+            args.push(message.to_string());
+            args.push(format!("{}/{}", server.trim_end_matches('/'), topic));
+        },
+        NotifyTarget::Telegram { bot_token, chat_id } => {
+            args.push("--data-urlencode".into());
+            args.push(format!("chat_id={}", chat_id));
+            args.push("--data-urlencode".into());
+            args.push(format!("text={}", message));
+            args.push(format!("https://api.telegram.org/bot{}/sendMessage", bot_token));
+        },
+    }
+    args
+}
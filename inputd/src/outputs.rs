@@ -0,0 +1,87 @@
+use std::time::Duration;
+
+use crate::config::{MagicEye, MagicEyeSource, Output, OutputTrigger};
+
+/// How long outputs blink after a playback error.
+pub const ERROR_BLINK_DURATION: Duration = Duration::from_secs(5);
+
+/// Blink period of outputs after a playback error.
+const BLINK_PERIOD_MS: u128 = 500;
+
+/// State of the radio that is shown on the outputs.
+pub struct RadioState {
+    /// Whether volumio is ready.
+    pub ready: bool,
+    /// Whether a playlist is playing.
+    pub playing: bool,
+    /// The current volume in percent.
+    pub volume: u8,
+    /// Time since the last playback error, if any.
+    pub since_error: Option<Duration>,
+    /// Whether the stream that was started last is not playing yet.
+    pub buffering: bool,
+    /// How accurately the tuning dial points at a station, in percent.
+    pub tuning_accuracy: u8,
+    /// Link quality of the Wi-Fi connection in percent, if connected.
+    pub wifi_quality: Option<u8>,
+    /// Whether the radio hasn't been used for a while.
+    pub idle: bool,
+    /// Brightness of the dial lamp and the display in percent, following the
+    /// ambient light or set manually.
+    pub brightness: u8,
+}
+
+/// Return the level of an output between 0.0 (off) and 1.0 (fully on).
+pub fn level(output: &Output, state: &RadioState) -> f64 {
+    let level = undimmed_level(output, state);
+    if output.dim_with_light {
+        level * state.brightness.min(100) as f64 / 100.0
+    } else {
+        level
+    }
+}
+
+/// Return the level of an output, without dimming it with the ambient light.
+fn undimmed_level(output: &Output, state: &RadioState) -> f64 {
+    if state.idle {
+        return 0.0;
+    }
+    if let (true, Some(since_error)) = (output.blink_on_error, state.since_error) {
+        if since_error < ERROR_BLINK_DURATION {
+            let on = since_error.as_millis() % BLINK_PERIOD_MS < BLINK_PERIOD_MS / 2;
+            return if on { 1.0 } else { 0.0 };
+        }
+    }
+
+    let on = match output.when {
+        OutputTrigger::Playing => state.playing,
+        OutputTrigger::Always => true,
+    };
+    if !on {
+        0.0
+    } else if output.dim_with_volume {
+        let min = output.min_brightness as f64;
+        (min + (100.0 - min) * state.volume.min(100) as f64 / 100.0) / 100.0
+    } else {
+        1.0
+    }
+}
+
+/// Return the target level of the magic eye between 0.0 (closed) and 1.0
+/// (fully open).
+pub fn magic_eye_level(eye: &MagicEye, state: &RadioState) -> f64 {
+    let level = match eye.source {
+        MagicEyeSource::Tuning if state.playing => state.tuning_accuracy.min(100) as f64 / 100.0,
+        MagicEyeSource::Playback if state.playing => match state.since_error {
+            Some(since_error) if since_error < ERROR_BLINK_DURATION => 0.0,
+            _ => 1.0,
+        },
+        MagicEyeSource::Wifi => state.wifi_quality.unwrap_or(0).min(100) as f64 / 100.0,
+        _ => 0.0,
+    };
+    if eye.inverted {
+        1.0 - level
+    } else {
+        level
+    }
+}
@@ -0,0 +1,106 @@
+use std::{
+    collections::BTreeMap,
+    fs, io,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+/// Contents of the persistent files that were not written yet, by path.
+static PENDING: Mutex<BTreeMap<String, String>> = Mutex::new(BTreeMap::new());
+
+/// A file that is rewritten periodically, e.g. the state file.
+///
+/// To spare the SD card, the file is written at most once per interval, so
+/// that several updates are written at once. Pending updates are written by
+/// `flush`, e.g. before shutting down.
+///
+/// The root filesystem may be mounted read-only as well, so a failure is only
+/// logged once and the daemon keeps the state in memory until the file can be
+/// written again.
+pub struct PersistentFile {
+    /// Subsystem that failures are logged as.
+    subsystem: &'static str,
+    path: String,
+    /// Minimum time between two writes.
+    interval: Duration,
+    /// The time the file was last written.
+    last_write: Option<Instant>,
+    /// Whether the last write failed.
+    failing: bool,
+}
+
+impl PersistentFile {
+    pub fn new(subsystem: &'static str, path: &str, interval: Duration) -> Self {
+        Self {
+            subsystem,
+            path: path.to_string(),
+            interval,
+            last_write: None,
+            failing: false,
+        }
+    }
+
+    /// Replace the contents of the file, once the interval has passed.
+    pub fn update(&mut self, contents: String) {
+        PENDING.lock().unwrap().insert(self.path.clone(), contents);
+        self.sync(Instant::now());
+    }
+
+    /// Write the pending contents if the interval has passed since the last
+    /// write.
+    ///
+    /// Returns whether nothing is pending anymore.
+    pub fn sync(&mut self, now: Instant) -> bool {
+        if self.last_write.is_some_and(|last_write| now.duration_since(last_write) < self.interval) {
+            return !PENDING.lock().unwrap().contains_key(&self.path);
+        }
+        let contents = match PENDING.lock().unwrap().remove(&self.path) {
+            Some(contents) => contents,
+            None => return true,
+        };
+        match write(&self.path, &contents) {
+            Ok(()) => {
+                if self.failing {
+                    info!(self.subsystem, "Writing {} again", self.path);
+                    self.failing = false;
+                }
+                self.last_write = Some(now);
+                true
+            },
+            Err(e) => {
+                if !self.failing {
+                    self.failing = true;
+                    if e.kind() == io::ErrorKind::ReadOnlyFilesystem {
+                        warn!(
+                            self.subsystem,
+                            "Could not write {}, the filesystem is read-only, keeping the state in memory", self.path
+                        );
+                    } else {
+                        error!(self.subsystem, "Could not write {}: {}", self.path, e);
+                    }
+                }
+                // Try again later, unless there are newer contents already
+                PENDING.lock().unwrap().entry(self.path.clone()).or_insert(contents);
+                false
+            },
+        }
+    }
+}
+
+/// Write the pending contents of all persistent files, e.g. before shutting
+/// down.
+pub fn flush() {
+    let pending = std::mem::take(&mut *PENDING.lock().unwrap());
+    for (path, contents) in pending {
+        if let Err(e) = write(&path, &contents) {
+            error!("persist", "Could not write {}: {}", path, e);
+        }
+    }
+}
+
+/// Replace the contents of a file atomically, so that readers never see a
+/// partial file.
+fn write(path: &str, contents: &str) -> io::Result<()> {
+    let tmp_path = format!("{}.tmp", path);
+    fs::write(&tmp_path, contents).and_then(|_| fs::rename(&tmp_path, path))
+}
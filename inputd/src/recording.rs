@@ -0,0 +1,175 @@
+use std::{
+    collections::HashMap,
+    fmt,
+    fs::File,
+    io::{self, BufWriter, Write},
+    str::FromStr,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use debouncr::Edge;
+
+use crate::{
+    apply_dead_bands,
+    config::{channel_name, Channel, Config, Role},
+    debounce::Debouncer,
+    map_potentiometer_value,
+};
+
+/// Buffered samples are written to the file at least this often.
+const FLUSH_INTERVAL: Duration = Duration::from_secs(1);
+
+/// A raw input sample.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Sample {
+    /// The level of the pin of a button (`true` if low).
+    Gpio { button: String, low: bool },
+    /// A raw ADC reading. `adc` is the name of the ADC, if not the default
+    /// one.
+    Adc {
+        adc: Option<String>,
+        channel: Channel,
+        raw: i16,
+    },
+}
+
+/// A sample with the time in milliseconds since the recording was started.
+///
+/// In the recording file, every record is a line like `1250 gpio ukw 1` or
+/// `1300 adc A0 12034`. Channels of an additional ADC are prefixed with its
+/// name, e.g. `1300 adc power/A3 20112`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Record {
+    pub time_ms: u64,
+    pub sample: Sample,
+}
+
+impl fmt::Display for Record {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.sample {
+            Sample::Gpio { button, low } => write!(f, "{} gpio {} {}", self.time_ms, button, *low as u8),
+            Sample::Adc { adc, channel, raw } => {
+                write!(f, "{} adc {} {}", self.time_ms, channel_name(adc.as_deref(), *channel), raw)
+            },
+        }
+    }
+}
+
+impl FromStr for Record {
+    type Err = String;
+
+    fn from_str(line: &str) -> Result<Self, Self::Err> {
+        let parts: Vec<&str> = line.split_whitespace().collect();
+        let invalid = || format!("Invalid record \"{}\"", line);
+        let (time_ms, kind, source, value) = match parts[..] {
+            [time_ms, kind, source, value] => (time_ms.parse().map_err(|_| invalid())?, kind, source, value),
+            _ => return Err(invalid()),
+        };
+        let sample = match kind {
+            "gpio" => Sample::Gpio {
+                button: source.to_string(),
+                low: value == "1",
+            },
+            "adc" => {
+                let (adc, channel) = match source.split_once('/') {
+                    Some((adc, channel)) => (Some(adc.to_string()), channel),
+                    None => (None, source),
+                };
+                Sample::Adc {
+                    adc,
+                    channel: channel.parse()?,
+                    raw: value.parse().map_err(|_| invalid())?,
+                }
+            },
+            _ => return Err(invalid()),
+        };
+        Ok(Self { time_ms, sample })
+    }
+}
+
+/// Records raw samples to a file.
+pub struct Recorder {
+    started: Instant,
+    writer: Mutex<(BufWriter<File>, Instant)>,
+}
+
+impl Recorder {
+    pub fn create(path: &str) -> io::Result<Self> {
+        let now = Instant::now();
+        Ok(Self {
+            started: now,
+            writer: Mutex::new((BufWriter::new(File::create(path)?), now)),
+        })
+    }
+
+    /// Record a sample. Write errors are ignored, recording is best effort.
+    pub fn record(&self, sample: Sample) {
+        let now = Instant::now();
+        let record = Record {
+            time_ms: now.duration_since(self.started).as_millis() as u64,
+            sample,
+        };
+        let mut writer = self.writer.lock().unwrap();
+        let (file, last_flush) = &mut *writer;
+        let _ = writeln!(file, "{}", record);
+        if now.duration_since(*last_flush) >= FLUSH_INTERVAL {
+            let _ = file.flush();
+            *last_flush = now;
+        }
+    }
+}
+
+/// Process a recording like the daemon would, returning the resulting
+/// events: debounced presses and releases, and changes of the analog
+/// controls.
+pub fn replay(records: &[Record], config: &Config, differential: bool, dead_bands: (u8, u8)) -> Vec<String> {
+    let mut buttons: HashMap<&str, (Debouncer, bool)> = config
+        .buttons
+        .iter()
+        .map(|button| {
+            let debounce = button.debounce(&config.debounce);
+            (button.name.as_str(), (Debouncer::new(debounce.samples), button.inverted))
+        })
+        .collect();
+    let controls = config.analog_controls(differential);
+    let mut values: Vec<Option<u8>> = vec![None; controls.len()];
+
+    let mut events = vec![];
+    for record in records {
+        match &record.sample {
+            Sample::Gpio { button, low } => {
+                let (debouncer, inverted) = match buttons.get_mut(button.as_str()) {
+                    Some(button) => button,
+                    None => continue,
+                };
+                match (debouncer.update(*low), *inverted) {
+                    (Some(Edge::Rising), false) | (Some(Edge::Falling), true) => {
+                        events.push(format!("{} pressed {}", record.time_ms, button))
+                    },
+                    (Some(Edge::Falling), false) | (Some(Edge::Rising), true) => {
+                        events.push(format!("{} released {}", record.time_ms, button))
+                    },
+                    (None, _) => {},
+                }
+            },
+            Sample::Adc { adc, channel, raw } => {
+                for (control, last_value) in controls.iter().zip(values.iter_mut()) {
+                    if control.adc != *adc || control.channel != *channel {
+                        continue;
+                    }
+                    let mut value = map_potentiometer_value(&control.lookup_table, (*raw).max(0) as u16);
+                    if control.role == Role::Volume {
+                        value = apply_dead_bands(value, dead_bands.0, dead_bands.1);
+                    }
+                    if *last_value != Some(value) {
+                        let channel = channel_name(adc.as_deref(), *channel);
+                        events.push(format!("{} {} ({:?}) {}", record.time_ms, channel, control.role, value));
+                        *last_value = Some(value);
+                    }
+                }
+            },
+        }
+    }
+    events
+}
@@ -0,0 +1,59 @@
+use std::{
+    fs,
+    process::{Command, Stdio},
+};
+
+use crate::config::Config;
+
+/// Load the configuration overrides from a central server, so that the
+/// stations of several radios can be managed in one place.
+///
+/// The overrides are cached, so that the radio keeps them while the server
+/// can't be reached. If the overrides can't be fetched or are invalid with
+/// the local configuration, the cached ones are returned, and without a cache
+/// (or if the cached ones are invalid with the local configuration as well) no
+/// overrides.
+pub fn load(local: &str, url: &str, cache: &str) -> String {
+    match fetch(url).and_then(|overrides| Config::parse_with_overrides(local, &overrides).map(|_| overrides)) {
+        Ok(overrides) => {
+            info!("config", { url: url }, "Loaded overrides from {}", url);
+            if fs::read_to_string(cache).ok().as_ref() != Some(&overrides) {
+                if let Err(e) = fs::write(cache, &overrides) {
+                    warn!("config", "Could not cache overrides in {}: {}", cache, e);
+                }
+            }
+            return overrides;
+        },
+        Err(e) => warn!("config", "Could not load overrides from {}: {}", url, e),
+    }
+    match fs::read_to_string(cache) {
+        // The local configuration may have changed since they were cached
+        Ok(overrides) => match Config::parse_with_overrides(local, &overrides) {
+            Ok(_) => {
+                info!("config", "Using the cached overrides from {}", cache);
+                overrides
+            },
+            Err(e) => {
+                warn!("config", "Ignoring the cached overrides from {}: {}", cache, e);
+                String::new()
+            },
+        },
+        Err(_) => {
+            warn!("config", "No cached overrides, using the local configuration only");
+            String::new()
+        },
+    }
+}
+
+/// Download the overrides with curl.
+fn fetch(url: &str) -> Result<String, String> {
+    match Command::new("/usr/bin/curl")
+        .args(["-sfL", "--max-time", "10", url])
+        .stderr(Stdio::null())
+        .output()
+    {
+        Ok(output) if output.status.success() => Ok(String::from_utf8_lossy(&output.stdout).into_owned()),
+        Ok(output) => Err(format!("Exit status {}", output.status)),
+        Err(e) => Err(format!("Could not run curl: {}", e)),
+    }
+}
@@ -0,0 +1,146 @@
+use std::process::{Command, Stdio};
+
+use embedded_hal::blocking::i2c::{Write, WriteRead};
+use linux_embedded_hal::I2cdev;
+
+/// Register of the seconds, the first of the seven time registers.
+const TIME_REGISTER: u8 = 0x00;
+
+/// Status register.
+const STATUS_REGISTER: u8 = 0x0f;
+
+/// Oscillator stop flag in the status register. It is set when the clock
+/// stopped, e.g. because the backup battery is empty, so the time is invalid.
+const OSCILLATOR_STOPPED: u8 = 0x80;
+
+/// Seconds per day.
+const DAY: u64 = 24 * 60 * 60;
+
+/// A DS3231 real-time clock on the I2C bus, keeping the time in UTC.
+pub struct Ds3231 {
+    dev: I2cdev,
+    address: u8,
+}
+
+impl Ds3231 {
+    pub fn new(dev: I2cdev, address: u8) -> Self {
+        Self { dev, address }
+    }
+
+    /// Read the time in seconds since the Unix epoch.
+    pub fn read(&mut self) -> Result<u64, String> {
+        let mut status = [0];
+        self.dev
+            .write_read(self.address, &[STATUS_REGISTER], &mut status)
+            .map_err(|e| format!("Could not read from DS3231: {}", e))?;
+        if status[0] & OSCILLATOR_STOPPED != 0 {
+            return Err("The clock of the DS3231 was stopped, its time is invalid".into());
+        }
+        let mut registers = [0; 7];
+        self.dev
+            .write_read(self.address, &[TIME_REGISTER], &mut registers)
+            .map_err(|e| format!("Could not read from DS3231: {}", e))?;
+        decode(&registers).ok_or_else(|| format!("Invalid time registers {:02x?}", registers))
+    }
+
+    /// Set the time in seconds since the Unix epoch.
+    pub fn write(&mut self, timestamp: u64) -> Result<(), String> {
+        let mut data = vec![TIME_REGISTER];
+        data.extend_from_slice(&encode(timestamp));
+        self.dev
+            .write(self.address, &data)
+            .map_err(|e| format!("Could not write to DS3231: {}", e))?;
+        // The time is valid again
+        self.dev
+            .write(self.address, &[STATUS_REGISTER, 0x00])
+            .map_err(|e| format!("Could not write to DS3231: {}", e))
+    }
+}
+
+fn from_bcd(value: u8) -> u8 {
+    (value >> 4) * 10 + (value & 0x0f)
+}
+
+fn to_bcd(value: u8) -> u8 {
+    ((value / 10) << 4) | (value % 10)
+}
+
+/// Return the number of days since the Unix epoch of a date.
+fn days_from_civil(year: u64, month: u64, day: u64) -> u64 {
+    // Years start in March, so that the leap day is the last day of the year
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year / 400;
+    let year_of_era = year % 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+/// Return the year, month and day of a number of days since the Unix epoch.
+fn civil_from_days(days: u64) -> (u64, u64, u64) {
+    let days = days + 719_468;
+    let era = days / 146_097;
+    let day_of_era = days % 146_097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month + 2) / 5 + 1;
+    let month = if month < 10 { month + 3 } else { month - 9 };
+    let year = era * 400 + year_of_era + u64::from(month <= 2);
+    (year, month, day)
+}
+
+/// Convert the seven time registers to seconds since the Unix epoch.
+pub fn decode(registers: &[u8; 7]) -> Option<u64> {
+    let seconds = u64::from(from_bcd(registers[0] & 0x7f));
+    let minutes = u64::from(from_bcd(registers[1] & 0x7f));
+    let hours = if registers[2] & 0x40 != 0 {
+        // 12 hour mode, bit 5 is PM
+        let hours = u64::from(from_bcd(registers[2] & 0x1f)) % 12;
+        if registers[2] & 0x20 != 0 {
+            hours + 12
+        } else {
+            hours
+        }
+    } else {
+        u64::from(from_bcd(registers[2] & 0x3f))
+    };
+    let day = u64::from(from_bcd(registers[4] & 0x3f));
+    let month = u64::from(from_bcd(registers[5] & 0x1f));
+    let century = if registers[5] & 0x80 != 0 { 2100 } else { 2000 };
+    let year = century + u64::from(from_bcd(registers[6]));
+    if seconds > 59 || minutes > 59 || hours > 23 || !(1..=31).contains(&day) || !(1..=12).contains(&month) {
+        return None;
+    }
+    Some(days_from_civil(year, month, day) * DAY + hours * 3600 + minutes * 60 + seconds)
+}
+
+/// Convert seconds since the Unix epoch to the seven time registers (24 hour
+/// mode).
+pub fn encode(timestamp: u64) -> [u8; 7] {
+    let days = timestamp / DAY;
+    let time = timestamp % DAY;
+    let (year, month, day) = civil_from_days(days);
+    // The 1st of January 1970 was a Thursday, counting from Monday (1) to
+    // Sunday (7)
+    let weekday = ((days + 3) % 7 + 1) as u8;
+    let century = if year >= 2100 { 0x80 } else { 0x00 };
+    [
+        to_bcd((time % 60) as u8),
+        to_bcd((time / 60 % 60) as u8),
+        to_bcd((time / 3600) as u8),
+        weekday,
+        to_bcd(day as u8),
+        century | to_bcd(month as u8),
+        to_bcd((year % 100) as u8),
+    ]
+}
+
+/// Return whether the system clock is synchronized with NTP.
+pub fn clock_synchronized() -> bool {
+    Command::new("timedatectl")
+        .args(["show", "--property=NTPSynchronized", "--value"])
+        .stderr(Stdio::null())
+        .output()
+        .is_ok_and(|output| String::from_utf8_lossy(&output.stdout).trim() == "yes")
+}
@@ -0,0 +1,224 @@
+use std::{
+    fs,
+    io::{self, BufRead, Write as _},
+    thread,
+    time::{Duration, Instant},
+};
+
+use ads1x1x::{Ads1x1x, DataRate16Bit, FullScaleRange, SlaveAddr};
+use linux_embedded_hal::I2cdev;
+use rppal::gpio::{Gpio, InputPin, Level};
+
+use crate::{
+    alsa,
+    config::{ButtonAction, Channel, Config},
+    read_channel, LOOKUP_TABLE_VOL,
+};
+
+/// GPIO pins that are probed for buttons. GPIO 2 and 3 are used by I2C.
+const CANDIDATE_PINS: std::ops::RangeInclusive<u8> = 4..=27;
+
+/// Time to wait for a button to be pressed.
+const PRESS_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The buttons of the original radio and what they do.
+const BUTTONS: [(&str, &str); 6] = [
+    ("aus", "shutdown"),
+    ("tonabnehmer", "jazz"),
+    ("ukw", "mellow"),
+    ("kurz", "world"),
+    ("mittel", "rockblues"),
+    ("lang", "progrock"),
+];
+
+/// A button found by the setup wizard.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DetectedButton {
+    pub name: String,
+    pub pin: u8,
+    pub inverted: bool,
+    pub action: ButtonAction,
+}
+
+/// Everything the setup wizard found out.
+#[derive(Debug, Clone, Default)]
+pub struct Setup {
+    pub buttons: Vec<DetectedButton>,
+    /// Calibration table of the volume knob on A0.
+    pub volume_table: Option<Vec<(u16, u16)>>,
+    /// ALSA card and mixer control.
+    pub alsa: Option<(String, String)>,
+}
+
+impl Setup {
+    /// Render the configuration file.
+    pub fn render(&self) -> String {
+        let mut config = String::from("# Generated by `inputd setup`, see config.example.toml for all options.\n");
+        for button in &self.buttons {
+            config.push_str(&format!("\n[[buttons]]\nname = \"{}\"\npin = {}\n", button.name, button.pin));
+            if button.inverted {
+                config.push_str("inverted = true\n");
+            }
+            match &button.action {
+                ButtonAction::Playlist(playlist) => {
+                    config.push_str(&format!("action = {{ playlist = \"{}\" }}\n", playlist))
+                },
+                ButtonAction::Band(band) => config.push_str(&format!("action = {{ band = \"{}\" }}\n", band)),
+                ButtonAction::SleepTimer(minutes) => {
+                    config.push_str(&format!("action = {{ sleep_timer = {} }}\n", minutes))
+                },
+                ButtonAction::Stop => config.push_str("action = \"stop\"\n"),
+                ButtonAction::Mute => config.push_str("action = \"mute\"\n"),
+                ButtonAction::LineOut => config.push_str("action = \"line_out\"\n"),
+                ButtonAction::Duck(duck) => config.push_str(&format!(
+                    "action = {{ duck = {{ volume = {}, duration_s = {} }} }}\n",
+                    duck.volume, duck.duration_s
+                )),
+                ButtonAction::Pause => config.push_str("action = \"pause\"\n"),
+                ButtonAction::Shutdown => config.push_str("action = \"shutdown\"\n"),
+            }
+        }
+        if let Some(table) = &self.volume_table {
+            let entries: Vec<String> = table.iter().map(|(angle, value)| format!("[{}, {}]", angle, value)).collect();
+            config.push_str(&format!(
+                "\n[[analog]]\nchannel = \"A0\"\nrole = \"volume\"\nlookup_table = [{}]\n",
+                entries.join(", ")
+            ));
+        }
+        if let Some((card, mixer)) = &self.alsa {
+            config.push_str(&format!("\n[alsa]\ncard = \"{}\"\nmixer = \"{}\"\n", card, mixer));
+        }
+        config
+    }
+}
+
+/// Scale a calibration table to the measured range of a potentiometer,
+/// keeping the shape of its curve.
+///
+/// Returns `None` if the range is too small for a strictly increasing table.
+pub fn scale_lookup_table(table: &[(u16, u16)], min: u16, max: u16) -> Option<Vec<(u16, u16)>> {
+    let (first, last) = (table.first()?.1 as u32, table.last()?.1 as u32);
+    if max <= min || last <= first {
+        return None;
+    }
+    let scaled: Vec<(u16, u16)> = table
+        .iter()
+        .map(|(angle, value)| {
+            let value = min as u32 + (*value as u32 - first) * (max - min) as u32 / (last - first);
+            (*angle, value as u16)
+        })
+        .collect();
+    if scaled.windows(2).all(|pair| pair[1].1 > pair[0].1) {
+        Some(scaled)
+    } else {
+        None
+    }
+}
+
+/// Return the first pin whose level differs from the baseline, and whether
+/// it is high now.
+///
+/// Pins of buttons that were already found are ignored, because the band
+/// keys of the radio are interlocked: pressing a key releases the previous
+/// one.
+pub fn changed_pin(baseline: &[(u8, bool)], levels: &[(u8, bool)], known: &[u8]) -> Option<(u8, bool)> {
+    baseline
+        .iter()
+        .zip(levels)
+        .find(|((pin, before), (_, now))| before != now && !known.contains(pin))
+        .map(|(_, (pin, high))| (*pin, *high))
+}
+
+/// Run the interactive setup wizard and write the configuration file.
+pub fn run(i2c: &str, path: &str) -> Result<(), String> {
+    let mut setup = Setup::default();
+
+    // I2C bus and ADC
+    println!("Checking the ADC on {}...", i2c);
+    let dev = I2cdev::new(i2c).map_err(|e| format!("Could not open {}: {}", i2c, e))?;
+    let mut adc = Ads1x1x::new_ads1115(dev, SlaveAddr::default());
+    adc.set_full_scale_range(FullScaleRange::Within4_096V)
+        .map_err(|e| format!("No ADS1115 found on {}: {:?}", i2c, e))?;
+    let _ = adc.set_data_rate(DataRate16Bit::Sps128);
+    println!("Found an ADS1115.");
+
+    // Buttons
+    let gpio = Gpio::new().map_err(|e| format!("Could not initialize GPIO: {}", e))?;
+    let pins: Vec<(u8, InputPin)> = CANDIDATE_PINS
+        .filter_map(|pin| gpio.get(pin).ok().map(|p| (pin, p.into_input_pullup())))
+        .collect();
+    let read_levels = || -> Vec<(u8, bool)> {
+        pins.iter().map(|(pin, input)| (*pin, input.read() == Level::High)).collect()
+    };
+    for (name, action) in BUTTONS.iter() {
+        // Switches like "aus" may be engaged, so the baseline is taken
+        // right before waiting for the press
+        println!("Press the {} key now (waiting {}s)...", name, PRESS_TIMEOUT.as_secs());
+        let baseline = read_levels();
+        let known: Vec<u8> = setup.buttons.iter().map(|button| button.pin).collect();
+        let started = Instant::now();
+        let detected = loop {
+            if let Some(changed) = changed_pin(&baseline, &read_levels(), &known) {
+                break Some(changed);
+            }
+            if started.elapsed() > PRESS_TIMEOUT {
+                break None;
+            }
+            thread::sleep(Duration::from_millis(10));
+        };
+        match detected {
+            Some((pin, high)) => {
+                println!("Found {} on GPIO {}", name, pin);
+                setup.buttons.push(DetectedButton {
+                    name: name.to_string(),
+                    pin,
+                    inverted: high,
+                    action: match *action {
+                        "shutdown" => ButtonAction::Shutdown,
+                        playlist => ButtonAction::Playlist(playlist.into()),
+                    },
+                });
+                thread::sleep(Duration::from_secs(1));
+            },
+            None => println!("No key pressed, skipping {}", name),
+        }
+    }
+
+    // Volume knob calibration
+    prompt("Turn the volume knob fully counter-clockwise and press Enter")?;
+    let min = read_channel(&mut adc, Channel::A0)?.max(0) as u16;
+    prompt("Turn the volume knob fully clockwise and press Enter")?;
+    let max = read_channel(&mut adc, Channel::A0)?.max(0) as u16;
+    setup.volume_table = scale_lookup_table(&LOOKUP_TABLE_VOL, min, max);
+    match setup.volume_table {
+        Some(_) => println!("Calibrated the volume knob from {} to {}", min, max),
+        None => println!("Range {}-{} is too small, using the built-in calibration", min, max),
+    }
+
+    // ALSA mixer
+    match alsa::controls("0") {
+        Ok(controls) if !controls.is_empty() => {
+            println!("Mixer controls of card 0: {}", controls.join(", "));
+            let control = prompt("Mixer control to set the volume with (empty to use volumio)")?;
+            if controls.contains(&control) {
+                setup.alsa = Some(("0".into(), control));
+            }
+        },
+        _ => println!("No ALSA mixer controls found, the volume is set through volumio"),
+    }
+
+    let config = setup.render();
+    Config::parse(&config).map_err(|e| format!("Generated an invalid configuration: {}", e))?;
+    fs::write(path, config).map_err(|e| format!("Could not write {}: {}", path, e))?;
+    println!("Wrote {}", path);
+    Ok(())
+}
+
+/// Print a prompt and read a line from stdin.
+fn prompt(text: &str) -> Result<String, String> {
+    print!("{}: ", text);
+    io::stdout().flush().map_err(|e| e.to_string())?;
+    let mut line = String::new();
+    io::stdin().lock().read_line(&mut line).map_err(|e| e.to_string())?;
+    Ok(line.trim().to_string())
+}
@@ -0,0 +1,71 @@
+/// The state of the radio that is restored after a restart of the daemon,
+/// written to the state file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Snapshot {
+    /// The playlist that is currently selected.
+    pub playlist: Option<String>,
+    /// The volume that was last set.
+    pub volume: u8,
+    /// Whether the output was muted with the mute gesture.
+    pub muted: bool,
+    /// Whether the line output is used instead of the speakers.
+    pub line_out: bool,
+    /// Whether the radio is locked against small children.
+    pub locked: bool,
+    /// The time at which the sleep timer stops playback, in seconds since the
+    /// Unix epoch. A point in time instead of the remaining time, so that the
+    /// snapshot does not change while the timer runs.
+    pub sleep_timer_until: Option<u64>,
+}
+
+impl Snapshot {
+    /// Render the snapshot as `key: value` lines.
+    pub fn render(&self) -> String {
+        let mut lines = vec![];
+        if let Some(playlist) = &self.playlist {
+            lines.push(format!("playlist: {}", playlist));
+        }
+        lines.push(format!("volume: {}", self.volume));
+        lines.push(format!("muted: {}", self.muted));
+        lines.push(format!("line out: {}", self.line_out));
+        lines.push(format!("locked: {}", self.locked));
+        if let Some(until) = self.sleep_timer_until {
+            lines.push(format!("sleep timer until: {}", until));
+        }
+        lines.join("\n") + "\n"
+    }
+
+    /// Parse a snapshot written by `render`.
+    ///
+    /// Unknown keys are ignored, so that a snapshot written by a newer
+    /// version can be restored after a downgrade.
+    pub fn parse(contents: &str) -> Result<Self, String> {
+        let mut playlist = None;
+        let mut volume = None;
+        let mut muted = false;
+        let mut line_out = false;
+        let mut locked = false;
+        let mut sleep_timer_until = None;
+        for line in contents.lines().filter(|line| !line.trim().is_empty()) {
+            let (key, value) = line.split_once(": ").ok_or_else(|| format!("Invalid line \"{}\"", line))?;
+            let invalid = |_| format!("Invalid {} \"{}\"", key, value);
+            match key {
+                "playlist" => playlist = Some(value.to_string()),
+                "volume" => volume = Some(value.parse::<u8>().map_err(invalid)?.min(100)),
+                "muted" => muted = value.parse().map_err(|_| format!("Invalid {} \"{}\"", key, value))?,
+                "line out" => line_out = value.parse().map_err(|_| format!("Invalid {} \"{}\"", key, value))?,
+                "locked" => locked = value.parse().map_err(|_| format!("Invalid {} \"{}\"", key, value))?,
+                "sleep timer until" => sleep_timer_until = Some(value.parse().map_err(invalid)?),
+                _ => {},
+            }
+        }
+        Ok(Snapshot {
+            playlist,
+            volume: volume.ok_or("Missing volume")?,
+            muted,
+            line_out,
+            locked,
+            sleep_timer_until,
+        })
+    }
+}
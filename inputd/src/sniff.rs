@@ -0,0 +1,56 @@
+use std::process::{Command, Stdio};
+
+/// Return the media type of the final response of the headers dumped by
+/// curl, without parameters like the charset.
+pub fn content_type(headers: &str) -> Option<String> {
+    let mut content_type = None;
+    for line in headers.lines() {
+        // Every response of a redirect chain starts with a status line
+        if line.starts_with("HTTP/") || line.starts_with("ICY ") {
+            content_type = None;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            if name.trim().eq_ignore_ascii_case("content-type") {
+                let media_type = value.split(';').next().unwrap_or_default().trim().to_lowercase();
+                content_type = Some(media_type).filter(|media_type| !media_type.is_empty());
+            }
+        }
+    }
+    content_type
+}
+
+/// Return why a stream with the media type can't be played, if it's not a
+/// format that volumio plays.
+pub fn problem(content_type: &str) -> Option<&'static str> {
+    let (kind, subtype) = content_type.split_once('/').unwrap_or((content_type, ""));
+    match (kind, subtype) {
+        ("audio", _) | ("video", _) => None,
+        ("application", "ogg" | "octet-stream" | "vnd.apple.mpegurl" | "x-mpegurl" | "pls+xml") => None,
+        ("text", "html") | ("application", "xhtml+xml") => Some("probably a web page, not a stream"),
+        _ => Some("not an audio format"),
+    }
+}
+
+/// Fetch the response headers of a stream with curl.
+///
+/// Streams don't end, so the transfer is cut off after a few seconds. The
+/// headers are dumped as they arrive, so they're complete by then.
+pub fn fetch_headers(url: &str) -> Option<String> {
+    if !url.starts_with("http://") && !url.starts_with("https://") {
+        return None;
+    }
+    let output = Command::new("/usr/bin/curl")
+        .args(["-sL", "--max-time", "5", "-D", "-", "-o", "/dev/null", url])
+        .stderr(Stdio::null())
+        .output()
+        .ok()?;
+    Some(String::from_utf8_lossy(&output.stdout).into_owned()).filter(|headers| !headers.is_empty())
+}
+
+/// Describe the problem with a stream that didn't start, if its content type
+/// tells what's wrong.
+pub fn diagnose(station: &str, url: &str) -> Option<String> {
+    let content_type = content_type(&fetch_headers(url)?)?;
+    let problem = problem(&content_type)?;
+    Some(format!("Station {} returned {} — {}", station, content_type, problem))
+}
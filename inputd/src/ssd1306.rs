@@ -0,0 +1,211 @@
+use embedded_hal::blocking::i2c::Write;
+use linux_embedded_hal::I2cdev;
+
+use crate::{
+    display::{self, Display, Screen},
+    font,
+    i18n::{tr, Message},
+};
+
+const WIDTH: usize = 128;
+const PAGES: usize = 8;
+
+/// Number of characters that fit on a line (6 pixels per character).
+const COLUMNS: usize = WIDTH / 6;
+
+/// Scale of the large clock shown while nothing is playing.
+const CLOCK_SCALE: usize = 3;
+
+/// Width of the large clock (HH:MM).
+const CLOCK_WIDTH: usize = 5 * 6 * CLOCK_SCALE;
+
+/// Number of refreshes after which the clock screen is moved, so that the
+/// same pixels aren't lit all night (about a minute).
+const CLOCK_SHIFT_TICKS: usize = 200;
+
+/// Initialization sequence for a 128x64 panel with the internal charge pump.
+const INIT: [u8; 25] = [
+    0xae, // Display off
+    0xd5, 0x80, // Clock divide ratio
+    0xa8, 0x3f, // Multiplex ratio (64 rows)
+    0xd3, 0x00, // Display offset
+    0x40, // Start line 0
+    0x8d, 0x14, // Enable charge pump
+    0x20, 0x00, // Horizontal addressing mode
+    0xa1, // Segment remap
+    0xc8, // Scan from COM63 to COM0
+    0xda, 0x12, // COM pin configuration
+    0x81, 0xcf, // Contrast
+    0xd9, 0xf1, // Precharge period
+    0xdb, 0x40, // VCOMH deselect level
+    0xa4, // Show RAM contents
+    0xa6, // Not inverted
+    0xaf, // Display on
+];
+
+/// A 128x64 SSD1306 OLED connected to the I2C bus.
+pub struct Ssd1306 {
+    dev: I2cdev,
+    address: u8,
+    /// The frame that is currently shown, if any.
+    shown: Option<Frame>,
+}
+
+impl Ssd1306 {
+    pub fn new(dev: I2cdev, address: u8) -> Result<Self, String> {
+        let mut display = Self {
+            dev,
+            address,
+            shown: None,
+        };
+        display.command(&INIT)?;
+        Ok(display)
+    }
+
+    fn command(&mut self, commands: &[u8]) -> Result<(), String> {
+        for command in commands {
+            self.dev
+                .write(self.address, &[0x00, *command])
+                .map_err(|e| format!("Could not write to SSD1306: {}", e))?;
+        }
+        Ok(())
+    }
+
+    fn write_frame(&mut self, frame: &Frame) -> Result<(), String> {
+        // Write the whole display RAM
+        self.command(&[0x21, 0, WIDTH as u8 - 1, 0x22, 0, PAGES as u8 - 1])?;
+        for chunk in frame.0.chunks(16) {
+            let mut data = vec![0x40];
+            data.extend_from_slice(chunk);
+            self.dev
+                .write(self.address, &data)
+                .map_err(|e| format!("Could not write to SSD1306: {}", e))?;
+        }
+        Ok(())
+    }
+}
+
+impl Display for Ssd1306 {
+    fn show(&mut self, screen: &Screen, tick: usize) -> Result<(), String> {
+        let frame = Frame::render(screen, tick);
+        if self.shown.as_ref() != Some(&frame) {
+            self.write_frame(&frame)?;
+            self.shown = Some(frame);
+        }
+        Ok(())
+    }
+
+    fn set_blanked(&mut self, blanked: bool) -> Result<(), String> {
+        self.command(&[if blanked { 0xae } else { 0xaf }]) // Display off or on
+    }
+
+    fn set_brightness(&mut self, brightness: u8) -> Result<(), String> {
+        let contrast = (brightness.min(100) as u16 * 255 / 100) as u8;
+        self.command(&[0x81, contrast])
+    }
+}
+
+/// Contents of the display RAM: One byte per column and page of 8 rows.
+#[derive(PartialEq, Eq)]
+pub struct Frame(pub [u8; WIDTH * PAGES]);
+
+impl Frame {
+    /// Render the clock at the top right, the station name, the scrolling
+    /// title and the volume bar.
+    ///
+    /// While nothing is playing, a large clock with the date and the next
+    /// alarm is shown instead.
+    pub fn render(screen: &Screen, tick: usize) -> Self {
+        if screen.station.is_none() {
+            return Self::render_clock(screen, tick);
+        }
+        let mut frame = Frame([0; WIDTH * PAGES]);
+        frame.text(0, WIDTH - screen.clock.len() * 6, &screen.clock);
+        if let Some(station) = &screen.station {
+            let station = display::to_ascii(station);
+            frame.text(2, 0, &station.chars().take(COLUMNS).collect::<String>());
+        }
+        if let Some(title) = &screen.title {
+            frame.text(4, 0, &display::scroll(&display::to_ascii(title), COLUMNS, tick));
+        }
+        if screen.muted {
+            frame.text(6, 0, &display::to_ascii(tr(Message::Mute)));
+        } else {
+            frame.volume_bar(6, screen.volume);
+        }
+        frame
+    }
+
+    /// Render the large clock, the date, the next alarm and the weather. The
+    /// block wanders across the display to prevent burn-in.
+    fn render_clock(screen: &Screen, tick: usize) -> Self {
+        let mut frame = Frame([0; WIDTH * PAGES]);
+        // The clock takes 3 pages, the date, the alarm and the weather one
+        // each
+        let shift = tick / CLOCK_SHIFT_TICKS;
+        let x = shift * 7 % (WIDTH - CLOCK_WIDTH + 1);
+        let page = shift % (PAGES - 5);
+        frame.large_text(page, x + (CLOCK_WIDTH - screen.clock.len() * 6 * CLOCK_SCALE) / 2, &screen.clock);
+        let centered = |text: &str| x + CLOCK_WIDTH.saturating_sub(text.len() * 6) / 2;
+        frame.text(page + 3, centered(&screen.date), &screen.date);
+        if let Some(alarm) = &screen.next_alarm {
+            let alarm = display::to_ascii(&format!("{} {}", tr(Message::Alarm), alarm));
+            frame.text(page + 4, centered(&alarm), &alarm);
+        }
+        if let Some(weather) = &screen.weather {
+            let weather: String = display::to_ascii(weather).chars().take(COLUMNS).collect();
+            frame.text(page + 5, centered(&weather).min(WIDTH - weather.len() * 6), &weather);
+        }
+        frame
+    }
+
+    /// Draw text scaled by `CLOCK_SCALE`, spanning three pages starting at
+    /// `page`.
+    fn large_text(&mut self, page: usize, x: usize, text: &str) {
+        for (i, c) in text.chars().enumerate() {
+            for (j, column) in font::glyph(c).iter().enumerate() {
+                // Every row becomes `CLOCK_SCALE` rows
+                let mut bits: u32 = 0;
+                for row in 0..8 {
+                    if column & (1 << row) != 0 {
+                        bits |= ((1 << CLOCK_SCALE) - 1) << (row * CLOCK_SCALE);
+                    }
+                }
+                for dx in 0..CLOCK_SCALE {
+                    let column_x = x + (i * 6 + j) * CLOCK_SCALE + dx;
+                    if column_x >= WIDTH {
+                        continue;
+                    }
+                    for p in 0..CLOCK_SCALE {
+                        if let Some(byte) = self.0.get_mut((page + p) * WIDTH + column_x) {
+                            *byte = (bits >> (p * 8)) as u8;
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// Draw text on a page, starting at column `x`.
+    fn text(&mut self, page: usize, x: usize, text: &str) {
+        for (i, c) in text.chars().enumerate() {
+            for (j, column) in font::glyph(c).iter().enumerate() {
+                if let Some(byte) = self.0.get_mut(page * WIDTH + x + i * 6 + j) {
+                    *byte = *column;
+                }
+            }
+        }
+    }
+
+    /// Draw a volume bar spanning the full width of a page.
+    fn volume_bar(&mut self, page: usize, volume: u8) {
+        let filled = volume.min(100) as usize * (WIDTH - 2) / 100;
+        for x in 0..WIDTH {
+            self.0[page * WIDTH + x] = if x == 0 || x == WIDTH - 1 || x <= filled {
+                0x7e
+            } else {
+                0x42
+            };
+        }
+    }
+}
@@ -0,0 +1,119 @@
+use std::time::Duration;
+
+use crate::{connectivity::Connectivity, debounce::SwitchWear};
+
+/// A report about the state of the daemon, written to the status file.
+#[derive(Debug, Clone, Default)]
+pub struct Status {
+    /// Time since the daemon was started.
+    pub uptime: Duration,
+    /// Time since every thread last reported being alive.
+    pub threads: Vec<(&'static str, Duration)>,
+    /// Time since the ADC was last read successfully.
+    pub adc_last_read: Option<Duration>,
+    /// Number of failed ADC reads.
+    pub adc_errors: u32,
+    /// Number of times an ADC was re-initialized after failed reads.
+    pub adc_recoveries: u32,
+    /// State reported by volumio, e.g. "play" or "stop".
+    pub player: Option<String>,
+    /// Time since the current playlist was started.
+    pub playing_since: Option<Duration>,
+    /// Bitrate of the current stream reported by volumio, e.g. "320 Kbps".
+    pub bitrate: Option<String>,
+    /// Number of streams that started playing.
+    pub streams_started: u32,
+    /// Number of streams that were skipped because they didn't start in
+    /// time.
+    pub streams_skipped: u32,
+    /// Time the last stream took from being started until it played.
+    pub stream_latency: Option<Duration>,
+    /// Link quality of the Wi-Fi connection in percent.
+    pub wifi_quality: Option<u8>,
+    /// The state of the network connection, once checked.
+    pub network: Option<Connectivity>,
+    /// Whether no stream of the last playlist started although the network
+    /// is online.
+    pub station_down: bool,
+    /// Why the last stream that didn't start can't be played, if known.
+    pub stream_problem: Option<String>,
+    /// Duty cycle of the fan in percent, if there is one.
+    pub fan_duty: Option<u8>,
+    /// Press and bounce counts of every button, once one was pressed.
+    pub switches: Vec<(String, SwitchWear)>,
+    /// Hash of the configuration file, to tell whether it changed.
+    pub config_hash: Option<u64>,
+}
+
+impl Status {
+    /// Render the report as `key: value` lines.
+    pub fn render(&self) -> String {
+        let age = |age: Option<Duration>| match age {
+            Some(age) => format!("{}s", age.as_secs()),
+            None => "never".into(),
+        };
+        let mut lines = vec![format!("uptime: {}s", self.uptime.as_secs())];
+        for (thread, since) in &self.threads {
+            lines.push(format!("thread {}: alive {}s ago", thread, since.as_secs()));
+        }
+        lines.push(format!("adc last read: {}", age(self.adc_last_read)));
+        lines.push(format!("adc errors: {}", self.adc_errors));
+        lines.push(format!("adc recoveries: {}", self.adc_recoveries));
+        lines.push(format!("player: {}", self.player.as_deref().unwrap_or("unknown")));
+        lines.push(format!("playing since: {}", age(self.playing_since)));
+        lines.push(format!("bitrate: {}", self.bitrate.as_deref().unwrap_or("unknown")));
+        lines.push(format!("streams started: {}", self.streams_started));
+        lines.push(format!("streams skipped: {}", self.streams_skipped));
+        lines.push(match self.stream_latency {
+            Some(latency) => format!("stream latency: {}ms", latency.as_millis()),
+            None => "stream latency: unknown".into(),
+        });
+        lines.push(match self.wifi_quality {
+            Some(quality) => format!("wifi: {}%", quality),
+            None => "wifi: none".into(),
+        });
+        lines.push(match self.network {
+            Some(network) => format!("network: {}", network),
+            None => "network: unknown".into(),
+        });
+        lines.push(format!("station: {}", if self.station_down { "down" } else { "ok" }));
+        if let Some(problem) = &self.stream_problem {
+            lines.push(format!("stream problem: {}", problem));
+        }
+        if let Some(duty) = self.fan_duty {
+            lines.push(format!("fan: {}%", duty));
+        }
+        for (name, wear) in &self.switches {
+            lines.push(format!(
+                "switch {}: {} presses, {} bounces, {:.2} bounces per edge{}",
+                name,
+                wear.presses,
+                wear.bounces,
+                wear.bounce_rate,
+                if wear.worn { ", needs cleaning" } else { "" }
+            ));
+        }
+        lines.push(match self.config_hash {
+            Some(hash) => format!("config hash: {:016x}", hash),
+            None => "config hash: none".into(),
+        });
+        lines.join("\n") + "\n"
+    }
+}
+
+/// Hash a file with 64 bit FNV-1a, which is stable across Rust versions
+/// unlike the hasher of the standard library.
+pub fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(0x0000_0100_0000_01b3)
+    })
+}
+
+/// Return whether the kernel routing table (`/proc/net/route`) contains a
+/// default route.
+pub fn has_default_route(route_table: &str) -> bool {
+    route_table
+        .lines()
+        .skip(1)
+        .any(|line| line.split_whitespace().nth(1) == Some("00000000"))
+}
@@ -1,35 +1,2536 @@
 use super::*;
+use crate::{
+    config::{Duck, GpioBackend, MagicEyeSource, NotifyTarget, RetryPolicy},
+    hardware::{AdcBank, FakeAdc, FakePin},
+    hd44780::LcdLine,
+    i18n::Language,
+    leds::Pattern,
+};
+use proptest::prelude::*;
 
 #[test]
 fn test_measurement_to_angle() {
     // Min
-    assert_eq!(measurement_to_angle(0), 0);
+    assert_eq!(measurement_to_angle(&LOOKUP_TABLE_VOL, 0), 0);
 
     // Max
-    assert_eq!(measurement_to_angle(27000), 280);
-    assert_eq!(measurement_to_angle(64000), 280);
+    assert_eq!(measurement_to_angle(&LOOKUP_TABLE_VOL, 27000), 280);
+    assert_eq!(measurement_to_angle(&LOOKUP_TABLE_VOL, 64000), 280);
 
     // Exact
-    assert_eq!(measurement_to_angle(26226), 250);
-    assert_eq!(measurement_to_angle(26227), 280);
-    assert_eq!(measurement_to_angle(19000), 160);
-    assert_eq!(measurement_to_angle(19250), 180);
+    assert_eq!(measurement_to_angle(&LOOKUP_TABLE_VOL, 26226), 250);
+    assert_eq!(measurement_to_angle(&LOOKUP_TABLE_VOL, 26227), 280);
+    assert_eq!(measurement_to_angle(&LOOKUP_TABLE_VOL, 19000), 160);
+    assert_eq!(measurement_to_angle(&LOOKUP_TABLE_VOL, 19250), 180);
 
     // Interpolated
-    assert_eq!(measurement_to_angle(19126), 175);
+    assert_eq!(measurement_to_angle(&LOOKUP_TABLE_VOL, 19126), 175);
 }
 
 #[test]
 fn test_measurement_to_angle_no_crash() {
     for i in 0..u16::MAX {
-        measurement_to_angle(i);
+        measurement_to_angle(&LOOKUP_TABLE_VOL, i);
     }
 }
 
 #[test]
 fn test_map_potentiometer_value() {
-    assert_eq!(map_potentiometer_value(0), 100);
-    assert_eq!(map_potentiometer_value(26227), 0);
-    assert_eq!(map_potentiometer_value(30000), 0);
-    assert_eq!(map_potentiometer_value(18700), 50);
+    assert_eq!(map_potentiometer_value(&LOOKUP_TABLE_VOL, 0), 100);
+    assert_eq!(map_potentiometer_value(&LOOKUP_TABLE_VOL, 26227), 0);
+    assert_eq!(map_potentiometer_value(&LOOKUP_TABLE_VOL, 30000), 0);
+    assert_eq!(map_potentiometer_value(&LOOKUP_TABLE_VOL, 18700), 50);
+}
+
+#[test]
+fn test_power_switch() {
+    let mut switch = PowerSwitch::new(280, 20, 5);
+
+    // Turning the knob down to the threshold switches off
+    assert_eq!(switch.update(100), None);
+    assert_eq!(switch.update(259), None);
+    assert_eq!(switch.update(260), Some(true));
+    assert_eq!(switch.update(280), None);
+
+    // Hysteresis
+    assert_eq!(switch.update(258), None);
+    assert_eq!(switch.update(255), None);
+    assert_eq!(switch.update(254), Some(false));
+    assert_eq!(switch.update(254), None);
+}
+
+#[test]
+fn test_volume_ramp() {
+    // Nothing applied yet
+    assert_eq!(volume_ramp(None, 40, 5), vec![40]);
+
+    // No change
+    assert_eq!(volume_ramp(Some(40), 40, 5), Vec::<u8>::new());
+
+    // Small changes in single percent steps
+    assert_eq!(volume_ramp(Some(40), 42, 5), vec![41, 42]);
+    assert_eq!(volume_ramp(Some(42), 40, 5), vec![41, 40]);
+
+    // Large changes are limited to the maximum number of steps
+    assert_eq!(volume_ramp(Some(0), 100, 5), vec![20, 40, 60, 80, 100]);
+    assert_eq!(volume_ramp(Some(100), 0, 4), vec![75, 50, 25, 0]);
+    assert_eq!(volume_ramp(Some(0), 100, 0), vec![100]);
+}
+
+#[test]
+fn test_apply_dead_bands() {
+    // Disabled
+    assert_eq!(apply_dead_bands(0, 0, 0), 0);
+    assert_eq!(apply_dead_bands(1, 0, 0), 1);
+    assert_eq!(apply_dead_bands(99, 0, 0), 99);
+    assert_eq!(apply_dead_bands(100, 0, 0), 100);
+
+    // Enabled
+    assert_eq!(apply_dead_bands(2, 2, 3), 0);
+    assert_eq!(apply_dead_bands(3, 2, 3), 3);
+    assert_eq!(apply_dead_bands(96, 2, 3), 96);
+    assert_eq!(apply_dead_bands(97, 2, 3), 100);
+}
+
+#[test]
+fn test_config_analog_controls() {
+    // Defaults
+    let controls = Config::default().analog_controls(false);
+    assert_eq!(controls.len(), 2);
+    assert_eq!(controls[0].channel, Channel::A0);
+    assert_eq!(controls[0].role, Role::Volume);
+    assert_eq!(controls[1].channel, Channel::A1);
+    assert_eq!(Config::default().analog_controls(true)[0].channel, Channel::A0A1);
+
+    // Configured
+    let config = Config::parse(
+        r#"
+        [[analog]]
+        channel = "A2-A3"
+        role = "volume"
+        lookup_table = [[0, 100], [270, 26000]]
+
+        [[analog]]
+        channel = "A1"
+        role = "monitor"
+        "#,
+    )
+    .unwrap();
+    let controls = config.analog_controls(false);
+    assert_eq!(controls.len(), 2);
+    assert_eq!(controls[0].channel, Channel::A2A3);
+    assert_eq!(controls[0].lookup_table, vec![(0, 100), (270, 26000)]);
+    assert_eq!(controls[1].role, Role::Monitor);
+    assert_eq!(controls[1].lookup_table, LOOKUP_TABLE_VOL.to_vec());
+}
+
+#[test]
+fn test_validate_lookup_table() {
+    assert!(config::validate_lookup_table(&LOOKUP_TABLE_VOL).is_ok());
+    assert!(config::validate_lookup_table(&[(0, 10)]).is_err());
+    assert!(config::validate_lookup_table(&[(0, 10), (0, 20)]).is_err());
+    assert!(config::validate_lookup_table(&[(0, 10), (10, 10)]).is_err());
+    assert!(config::validate_lookup_table(&[(10, 10), (0, 20)]).is_err());
+}
+
+#[test]
+fn test_tuner() {
+    // Three stations: 0-33, 33-66, 66-100 with a gap of 10 and hysteresis of 2
+    let mut tuner = Tuner::new(10, 2);
+
+    // No stations
+    assert_eq!(tuner.update(50, 0), None);
+
+    // Initial position
+    assert_eq!(tuner.update(10, 3), Some(DialPosition::Station(0)));
+    assert_eq!(tuner.update(28, 3), None);
+
+    // Leaving a station only after the hysteresis
+    assert_eq!(tuner.update(30, 3), None);
+    assert_eq!(tuner.update(31, 3), Some(DialPosition::Between));
+
+    // Entering a station only after the hysteresis
+    assert_eq!(tuner.update(38, 3), None);
+    assert_eq!(tuner.update(40, 3), Some(DialPosition::Station(1)));
+    assert_eq!(tuner.update(100, 3), Some(DialPosition::Station(2)));
+
+    // Reset
+    tuner.reset();
+    assert_eq!(tuner.update(100, 3), Some(DialPosition::Station(2)));
+
+    // Without gap, the hysteresis prevents flapping at the boundary
+    let mut tuner = Tuner::new(0, 2);
+    assert_eq!(tuner.update(50, 2), Some(DialPosition::Station(0)));
+    assert_eq!(tuner.update(51, 2), None);
+    assert_eq!(tuner.update(52, 2), None);
+    assert_eq!(tuner.update(53, 2), Some(DialPosition::Station(1)));
+    assert_eq!(tuner.update(49, 2), None);
+    assert_eq!(tuner.update(47, 2), Some(DialPosition::Station(0)));
+}
+
+#[test]
+fn test_seeker() {
+    // Stations at 20, 50 and 80 with a hysteresis of 2
+    let mut seeker = Seeker::new(Duration::from_millis(800), 2);
+    let start = Instant::now();
+    let at = |ms| start + Duration::from_millis(ms);
+    let positions = [20, 50, 80];
+
+    // No stations
+    assert_eq!(seeker.update(50, &[], at(0)), None);
+
+    // Locking onto the nearest station right away at startup
+    assert_eq!(seeker.update(30, &positions, at(0)), Some(DialPosition::Station(0)));
+    assert_eq!(seeker.update(32, &positions, at(100)), None);
+
+    // Static while the dial is turned, the nearest station once it rests
+    assert_eq!(seeker.update(40, &positions, at(200)), Some(DialPosition::Between));
+    assert_eq!(seeker.update(60, &positions, at(400)), None);
+    assert_eq!(seeker.update(61, &positions, at(1100)), None);
+    assert_eq!(seeker.update(61, &positions, at(1200)), Some(DialPosition::Station(1)));
+    assert_eq!(seeker.update(61, &positions, at(2000)), None);
+
+    // Turning the dial slowly is noticed as well
+    assert_eq!(seeker.update(63, &positions, at(2100)), None);
+    assert_eq!(seeker.update(65, &positions, at(2200)), Some(DialPosition::Between));
+    assert_eq!(seeker.update(75, &positions, at(2400)), None);
+    assert_eq!(seeker.update(75, &positions, at(3200)), Some(DialPosition::Station(2)));
+
+    // Reset
+    seeker.reset();
+    assert_eq!(seeker.update(0, &positions, at(3300)), Some(DialPosition::Station(0)));
+
+    assert_eq!(tuning::seek_accuracy(50, &[]), 0);
+    assert_eq!(tuning::seek_accuracy(50, &positions), 100);
+    assert_eq!(tuning::seek_accuracy(46, &positions), 60);
+    assert_eq!(tuning::seek_accuracy(35, &positions), 0);
+}
+
+#[test]
+fn test_config_tuning() {
+    let config = Config::parse(
+        r#"
+        [[analog]]
+        channel = "A2"
+        role = "tuning"
+
+        [tuning]
+        static_playlist = "static"
+        bands.ukw = ["srf1", "srf2"]
+        "#,
+    )
+    .unwrap();
+    assert_eq!(config.tuning.stations("ukw"), &["srf1".to_string(), "srf2".to_string()]);
+    assert!(config.tuning.stations("lang").is_empty());
+    assert_eq!(config.tuning.hysteresis, 2);
+
+    // Tuning without tuning control
+    assert!(Config::parse("tuning.bands.ukw = [\"srf1\"]").is_err());
+
+    // Unknown band
+    let result = Config::parse(
+        r#"
+        [[analog]]
+        channel = "A2"
+        role = "tuning"
+
+        [tuning.bands]
+        fm = ["srf1"]
+        "#,
+    );
+    assert!(result.is_err());
+
+    // Seek mode
+    let seek = |positions: &str| {
+        Config::parse(&format!(
+            r#"
+            [[analog]]
+            channel = "A2"
+            role = "tuning"
+
+            [tuning]
+            mode = "seek"
+            bands.ukw = ["srf1", "srf2"]
+
+            [tuning.positions]
+            {}
+            "#,
+            positions
+        ))
+    };
+    let config = seek("ukw = { srf2 = 70, srf1 = 25 }").unwrap();
+    assert_eq!(config.tuning.mode, TuningMode::Seek);
+    assert_eq!(config.tuning.seek_static_ms, 800);
+    assert_eq!(config.tuning.positions("ukw"), vec![25, 70]);
+    assert!(config.tuning.positions("lang").is_empty());
+
+    // Missing position, unknown station or band, position out of range
+    assert!(seek("ukw = { srf1 = 25 }").is_err());
+    assert!(seek("ukw = { srf1 = 25, srf2 = 70, srf3 = 90 }").is_err());
+    assert!(seek("ukw = { srf1 = 25, srf2 = 70 }\nfm = { srf1 = 25 }").is_err());
+    assert!(seek("ukw = { srf1 = 25, srf2 = 101 }").is_err());
+
+    // Positions without the seek mode
+    let result = Config::parse(
+        r#"
+        [[analog]]
+        channel = "A2"
+        role = "tuning"
+
+        [tuning]
+        bands.ukw = ["srf1"]
+        positions.ukw = { srf1 = 25 }
+        "#,
+    );
+    assert!(result.is_err());
+}
+
+#[test]
+fn test_config_bands() {
+    let parse = |extra: &str| {
+        Config::parse(&format!(
+            r#"
+            [[buttons]]
+            name = "taste-ukw"
+            pin = 22
+            max_volume = 60
+            action = {{ band = "ukw" }}
+
+            [[buttons]]
+            name = "tonabnehmer"
+            pin = 27
+            action = {{ playlist = "jazz" }}
+
+            [[bands]]
+            name = "ukw"
+            stations = ["srf1", "srf2"]
+            {}
+            "#,
+            extra
+        ))
+    };
+
+    // Without a tuning control, the first station is played
+    let config = parse("").unwrap();
+    assert_eq!(config.button("taste-ukw").unwrap().band(), Some("ukw"));
+    assert_eq!(config.button("tonabnehmer").unwrap().band(), Some("tonabnehmer"));
+    assert_eq!(config.band_playlist("ukw", None), Some("srf1"));
+    assert_eq!(config.band_playlist("tonabnehmer", None), Some("jazz"));
+    assert_eq!(config.band_max_volume("ukw"), Some(60));
+    assert_eq!(config.band_max_volume("tonabnehmer"), None);
+    assert!(config.tuning().stations("ukw").is_empty());
+
+    // With a tuning control, it selects the station
+    let config = parse("[[analog]]\nchannel = \"A2\"\nrole = \"tuning\"").unwrap();
+    assert_eq!(config.band_playlist("ukw", None), None);
+    assert_eq!(config.band_playlist("tonabnehmer", None), Some("jazz"));
+    assert_eq!(config.tuning().stations("ukw"), &["srf1".to_string(), "srf2".to_string()]);
+
+    // Bands can be selected with keys
+    assert!(parse("[[evdev]]\ndevice = \"/dev/input/event0\"\nkeys = { 2 = \"ukw\" }").is_ok());
+
+    // Unknown band, duplicate band, band without stations, band with the
+    // name of a playlist button, stations in both places
+    assert!(Config::parse("[[buttons]]\nname = \"a\"\npin = 22\naction = { band = \"ukw\" }").is_err());
+    assert!(parse("[[bands]]\nname = \"ukw\"\nstations = [\"srf3\"]").is_err());
+    assert!(parse("[[bands]]\nname = \"kurz\"\nstations = []").is_err());
+    assert!(parse("[[bands]]\nname = \"tonabnehmer\"\nstations = [\"srf3\"]").is_err());
+    assert!(parse("[[analog]]\nchannel = \"A2\"\nrole = \"tuning\"\n[tuning.bands]\nukw = [\"srf3\"]").is_err());
+}
+
+#[test]
+fn test_config_lock() {
+    let parse = |lock: &str| {
+        Config::parse(&format!(
+            "[[buttons]]\nname = \"stop\"\npin = 23\naction = \"stop\"\n\
+             [[buttons]]\nname = \"ukw\"\npin = 22\naction = {{ playlist = \"mellow\" }}\n[lock]\n{}",
+            lock
+        ))
+    };
+    let config = parse("button = \"stop\"").unwrap();
+    assert_eq!(config.lock.max_volume, 30);
+    assert_eq!(config.lock.hold_s, 5);
+
+    // Unknown button, band button, shutdown button, too loud, no hold time
+    assert!(parse("button = \"pause\"").is_err());
+    assert!(parse("button = \"ukw\"").is_err());
+    assert!(Config::parse("[lock]\nbutton = \"aus\"").is_err());
+    assert!(parse("max_volume = 120").is_err());
+    assert!(parse("button = \"stop\"\nhold_s = 0").is_err());
+}
+
+#[test]
+fn test_band_programs() {
+    let config = Config::parse(
+        r#"
+        [[bands]]
+        name = "radio"
+        stations = ["srf3", "srf1"]
+        programs = [
+            { days = ["mon", "tue", "wed", "thu", "fri"], from = "06:00", to = "09:00", station = "srf4-news" },
+            { from = "22:00", to = "02:00", station = "jazz" },
+        ]
+        "#,
+    )
+    .unwrap();
+    let at = |weekday, hours: u16, minutes: u16| config.band_playlist("radio", Some((weekday, hours * 60 + minutes)));
+    assert_eq!(at(Weekday::Mon, 6, 0), Some("srf4-news"));
+    assert_eq!(at(Weekday::Fri, 8, 59), Some("srf4-news"));
+    assert_eq!(at(Weekday::Fri, 9, 0), Some("srf3"));
+    assert_eq!(at(Weekday::Sat, 7, 0), Some("srf3"));
+
+    // Programs that end past midnight
+    assert_eq!(at(Weekday::Sun, 23, 0), Some("jazz"));
+    assert_eq!(at(Weekday::Mon, 1, 59), Some("jazz"));
+    assert_eq!(at(Weekday::Mon, 2, 0), Some("srf3"));
+    assert_eq!(Weekday::Mon.previous(), Weekday::Sun);
+
+    // Without the time, the first station is played
+    assert_eq!(config.band_playlist("radio", None), Some("srf3"));
+
+    let parse = |program: &str| {
+        Config::parse(&format!("[[bands]]\nname = \"radio\"\nstations = [\"srf3\"]\nprograms = [{}]", program))
+    };
+    assert!(parse("{ from = \"6:00\", to = \"09:00\", station = \"srf4\" }").is_ok());
+    assert!(parse("{ from = \"06:00\", to = \"24:00\", station = \"srf4\" }").is_err());
+    assert!(parse("{ from = \"06:00\", to = \"06:00\", station = \"srf4\" }").is_err());
+    assert!(parse("{ from = \"06:00\", to = \"09:00\", station = \"\" }").is_err());
+}
+
+#[test]
+fn test_quadrature_decoder() {
+    // Full detent in one direction
+    let mut decoder = QuadratureDecoder::new(4, true, true);
+    assert_eq!(decoder.update(true, false), 0);
+    assert_eq!(decoder.update(false, false), 0);
+    assert_eq!(decoder.update(false, true), 0);
+    assert_eq!(decoder.update(true, true), -1);
+
+    // Full detent in the other direction
+    assert_eq!(decoder.update(false, true), 0);
+    assert_eq!(decoder.update(false, false), 0);
+    assert_eq!(decoder.update(true, false), 0);
+    assert_eq!(decoder.update(true, true), 1);
+
+    // Bouncing back and forth doesn't count
+    assert_eq!(decoder.update(true, false), 0);
+    assert_eq!(decoder.update(true, true), 0);
+    assert_eq!(decoder.update(true, false), 0);
+    assert_eq!(decoder.update(true, true), 0);
+
+    // Invalid transitions are ignored
+    assert_eq!(decoder.update(false, false), 0);
+    assert_eq!(decoder.update(true, true), 0);
+
+    // Encoders with fewer steps per detent
+    let mut decoder = QuadratureDecoder::new(2, true, true);
+    assert_eq!(decoder.update(true, false), 0);
+    assert_eq!(decoder.update(false, false), -1);
+}
+
+#[test]
+fn test_encoder_volume_step() {
+    assert_eq!(encoder::volume_step(Duration::from_millis(10)), 5);
+    assert_eq!(encoder::volume_step(Duration::from_millis(50)), 2);
+    assert_eq!(encoder::volume_step(Duration::from_millis(500)), 1);
+}
+
+#[test]
+fn test_parse_input_event() {
+    let mut buf = [0; 2 * std::mem::size_of::<usize>() + 8];
+    let offset = buf.len() - 8;
+    buf[offset..offset + 2].copy_from_slice(&evdev::EV_KEY.to_ne_bytes());
+    buf[offset + 2..offset + 4].copy_from_slice(&115u16.to_ne_bytes());
+    buf[offset + 4..].copy_from_slice(&evdev::KEY_REPEAT.to_ne_bytes());
+    let event = evdev::InputEvent::parse(&buf);
+    assert_eq!(
+        event,
+        evdev::InputEvent {
+            kind: evdev::EV_KEY,
+            code: 115,
+            value: evdev::KEY_REPEAT,
+        }
+    );
+}
+
+#[test]
+fn test_config_evdev() {
+    let config = Config::parse(
+        r#"
+        [[evdev]]
+        device = "/dev/input/event0"
+        keys = { 2 = "ukw", 115 = "volume_up" }
+        buttons = { 16 = "aus", 17 = "kurz" }
+        "#,
+    )
+    .unwrap();
+    assert_eq!(config.evdev[0].button(17), Some("kurz"));
+    assert_eq!(config.evdev[0].button(2), None);
+    assert_eq!(config.evdev[0].action(2), Some(&KeyAction::Band("ukw".into())));
+    assert_eq!(config.evdev[0].action(115), Some(&KeyAction::VolumeUp));
+    assert_eq!(config.evdev[0].action(3), None);
+
+    // Invalid key code
+    let result = Config::parse(
+        r#"
+        [[evdev]]
+        device = "/dev/input/event0"
+        keys = { KEY_1 = "ukw" }
+        "#,
+    );
+    assert!(result.is_err());
+
+    // Unknown band
+    let result = Config::parse(
+        r#"
+        [[evdev]]
+        device = "/dev/input/event0"
+        keys = { 2 = "fm" }
+        "#,
+    );
+    assert!(result.is_err());
+}
+
+#[test]
+fn test_config_buttons() {
+    // The buttons of the original radio are used by default
+    let config = Config::parse("").unwrap();
+    assert_eq!(config.buttons.len(), 6);
+    assert_eq!(config.button("aus").unwrap().action, ButtonAction::Shutdown);
+    assert_eq!(config.button("ukw").unwrap().playlist(), Some("mellow"));
+
+    let config = Config::parse(
+        r#"
+        [[buttons]]
+        name = "aus"
+        pin = 17
+        inverted = true
+        action = "stop"
+
+        [[buttons]]
+        name = "ukw"
+        pin = 22
+        action = { playlist = "news" }
+        max_volume = 70
+
+        [[buttons]]
+        name = "schlaf"
+        pin = 23
+        action = { sleep_timer = 30 }
+        "#,
+    )
+    .unwrap();
+    assert_eq!(config.buttons.len(), 3);
+    assert_eq!(config.button("aus").unwrap().action, ButtonAction::Stop);
+    assert!(config.button("aus").unwrap().inverted);
+    assert_eq!(config.button("ukw").unwrap().playlist(), Some("news"));
+    assert_eq!(config.button("ukw").unwrap().max_volume, Some(70));
+    assert_eq!(config.button("schlaf").unwrap().action, ButtonAction::SleepTimer(30));
+    assert!(config.button("kurz").is_none());
+
+    // Duplicate pin
+    let result = Config::parse(
+        r#"
+        [[buttons]]
+        name = "ukw"
+        pin = 22
+        action = { playlist = "news" }
+
+        [[buttons]]
+        name = "kurz"
+        pin = 22
+        action = "stop"
+        "#,
+    );
+    assert!(result.is_err());
+}
+
+#[test]
+fn test_button_latch() {
+    let mut latch = ButtonLatch::default();
+    assert_eq!(latch.press("ukw", true), (vec!["ukw".to_string()], vec![]));
+    assert_eq!(latch.press("kurz", true), (vec!["kurz".to_string()], vec!["ukw".to_string()]));
+    assert_eq!(latch.press("kurz", true), (vec![], vec!["kurz".to_string()]));
+    assert_eq!(latch.press("lang", true), (vec!["lang".to_string()], vec![]));
+
+    // Buttons that don't latch are only pressed
+    assert_eq!(latch.press("aus", false), (vec!["aus".to_string()], vec![]));
+    assert_eq!(latch.press("aus", false), (vec!["aus".to_string()], vec![]));
+    assert_eq!(latch.press("lang", true), (vec![], vec!["lang".to_string()]));
+}
+
+#[test]
+fn test_stuck_buttons() {
+    let config = Config::default();
+    let stuck_after = |name: &str| config.button(name).and_then(|button| button.stuck_after());
+    let names = |names: &[&str]| names.iter().map(|name| name.to_string()).collect::<Vec<_>>();
+    let start = Instant::now();
+    let mut stuck = StuckButtons::default();
+
+    // Band buttons are not watched by default
+    let (mut pressed, mut released) = (names(&["aus", "ukw"]), vec![]);
+    assert!(stuck.update(&mut pressed, &mut released, start, stuck_after).is_empty());
+    assert_eq!(pressed, names(&["aus", "ukw"]));
+    let (mut pressed, mut released) = (vec![], vec![]);
+    let became_stuck = stuck.update(&mut pressed, &mut released, start + Duration::from_secs(59), stuck_after);
+    assert!(became_stuck.is_empty());
+    let became_stuck = stuck.update(&mut pressed, &mut released, start + Duration::from_secs(60), stuck_after);
+    assert_eq!(became_stuck, names(&["aus"]));
+    assert_eq!(released, names(&["aus"]));
+
+    // Releasing a stuck button is ignored, afterwards it works again
+    let (mut pressed, mut released) = (vec![], names(&["aus"]));
+    assert!(stuck.update(&mut pressed, &mut released, start + Duration::from_secs(70), stuck_after).is_empty());
+    assert!(released.is_empty());
+    let (mut pressed, mut released) = (names(&["aus"]), vec![]);
+    stuck.update(&mut pressed, &mut released, start + Duration::from_secs(80), stuck_after);
+    assert_eq!(pressed, names(&["aus"]));
+
+    // Buttons held at startup are ignored until they are released
+    let mut stuck = StuckButtons::default();
+    stuck.ignore("aus");
+    let (mut pressed, mut released) = (names(&["aus"]), vec![]);
+    stuck.update(&mut pressed, &mut released, start, stuck_after);
+    assert!(pressed.is_empty());
+    let (mut pressed, mut released) = (vec![], names(&["aus"]));
+    stuck.update(&mut pressed, &mut released, start, stuck_after);
+    assert!(released.is_empty());
+
+    let config = Config::parse(
+        r#"
+        [[buttons]]
+        name = "ukw"
+        pin = 22
+        action = { playlist = "mellow" }
+        stuck_after_s = 3600
+
+        [[buttons]]
+        name = "stop"
+        pin = 5
+        action = "stop"
+        stuck_after_s = 0
+        "#,
+    )
+    .unwrap();
+    assert_eq!(config.button("ukw").unwrap().stuck_after(), Some(Duration::from_secs(3600)));
+    assert_eq!(config.button("stop").unwrap().stuck_after(), None);
+}
+
+#[test]
+fn test_debouncer() {
+    let mut debouncer = Debouncer::new(3);
+    assert_eq!(debouncer.update(true), None);
+    assert_eq!(debouncer.update(false), None);
+    assert_eq!(debouncer.update(true), None);
+    assert_eq!(debouncer.update(true), None);
+    assert_eq!(debouncer.update(true), Some(Edge::Rising));
+    assert_eq!(debouncer.update(true), None);
+    assert_eq!(debouncer.update(false), None);
+    assert_eq!(debouncer.update(false), None);
+    assert_eq!(debouncer.update(false), Some(Edge::Falling));
+    assert_eq!(debouncer.update(false), None);
+
+    // A single sample changes the state immediately
+    let mut debouncer = Debouncer::new(1);
+    assert_eq!(debouncer.update(true), Some(Edge::Rising));
+    assert_eq!(debouncer.update(false), Some(Edge::Falling));
+
+    // The full history is used
+    let mut debouncer = Debouncer::new(32);
+    for _ in 0..31 {
+        assert_eq!(debouncer.update(true), None);
+    }
+    assert_eq!(debouncer.update(true), Some(Edge::Rising));
+}
+
+#[test]
+fn test_switch_wear() {
+    // Clean presses and releases
+    let mut wear = SwitchWear::default();
+    for _ in 0..10 {
+        assert!(!wear.update(true, None, false));
+        assert!(!wear.update(true, Some(Edge::Rising), true));
+        assert!(!wear.update(false, None, false));
+        assert!(!wear.update(false, Some(Edge::Falling), false));
+    }
+    assert_eq!((wear.presses, wear.edges, wear.bounces, wear.worn), (10, 20, 0, false));
+
+    // Every edge bounces twice
+    let mut became_worn = 0;
+    for _ in 0..20 {
+        for (active, edge, pressed) in [(true, None, false), (false, None, false), (true, None, false)] {
+            assert!(!wear.update(active, edge, pressed));
+        }
+        became_worn += wear.update(true, Some(Edge::Rising), true) as u32;
+        for (active, edge, pressed) in [(false, None, false), (true, None, false), (false, None, false)] {
+            assert!(!wear.update(active, edge, pressed));
+        }
+        became_worn += wear.update(false, Some(Edge::Falling), false) as u32;
+    }
+    assert_eq!((wear.presses, wear.edges, wear.bounces), (30, 60, 80));
+    assert!(wear.worn);
+    assert!(wear.bounce_rate > 1.0 && wear.bounce_rate < 2.0);
+    assert_eq!(became_worn, 1);
+
+    // The first edges don't count as wear, however much they bounce
+    let mut wear = SwitchWear::default();
+    for _ in 0..10 {
+        for i in 0..60 {
+            wear.update(i % 2 == 0, None, false);
+        }
+        wear.update(true, Some(Edge::Rising), true);
+    }
+    assert!(wear.bounce_rate > 1.0);
+    assert!(!wear.worn);
+}
+
+#[test]
+fn test_config_debounce() {
+    let config = Config::parse(
+        r#"
+        [debounce]
+        samples = 8
+
+        [[buttons]]
+        name = "ukw"
+        pin = 22
+        action = { playlist = "mellow" }
+        debounce_samples = 32
+        debounce_interval_ms = 5
+
+        [[buttons]]
+        name = "kurz"
+        pin = 5
+        action = { playlist = "world" }
+        "#,
+    )
+    .unwrap();
+    let ukw = config.button("ukw").unwrap().debounce(&config.debounce);
+    assert_eq!((ukw.samples, ukw.interval_ms), (32, 5));
+    let kurz = config.button("kurz").unwrap().debounce(&config.debounce);
+    assert_eq!((kurz.samples, kurz.interval_ms), (8, 10));
+
+    assert!(Config::parse("[debounce]\nsamples = 33").is_err());
+    assert!(Config::parse("[debounce]\ninterval_ms = 0").is_err());
+}
+
+#[test]
+fn test_config_shutdown() {
+    let shutdown = Config::default().shutdown;
+    assert!(!shutdown.halts(Duration::from_secs(9)));
+    assert!(shutdown.halts(Duration::from_secs(10)));
+
+    let config = Config::parse("[shutdown]\nmin_on_s = 0\nstop_only = true").unwrap();
+    assert!(!config.shutdown.halts(Duration::from_secs(3600)));
+}
+
+#[test]
+fn test_power_latch() {
+    let config = Config::parse("[power_latch]\nhold_pin = 26\nrequest_pin = 19").unwrap();
+    let latch = config.power_latch.unwrap();
+    assert_eq!((latch.hold_pin, latch.request_pin), (26, Some(19)));
+    assert!(Config::parse("[power_latch]\nhold_pin = 26").is_ok());
+
+    // Pins must not be used twice
+    let latch = "[power_latch]\nhold_pin = 26\nrequest_pin = 19\n\n";
+    assert!(Config::parse("[power_latch]\nhold_pin = 17").is_err());
+    assert!(Config::parse("[power_latch]\nhold_pin = 26\nrequest_pin = 17").is_err());
+    assert!(Config::parse("[power_latch]\nhold_pin = 26\nrequest_pin = 26").is_err());
+    assert!(Config::parse(&format!("{}[[outputs]]\npin = 19", latch)).is_err());
+    assert!(Config::parse(&format!("{}[[outputs]]\npin = 26", latch)).is_err());
+    assert!(Config::parse(&format!("{}[[leds]]\npin = 19\npatterns = {{ playing = \"on\" }}", latch)).is_err());
+    assert!(Config::parse(&format!("{}[fan]\npin = 19", latch)).is_err());
+    assert!(Config::parse(&format!("{}[fan]\npin = 26", latch)).is_err());
+    assert!(Config::parse(&format!("{}[fan]\npin = 18", latch)).is_ok());
+    assert!(Config::parse(&format!("{}[headphones]\ndetect_pin = 19", latch)).is_err());
+    let encoder = "[encoder]\npin_a = 23\npin_b = 24\nrole = \"volume\"";
+    assert!(Config::parse(&format!("analog = []\n{}{}\nswitch_pin = 20", latch, encoder)).is_ok());
+    assert!(Config::parse(&format!("analog = []\n{}{}\nswitch_pin = 19", latch, encoder)).is_err());
+}
+
+#[test]
+fn test_config_gpio() {
+    let gpio = Config::default().gpio;
+    assert_eq!(gpio.backend, GpioBackend::Rppal);
+    assert_eq!(gpio.chip, "/dev/gpiochip0");
+
+    let config = Config::parse("[gpio]\nbackend = \"cdev\"\nchip = \"/dev/gpiochip1\"").unwrap();
+    assert_eq!(config.gpio.backend, GpioBackend::Cdev);
+    assert_eq!(config.gpio.chip, "/dev/gpiochip1");
+    assert!(Config::parse("[gpio]\nbackend = \"sysfs\"").is_err());
+}
+
+#[test]
+fn test_band_selector() {
+    let start = Instant::now();
+    let ms = |ms| start + Duration::from_millis(ms);
+    let mut bands = BandSelector::new(Duration::from_millis(50), Duration::ZERO, Duration::ZERO);
+
+    // A press is applied once the buttons have settled
+    bands.press("ukw", ms(0));
+    assert_eq!(bands.update(ms(10)), None);
+    assert_eq!(bands.update(ms(50)), Some(BandChange::Select("ukw".into())));
+    assert_eq!(bands.update(ms(60)), None);
+
+    // Pushing in the next key releases the previous one: The new band wins
+    // and playback isn't stopped
+    bands.press("kurz", ms(100));
+    bands.release("ukw", ms(110));
+    assert_eq!(bands.update(ms(150)), None);
+    assert_eq!(bands.update(ms(160)), Some(BandChange::Select("kurz".into())));
+
+    // Two keys held down: The last pressed one wins
+    bands.press("lang", ms(200));
+    bands.press("mittel", ms(210));
+    assert_eq!(bands.update(ms(260)), Some(BandChange::Select("mittel".into())));
+
+    // Releasing the selected key falls back to the other held key
+    bands.release("mittel", ms(300));
+    assert_eq!(bands.update(ms(350)), Some(BandChange::Select("lang".into())));
+
+    // A short moment without any key pressed is ignored
+    bands.release("kurz", ms(400));
+    bands.release("lang", ms(410));
+    bands.press("ukw", ms(420));
+    assert_eq!(bands.update(ms(470)), Some(BandChange::Select("ukw".into())));
+
+    // Releasing all keys deselects the band
+    bands.release("ukw", ms(500));
+    assert_eq!(bands.update(ms(550)), Some(BandChange::Release("ukw".into())));
+    assert_eq!(bands.update(ms(600)), None);
+}
+
+#[test]
+fn test_band_selector_stop_grace() {
+    let start = Instant::now();
+    let ms = |ms| start + Duration::from_millis(ms);
+    let mut bands = BandSelector::new(Duration::from_millis(50), Duration::from_millis(250), Duration::ZERO);
+    bands.press("ukw", ms(0));
+    assert_eq!(bands.update(ms(50)), Some(BandChange::Select("ukw".into())));
+
+    // Pressing another band within the grace period doesn't stop playback
+    bands.release("ukw", ms(100));
+    assert_eq!(bands.update(ms(200)), None);
+    bands.press("kurz", ms(300));
+    assert_eq!(bands.update(ms(349)), None);
+    assert_eq!(bands.update(ms(350)), Some(BandChange::Select("kurz".into())));
+
+    // Otherwise the band is deselected after the grace period
+    bands.release("kurz", ms(400));
+    assert_eq!(bands.update(ms(649)), None);
+    assert_eq!(bands.update(ms(650)), Some(BandChange::Release("kurz".into())));
+}
+
+#[test]
+fn test_band_selector_lucky() {
+    let start = Instant::now();
+    let ms = |ms| start + Duration::from_millis(ms);
+    let mut bands = BandSelector::new(Duration::from_millis(50), Duration::ZERO, Duration::from_millis(1000));
+
+    // Sliding from one key to the next doesn't trigger the gesture
+    bands.press("ukw", ms(0));
+    bands.press("kurz", ms(10));
+    bands.release("ukw", ms(20));
+    assert_eq!(bands.update(ms(70)), Some(BandChange::Select("kurz".into())));
+    assert_eq!(bands.update(ms(1100)), None);
+
+    // Holding two keys down together plays a random station, which stays
+    // on when one of them is released
+    bands.press("ukw", ms(2000));
+    assert_eq!(bands.update(ms(2050)), Some(BandChange::Select("ukw".into())));
+    assert_eq!(bands.update(ms(2999)), None);
+    assert_eq!(bands.update(ms(3000)), Some(BandChange::Lucky));
+    assert_eq!(bands.update(ms(4000)), None);
+    bands.release("ukw", ms(4100));
+    assert_eq!(bands.update(ms(4150)), None);
+
+    // Pressing another key selects its band again
+    bands.press("lang", ms(5000));
+    bands.release("kurz", ms(5010));
+    assert_eq!(bands.update(ms(5060)), Some(BandChange::Select("lang".into())));
+
+    // Releasing all keys deselects the band
+    bands.press("ukw", ms(6000));
+    assert_eq!(bands.update(ms(6050)), Some(BandChange::Select("ukw".into())));
+    assert_eq!(bands.update(ms(7000)), Some(BandChange::Lucky));
+    bands.release("ukw", ms(7100));
+    bands.release("lang", ms(7110));
+    assert_eq!(bands.update(ms(7160)), Some(BandChange::Release("ukw".into())));
+}
+
+#[test]
+fn test_band_selector_reset() {
+    let start = Instant::now();
+    let ms = |ms| start + Duration::from_millis(ms);
+    let mut bands = BandSelector::new(Duration::from_millis(50), Duration::ZERO, Duration::from_millis(1000));
+
+    // Resetting cancels a pending gesture
+    bands.press("ukw", ms(0));
+    bands.press("kurz", ms(10));
+    bands.reset(vec!["ukw".into(), "kurz".into()]);
+    assert_eq!(bands.update(ms(1100)), None);
+
+    // The band held down when resetting is selected without a change, keys
+    // pressed and released afterwards are compared with it
+    bands.reset(vec!["lang".into()]);
+    assert_eq!(bands.update(ms(2000)), None);
+    bands.press("lang", ms(2100));
+    assert_eq!(bands.update(ms(2150)), None);
+    bands.release("lang", ms(2200));
+    assert_eq!(bands.update(ms(2250)), Some(BandChange::Release("lang".into())));
+
+    // Resetting with no band held down doesn't stop playback again
+    bands.press("ukw", ms(3000));
+    assert_eq!(bands.update(ms(3050)), Some(BandChange::Select("ukw".into())));
+    bands.reset(vec![]);
+    assert_eq!(bands.update(ms(3100)), None);
+    bands.press("mittel", ms(3200));
+    assert_eq!(bands.update(ms(3250)), Some(BandChange::Select("mittel".into())));
+}
+
+#[test]
+fn test_all_stations() {
+    let config = Config::parse(
+        r#"
+        [[buttons]]
+        name = "tonabnehmer"
+        pin = 27
+        action = { playlist = "jazz" }
+
+        [[bands]]
+        name = "radio"
+        stations = ["srf1", "srf3"]
+        programs = [{ from = "06:00", to = "09:00", station = "srf4-news" }]
+
+        [[bands]]
+        name = "kurz"
+        stations = ["numbers"]
+
+        [dx]
+        band = "kurz"
+        recordings = ["/home/volumio/dx"]
+
+        [[schedule]]
+        time = "07:00"
+        action = "play"
+        playlist = "srf1"
+
+        [[stations]]
+        id = "rnz-pacific"
+        "#,
+    )
+    .unwrap();
+    assert_eq!(config.all_stations(), ["srf1", "srf3", "srf4-news", "jazz", "rnz-pacific"]);
+}
+
+#[test]
+fn test_output_level() {
+    let config = Config::parse(
+        r#"
+        [[outputs]]
+        pin = 26
+
+        [[outputs]]
+        pin = 19
+        when = "always"
+        dim_with_volume = true
+        min_brightness = 20
+        blink_on_error = true
+        "#,
+    )
+    .unwrap();
+    let (lamp, dimmed) = (&config.outputs[0], &config.outputs[1]);
+    let mut state = RadioState {
+        ready: true,
+        playing: false,
+        volume: 50,
+        since_error: None,
+        buffering: false,
+        tuning_accuracy: 0,
+        wifi_quality: None,
+        idle: false,
+        brightness: 100,
+    };
+    assert_eq!(outputs::level(lamp, &state), 0.0);
+    assert_eq!(outputs::level(dimmed, &state), 0.6);
+
+    state.playing = true;
+    assert_eq!(outputs::level(lamp, &state), 1.0);
+    state.volume = 0;
+    assert_eq!(outputs::level(dimmed, &state), 0.2);
+
+    // Blink after an error
+    state.since_error = Some(Duration::from_millis(100));
+    assert_eq!(outputs::level(lamp, &state), 1.0);
+    assert_eq!(outputs::level(dimmed, &state), 1.0);
+    state.since_error = Some(Duration::from_millis(300));
+    assert_eq!(outputs::level(dimmed, &state), 0.0);
+    state.since_error = Some(outputs::ERROR_BLINK_DURATION);
+    assert_eq!(outputs::level(dimmed, &state), 0.2);
+
+    // Everything is off while idle
+    state.idle = true;
+    assert_eq!(outputs::level(lamp, &state), 0.0);
+    assert_eq!(outputs::level(dimmed, &state), 0.0);
+
+    // Pins must not be used twice
+    assert!(Config::parse("[[outputs]]\npin = 17").is_err());
+}
+
+#[test]
+fn test_config_idle() {
+    let idle = Config::parse("[idle]\nafter_minutes = 120").unwrap().idle.unwrap();
+    assert_eq!(idle.after_minutes, 120);
+    assert!(!idle.shutdown);
+    assert!(Config::parse("[idle]\nafter_minutes = 120\nshutdown = true").unwrap().idle.unwrap().shutdown);
+    assert!(Config::parse("[idle]\nafter_minutes = 0").is_err());
+    assert!(Config::parse("[idle]\nshutdown = true").is_err());
+}
+
+#[test]
+fn test_tuning_accuracy() {
+    assert_eq!(tuning::accuracy(50, 0), 0);
+    assert_eq!(tuning::accuracy(50, 1), 100);
+    assert_eq!(tuning::accuracy(0, 1), 0);
+    assert_eq!(tuning::accuracy(25, 2), 100);
+    assert_eq!(tuning::accuracy(50, 2), 0);
+    assert_eq!(tuning::accuracy(75, 2), 100);
+    assert_eq!(tuning::accuracy(100, 2), 0);
+    assert_eq!(tuning::accuracy(85, 2), 60);
+}
+
+#[test]
+fn test_magic_eye_level() {
+    let config = Config::parse(
+        r#"
+        [[analog]]
+        channel = "A1"
+        role = "tuning"
+
+        [magic_eye]
+        mcp4725_address = 0x60
+        source = "tuning"
+        "#,
+    )
+    .unwrap();
+    let eye = config.magic_eye.unwrap();
+    let mut state = RadioState {
+        ready: true,
+        playing: true,
+        volume: 50,
+        since_error: None,
+        buffering: false,
+        tuning_accuracy: 80,
+        wifi_quality: Some(60),
+        idle: false,
+        brightness: 100,
+    };
+    assert_eq!(outputs::magic_eye_level(&eye, &state), 0.8);
+    state.playing = false;
+    assert_eq!(outputs::magic_eye_level(&eye, &state), 0.0);
+
+    // The Wi-Fi signal is shown while not playing as well
+    let eye = MagicEye {
+        source: MagicEyeSource::Wifi,
+        ..eye
+    };
+    assert_eq!(outputs::magic_eye_level(&eye, &state), 0.6);
+    state.wifi_quality = None;
+    assert_eq!(outputs::magic_eye_level(&eye, &state), 0.0);
+
+    // Either a pin or a DAC is needed
+    let result = Config::parse("[magic_eye]\nsource = \"playback\"");
+    assert!(result.is_err());
+    // Tuning requires a tuning dial
+    let result = Config::parse("[magic_eye]\npin = 18\nsource = \"tuning\"");
+    assert!(result.is_err());
+    assert!(Config::parse("[magic_eye]\npin = 18\nsource = \"playback\"").is_ok());
+}
+
+#[test]
+fn test_daemon_state() {
+    let mut state = RadioState {
+        ready: false,
+        playing: false,
+        volume: 30,
+        since_error: None,
+        buffering: false,
+        tuning_accuracy: 0,
+        wifi_quality: None,
+        idle: false,
+        brightness: 100,
+    };
+    assert_eq!(DaemonState::of(&state), DaemonState::Booting);
+    state.ready = true;
+    assert_eq!(DaemonState::of(&state), DaemonState::Ready);
+    state.playing = true;
+    state.buffering = true;
+    assert_eq!(DaemonState::of(&state), DaemonState::Buffering);
+    state.buffering = false;
+    assert_eq!(DaemonState::of(&state), DaemonState::Playing);
+    state.since_error = Some(Duration::from_secs(1));
+    assert_eq!(DaemonState::of(&state), DaemonState::Error);
+}
+
+#[test]
+fn test_led_patterns() {
+    let config = Config::parse(
+        r#"
+        [[leds]]
+        pin = 16
+        patterns = { playing = "on", error = "off" }
+        "#,
+    )
+    .unwrap();
+    let led = &config.leds[0];
+    assert_eq!(led.pattern(DaemonState::Playing), Pattern::On);
+    assert_eq!(led.pattern(DaemonState::Error), Pattern::Off);
+    assert_eq!(led.pattern(DaemonState::Booting), Pattern::SlowBlink);
+
+    let ms = Duration::from_millis;
+    assert!(Pattern::SlowBlink.is_on(ms(0)));
+    assert!(!Pattern::SlowBlink.is_on(ms(600)));
+    assert!(Pattern::SlowBlink.is_on(ms(1100)));
+    assert!(!Pattern::FastBlink.is_on(ms(200)));
+    assert!(Pattern::Heartbeat.is_on(ms(50)));
+    assert!(!Pattern::Heartbeat.is_on(ms(150)));
+    assert!(Pattern::Heartbeat.is_on(ms(250)));
+    assert!(!Pattern::Heartbeat.is_on(ms(1000)));
+}
+
+#[test]
+fn test_json_string() {
+    let status = r#"{"status":"play","title":"Caf\u00e9 \"del Mar\"","artist":"","volume":30}"#;
+    assert_eq!(json_string(status, "status"), Some("play".into()));
+    assert_eq!(json_string(status, "title"), Some("Café \"del Mar\"".into()));
+    assert_eq!(json_string(status, "artist"), Some("".into()));
+    assert_eq!(json_string(status, "volume"), None);
+    assert_eq!(json_string(status, "album"), None);
+
+    let status = r#"{"status":"play","seek":12345,"offset": -2,"duration":0}"#;
+    assert_eq!(json_integer(status, "seek"), Some(12345));
+    assert_eq!(json_integer(status, "offset"), Some(-2));
+    assert_eq!(json_integer(status, "status"), None);
+    assert_eq!(json_integer(status, "volume"), None);
+}
+
+#[test]
+fn test_display_text() {
+    assert_eq!(display::to_ascii("Grüezi"), "Grueezi");
+    assert_eq!(display::to_ascii("Déjà"), "D?j?");
+    assert_eq!(display::scroll("short", 8, 3), "short");
+    assert_eq!(display::scroll("abcdef", 4, 0), "abcd");
+    assert_eq!(display::scroll("abcdef", 4, 4), "ef  ");
+    assert_eq!(display::scroll("abcdef", 4, 8), " abc");
+    assert_eq!(display::scroll("abcdef", 4, 9), "abcd");
+}
+
+#[test]
+fn test_ssd1306_frame() {
+    let screen = Screen {
+        station: Some("srf1".into()),
+        title: None,
+        volume: 50,
+        muted: false,
+        clock: "12:34".into(),
+        date: "Fri 16.10.".into(),
+        next_alarm: None,
+        weather: None,
+        artwork: None,
+    };
+    let frame = ssd1306::Frame::render(&screen, 0);
+    // The clock is right-aligned on the first page
+    assert_eq!(frame.0[128 - 30..128 - 25], font::glyph('1'));
+    // The station starts at the left edge of the third page
+    assert_eq!(frame.0[256..261], font::glyph('s'));
+    // The volume bar is filled to the middle
+    assert_eq!(frame.0[6 * 128 + 60], 0x7e);
+    assert_eq!(frame.0[6 * 128 + 70], 0x42);
+
+    let config = Config::parse("[display]\ntype = \"ssd1306\"").unwrap();
+    assert_eq!(config.display, Some(DisplayConfig::Ssd1306 { address: 0x3c }));
+
+    // While nothing is playing, a large clock is shown
+    let screen = Screen {
+        station: None,
+        next_alarm: Some("06:45".into()),
+        ..screen
+    };
+    let frame = ssd1306::Frame::render(&screen, 0);
+    // The vertical bar of the '1', scaled by 3 over three pages
+    assert_eq!(frame.0[2 * 3], 0xff);
+    assert_eq!(frame.0[128 + 2 * 3 + 2], 0xff);
+    assert_eq!(frame.0[256 + 2 * 3], 0x1f);
+    // The date and the alarm are centered below
+    assert_eq!(frame.0[3 * 128 + 15..3 * 128 + 20], font::glyph('F'));
+    assert_eq!(frame.0[4 * 128 + 12..4 * 128 + 17], font::glyph('A'));
+    // The clock moves to prevent burn-in
+    let moved = ssd1306::Frame::render(&screen, 200);
+    assert!(moved.0[..128].iter().all(|byte| *byte == 0));
+    assert_eq!(moved.0[128 + 7 + 2 * 3], 0xff);
+    assert_eq!(moved.0[3 * 128 + 7 + 2 * 3], 0x1f);
+}
+
+#[test]
+fn test_hd44780_lines() {
+    let screen = Screen {
+        station: Some("Radio Zürich".into()),
+        title: Some("Artist - Title".into()),
+        volume: 50,
+        muted: false,
+        clock: "12:34".into(),
+        date: "Fri 16.10.".into(),
+        next_alarm: None,
+        weather: Some("7C overcast".into()),
+        artwork: None,
+    };
+    let layout = [LcdLine::Station, LcdLine::Volume, LcdLine::Clock, LcdLine::Weather];
+    assert_eq!(
+        hd44780::render_lines(&screen, &layout, 16, 0),
+        vec!["Radio Zuerich   ", "Vol  50% ###    ", "12:34           ", "7C overcast     "]
+    );
+    assert_eq!(hd44780::render_lines(&screen, &[LcdLine::Title], 8, 2), vec!["tist - T"]);
+
+    let config = Config::parse("[display]\ntype = \"hd44780\"\ncolumns = 20\nlayout = [\"clock\"]").unwrap();
+    assert_eq!(
+        config.display,
+        Some(DisplayConfig::Hd44780 {
+            address: 0x27,
+            columns: 20,
+            layout: vec![LcdLine::Clock],
+        })
+    );
+    assert!(Config::parse("[display]\ntype = \"hd44780\"\nlayout = []").is_err());
+}
+
+#[test]
+fn test_epaper_canvas() {
+    let content = epaper::Content {
+        station: Some("srf1".into()),
+        title: None,
+        clock: "12:34".into(),
+        artwork: None,
+    };
+    let canvas = epaper::Canvas::render(&content);
+    // The top row of the '1' in the clock, scaled by 3
+    assert!(canvas.is_black(4 + 2 * 3, 4));
+    assert!(canvas.is_black(4 + 2 * 3 + 2, 4 + 2));
+    assert!(!canvas.is_black(4 + 2 * 3 + 3, 4));
+    // Blank areas are white
+    assert!(!canvas.is_black(0, 0));
+    assert!(!canvas.is_black(295, 127));
+
+    // The artwork is shown on the right
+    let artwork = artwork::dither(&[0; artwork::ARTWORK_SIZE * artwork::ARTWORK_SIZE], 120, 120);
+    let canvas = epaper::Canvas::render(&epaper::Content {
+        artwork: Some(artwork),
+        ..content
+    });
+    assert!(canvas.is_black(172, 4));
+    assert!(canvas.is_black(291, 123));
+    assert!(!canvas.is_black(171, 4));
+    assert!(!canvas.is_black(292, 123));
+}
+
+#[test]
+fn test_sniff() {
+    let headers = "HTTP/1.1 302 Found\r\nLocation: https://www.example.com/\r\nContent-Type: text/plain\r\n\r\n\
+                   HTTP/1.1 200 OK\r\nContent-Type: text/html; charset=utf-8\r\n\r\n";
+    assert_eq!(sniff::content_type(headers).as_deref(), Some("text/html"));
+    assert_eq!(sniff::content_type("ICY 200 OK\r\ncontent-type:audio/mpeg\r\n").as_deref(), Some("audio/mpeg"));
+    assert_eq!(sniff::content_type("HTTP/1.1 200 OK\r\n\r\n"), None);
+
+    assert_eq!(sniff::problem("audio/aac"), None);
+    assert_eq!(sniff::problem("application/ogg"), None);
+    assert_eq!(sniff::problem("application/vnd.apple.mpegurl"), None);
+    assert_eq!(sniff::problem("text/html"), Some("probably a web page, not a stream"));
+    assert_eq!(sniff::problem("application/json"), Some("not an audio format"));
+
+    // Only HTTP streams are checked
+    assert_eq!(sniff::diagnose("srf1", "/music/track.mp3"), None);
+
+    let status = Status {
+        stream_problem: Some("Station srf1 returned text/html — probably a web page, not a stream".into()),
+        ..Default::default()
+    };
+    assert!(status.render().contains(
+        "\nstation: ok\nstream problem: Station srf1 returned text/html — probably a web page, not a stream\n"
+    ));
+}
+
+#[test]
+fn test_artwork() {
+    // Black and white stay as they are, grey is dithered to a pattern with
+    // about as many black as white pixels
+    assert!(artwork::dither(&[0; 16], 4, 4).black.iter().all(|&black| black));
+    assert!(artwork::dither(&[255; 16], 4, 4).black.iter().all(|&black| !black));
+    let grey = artwork::dither(&[128; 100], 10, 10);
+    let black = grey.black.iter().filter(|&&black| black).count();
+    assert!((45..=55).contains(&black), "{} black pixels", black);
+    assert!(grey.is_black(1, 0) != grey.is_black(0, 0));
+    assert!(!grey.is_black(10, 0));
+
+    assert_eq!(artwork::resolve("/albumart?web=x"), "http://127.0.0.1:3000/albumart?web=x");
+    assert_eq!(artwork::resolve("https://example.com/logo.png"), "https://example.com/logo.png");
+
+    // Every URL has its own cached file
+    let path = artwork::cache_path("/var/cache/inputd", "https://example.com/logo.png");
+    assert!(path.starts_with("/var/cache/inputd"));
+    assert_eq!(path, artwork::cache_path("/var/cache/inputd", "https://example.com/logo.png"));
+    assert_ne!(path, artwork::cache_path("/var/cache/inputd", "https://example.com/other.png"));
+
+    let config = Config::parse(
+        "[display]\ntype = \"epaper\"\ndc_pin = 25\nreset_pin = 24\nbusy_pin = 23\nartwork_cache = \"/tmp/art\"",
+    )
+    .unwrap();
+    assert!(matches!(config.display, Some(DisplayConfig::Epaper { artwork_cache: Some(_), .. })));
+}
+
+#[test]
+fn test_headphones_volume() {
+    let config = Config::parse("[headphones]\ndetect_pin = 12\nmax_volume = 50").unwrap();
+    let max_volume = config.headphones.unwrap().max_volume;
+    let shared = SharedState::default();
+    assert_eq!(shared.output_volume(80), 80);
+    *shared.headphones.lock().unwrap() = Some(max_volume);
+    assert_eq!(shared.output_volume(80), 40);
+    assert_eq!(shared.output_volume(100), 50);
+
+    // The maximum volume of the band is applied as well
+    *shared.band_max_volume.lock().unwrap() = Some(50);
+    assert_eq!(shared.output_volume(100), 25);
+    *shared.headphones.lock().unwrap() = None;
+    assert_eq!(shared.output_volume(100), 50);
+
+    assert!(Config::parse("[headphones]\ndetect_pin = 12\nmax_volume = 101").is_err());
+}
+
+#[test]
+fn test_stations() {
+    let config = Config::parse(
+        r#"
+        [[stations]]
+        id = "srf1"
+        name = "Radio SRF 1"
+
+        [[stations]]
+        id = "srf3"
+        max_volume = 60
+        "#,
+    )
+    .unwrap();
+    assert_eq!(config.stations[0].name(), "Radio SRF 1");
+    assert_eq!(config.stations[1].name(), "srf3");
+
+    let shared = SharedState {
+        stations: config.stations,
+        ..Default::default()
+    };
+    assert_eq!(shared.station_name("srf1"), "Radio SRF 1");
+    assert_eq!(shared.station_name("srf3"), "srf3");
+    assert_eq!(shared.station_name("jazz"), "jazz");
+
+    // The maximum volume of the station is combined with the one of the band
+    *shared.station_max_volume.lock().unwrap() = Some(60);
+    assert_eq!(shared.output_volume(100), 60);
+    *shared.band_max_volume.lock().unwrap() = Some(50);
+    assert_eq!(shared.output_volume(100), 30);
+
+    assert!(Config::parse("[[stations]]\nid = \"srf1\"\n[[stations]]\nid = \"srf1\"").is_err());
+    assert!(Config::parse("[[stations]]\nid = \"srf1\"\nname = \"\"").is_err());
+    assert!(Config::parse("[[stations]]\nid = \"srf1\"\nmax_volume = 120").is_err());
+}
+
+#[test]
+fn test_retry_policy() {
+    let config = Config::parse(
+        r#"
+        [streams]
+        timeout_ms = 10000
+        attempts = 4
+
+        [[stations]]
+        id = "srf1"
+
+        [[stations]]
+        id = "community"
+        stream_timeout_ms = 30000
+        stream_attempts = 5
+        stream_backoff_ms = 2000
+        "#,
+    )
+    .unwrap();
+    let global = config.streams.retry_policy();
+    assert_eq!(
+        global,
+        RetryPolicy {
+            timeout: Duration::from_secs(10),
+            attempts: 4,
+            backoff: Duration::from_secs(0),
+        }
+    );
+    assert_eq!(config.stations[0].retry_policy(&config.streams), global);
+    let policy = config.stations[1].retry_policy(&config.streams);
+    assert_eq!(policy.timeout, Duration::from_secs(30));
+    assert_eq!(policy.attempts, 5);
+
+    // The backoff doubles with every attempt
+    assert_eq!(policy.delay(1), Duration::from_secs(2));
+    assert_eq!(policy.delay(2), Duration::from_secs(4));
+    assert_eq!(policy.delay(4), Duration::from_secs(16));
+    assert_eq!(global.delay(2), Duration::from_secs(0));
+
+    assert!(Config::parse("[[stations]]\nid = \"srf1\"\nstream_attempts = 0").is_err());
+    assert!(Config::parse("[[stations]]\nid = \"srf1\"\nstream_timeout_ms = 0").is_err());
+    assert!(Config::parse("[streams]\nattempts = 0").is_err());
+
+    // The backoff is bounded, so that the delay can't overflow
+    assert!(Config::parse("[streams]\nbackoff_ms = 3600000").is_ok());
+    assert!(Config::parse("[streams]\nbackoff_ms = 3600001").is_err());
+    assert!(Config::parse("[[stations]]\nid = \"srf1\"\nstream_backoff_ms = 18446744073709551615").is_err());
+    let policy = Config::parse("[streams]\nbackoff_ms = 3600000").unwrap().streams.retry_policy();
+    assert_eq!(policy.delay(u32::MAX), Duration::from_secs(512 * 3600));
+    assert_eq!(Config::parse("").unwrap().streams.retry_policy().timeout, Duration::from_secs(15));
+}
+
+#[test]
+fn test_duck() {
+    let button = |action: &str| format!("[[buttons]]\nname = \"doorbell\"\npin = 23\naction = {}", action);
+    let config = Config::parse(&button("{ duck = { volume = 20, duration_s = 30 } }")).unwrap();
+    assert_eq!(
+        config.buttons[0].action,
+        ButtonAction::Duck(Duck {
+            volume: 20,
+            duration_s: 30
+        })
+    );
+    assert_eq!(Config::parse(&button("\"pause\"")).unwrap().buttons[0].action, ButtonAction::Pause);
+    assert!(Config::parse(&button("{ duck = { volume = 120, duration_s = 30 } }")).is_err());
+    assert!(Config::parse(&button("{ duck = { volume = 20, duration_s = 0 } }")).is_err());
+
+    // Ducking limits the volume instead of scaling it
+    let shared = SharedState::default();
+    *shared.duck_volume.lock().unwrap() = Some(20);
+    assert_eq!(shared.output_volume(80), 20);
+    assert_eq!(shared.output_volume(10), 10);
+    *shared.band_max_volume.lock().unwrap() = Some(50);
+    assert_eq!(shared.output_volume(30), 15);
+}
+
+#[test]
+fn test_line_out() {
+    let line_out = "[line_out]\nline_out_command = \"amixer sset Speaker off\"\n\
+                    speakers_command = \"amixer sset Speaker on\"\n";
+    let button = "[[buttons]]\nname = \"tonabnehmer\"\npin = 27\naction = \"line_out\"\n";
+    let config = Config::parse(&format!("{}\n{}", line_out, button)).unwrap();
+    assert_eq!(config.buttons[0].action, ButtonAction::LineOut);
+    // The line output must be configured
+    assert!(Config::parse(button).is_err());
+    assert!(Config::parse("[[evdev]]\ndevice = \"/dev/input/event0\"\nkeys = { 28 = \"line_out\" }").is_err());
+
+    let _lock = DRY_RUN_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    DRY_RUN.store(true, Ordering::SeqCst);
+    let shared = SharedState::default();
+    shared.toggle_line_out(config.line_out.as_ref());
+    assert!(shared.line_out.load(Ordering::SeqCst));
+    assert!(shared.snapshot().line_out);
+    shared.toggle_line_out(config.line_out.as_ref());
+    assert!(!shared.line_out.load(Ordering::SeqCst));
+    DRY_RUN.store(false, Ordering::SeqCst);
+}
+
+#[test]
+fn test_parse_mixer_controls() {
+    let output = "Simple mixer control 'Digital',0\nSimple mixer control 'Analogue Playback Boost',0\n";
+    assert_eq!(alsa::parse_controls(output), vec!["Digital", "Analogue Playback Boost"]);
+    assert!(alsa::parse_controls("").is_empty());
+
+    let cards = " 0 [sndrpihifiberry]: HifiberryDac - snd_rpi_hifiberry_dac\n\
+                 \x20                     snd_rpi_hifiberry_dac\n\
+                 \x201 [Headphones     ]: bcm2835_headpho - bcm2835 Headphones\n";
+    assert_eq!(
+        alsa::parse_cards(cards),
+        vec![("0".into(), "sndrpihifiberry".into()), ("1".into(), "Headphones".into())]
+    );
+
+    let config = Config::parse(
+        r#"
+        [alsa]
+        card = "sndrpihifiberry"
+        mixer = "Digital"
+        preferred = [{ card = "Device", mixer = "PCM" }, { card = "2", mixer = "Speaker" }]
+        "#,
+    )
+    .unwrap();
+    let alsa = config.alsa.unwrap();
+    let default_card = alsa.default_card();
+    let mut cards = alsa::parse_cards(cards);
+    assert_eq!(alsa::select_card(&alsa.preferred, &default_card, &cards), &default_card);
+    cards.push(("2".into(), "Audio".into()));
+    assert_eq!(alsa::select_card(&alsa.preferred, &default_card, &cards).mixer, "Speaker");
+    cards.push(("3".into(), "Device".into()));
+    assert_eq!(alsa::select_card(&alsa.preferred, &default_card, &cards).mixer, "PCM");
+}
+
+#[test]
+fn test_volume_sink() {
+    #[derive(Default)]
+    struct Sink(Mutex<Vec<u8>>);
+    impl VolumeSink for Sink {
+        fn set_volume(&self, volume: u8) -> bool {
+            self.0.lock().unwrap().push(volume);
+            true
+        }
+        fn set_mute(&self, _muted: bool) {}
+    }
+
+    // Sinks without channel volumes ignore the balance
+    let sink = Sink::default();
+    assert!(sink.set_channel_volumes(40, 80));
+    assert_eq!(*sink.0.lock().unwrap(), vec![80]);
+}
+
+#[test]
+fn test_balance() {
+    assert_eq!(balance_from_value(50, 0), 0);
+    assert_eq!(balance_from_value(0, 0), -100);
+    assert_eq!(balance_from_value(100, 0), 100);
+    assert_eq!(balance_from_value(75, 0), 50);
+    assert_eq!(balance_from_value(54, 5), 0);
+    assert_eq!(balance_from_value(46, 5), 0);
+    assert_eq!(balance_from_value(100, 5), 100);
+    assert_eq!(balance_from_value(0, 5), -100);
+
+    assert_eq!(channel_volumes(80, 0), (80, 80));
+    assert_eq!(channel_volumes(80, 50), (40, 80));
+    assert_eq!(channel_volumes(80, -100), (80, 0));
+
+    // Balance requires an ALSA mixer
+    let analog = "[[analog]]\nchannel = \"A0\"\nrole = \"volume\"\n\n[[analog]]\nchannel = \"A2\"\nrole = \"balance\"\n";
+    assert!(Config::parse(analog).is_err());
+    assert!(Config::parse(&format!("{}\n[alsa]\nmixer = \"Digital\"", analog)).is_ok());
+}
+
+#[test]
+fn test_battery() {
+    assert_eq!(battery::voltage(16384, 2.0), 4.096);
+    assert_eq!(battery::voltage(-5, 2.0), 0.0);
+
+    let mut monitor = BatteryMonitor::new(6.2, 5.8);
+    assert_eq!(monitor.update(6.6), None);
+    assert_eq!(monitor.update(6.1), Some(BatteryEvent::Low));
+    assert_eq!(monitor.update(6.22), None);
+    assert_eq!(monitor.update(6.3), Some(BatteryEvent::Recovered));
+
+    // A single drop doesn't shut down
+    assert_eq!(monitor.update(5.7), Some(BatteryEvent::Low));
+    assert_eq!(monitor.update(5.9), None);
+    assert_eq!(monitor.update(5.7), None);
+    assert_eq!(monitor.update(5.7), None);
+    assert_eq!(monitor.update(5.7), Some(BatteryEvent::Critical));
+
+    // The battery channel must be free and the voltages ordered
+    let battery = "[battery]\nchannel = \"A3\"\nwarn_voltage = 3.3\nshutdown_voltage = 3.0\n";
+    assert!(Config::parse(battery).is_ok());
+    assert!(Config::parse(&battery.replace("3.0", "3.4")).is_err());
+    assert!(Config::parse(&format!("{}\n[[analog]]\nchannel = \"A3\"\nrole = \"monitor\"", battery)).is_err());
+}
+
+#[test]
+fn test_health() {
+    assert_eq!(health::parse_temperature("48312\n"), Some(48.312));
+    assert_eq!(health::parse_temperature(""), None);
+
+    let throttling = |under_voltage, throttled| Some(Throttling { under_voltage, throttled });
+    assert_eq!(health::parse_throttling("throttled=0x0\n"), throttling(false, false));
+    assert_eq!(health::parse_throttling("throttled=0x50005\n"), throttling(true, true));
+    assert_eq!(health::parse_throttling("throttled=0x50000\n"), throttling(false, false));
+    assert_eq!(health::parse_throttling("throttled=0x8"), throttling(false, true));
+    assert_eq!(health::parse_throttling("error"), None);
+}
+
+#[test]
+fn test_dx() {
+    // Directories are expanded to their files
+    let dir = std::env::temp_dir().join(format!("inputd-test-dx-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("b.mp3"), "").unwrap();
+    fs::write(dir.join("a.mp3"), "").unwrap();
+    let url = "https://archive.org/download/ird059/tcp_d1_01.mp3".to_string();
+    let pool = dx::pool(&[dir.display().to_string(), url.clone()]);
+    fs::remove_dir_all(&dir).unwrap();
+    assert_eq!(pool.len(), 3);
+    assert!(pool[0].ends_with("/a.mp3") && pool[1].ends_with("/b.mp3"));
+    assert_eq!(pool[2], url);
+
+    // The same recording isn't played twice in a row
+    let mut shuffle = dx::Shuffle::new(42);
+    let mut last = None;
+    for _ in 0..100 {
+        let index = shuffle.pick(3);
+        assert!(index.is_some_and(|index| index < 3) && index != last);
+        last = index;
+    }
+    assert_eq!(shuffle.pick(1), Some(0));
+    assert_eq!(shuffle.pick(1), Some(0));
+    assert_eq!(shuffle.pick(0), None);
+    assert!((0.1..=0.3).contains(&shuffle.fade_frequency()));
+
+    let config = Config::parse("[dx]\nband = \"kurz\"\nrecordings = [\"/srv/dx\"]").unwrap();
+    let dx = config.dx.as_ref().unwrap();
+    assert_eq!((dx.noise, dx.fading, dx.device.as_str()), (0.1, 0.6, "default"));
+    let args = dx::ffmpeg_args("/srv/dx/a.mp3", dx, 0.2);
+    assert_eq!(args[5], "/srv/dx/a.mp3");
+    assert_eq!(
+        args[7],
+        "[0:a]highpass=f=300,lowpass=f=3000,tremolo=f=0.20:d=0.60[rx];\
+         anoisesrc=color=brown:amplitude=0.10[noise];[rx][noise]amix=inputs=2:duration=first"
+    );
+    assert_eq!(args[8..], ["-f", "alsa", "default"]);
+
+    // The band plays the recordings instead of its playlist
+    assert!(config.is_dx_band("kurz"));
+    assert_eq!(config.band_playlist("kurz", None), None);
+    assert_eq!(config.band_playlist("ukw", None), Some("mellow"));
+
+    assert!(Config::parse("[dx]\nband = \"fm\"\nrecordings = [\"/srv/dx\"]").is_err());
+    assert!(Config::parse("[dx]\nband = \"kurz\"\nrecordings = []").is_err());
+    assert!(Config::parse("[dx]\nband = \"kurz\"\nrecordings = [\"/srv/dx\"]\nnoise = 1.5").is_err());
+}
+
+#[test]
+fn test_fan() {
+    let curve = [(50.0, 0), (55.0, 40), (70.0, 100)];
+    assert_eq!(fan::duty_cycle(&curve, 20.0), 0);
+    assert_eq!(fan::duty_cycle(&curve, 52.5), 20);
+    assert_eq!(fan::duty_cycle(&curve, 62.5), 70);
+    assert_eq!(fan::duty_cycle(&curve, 90.0), 100);
+    assert_eq!(fan::duty_cycle(&[], 90.0), 0);
+
+    // The fan slows down with a hysteresis
+    let mut controller = FanController::new(curve.to_vec());
+    assert_eq!(controller.update(55.0), 40);
+    assert_eq!(controller.update(54.0), 40);
+    assert_eq!(controller.update(52.5), 36);
+    assert_eq!(controller.update(45.0), 0);
+
+    let config = Config::parse("[fan]\npin = 18\ncurve = [[40.0, 30], [60.0, 100]]").unwrap();
+    let fan = config.fan.unwrap();
+    assert_eq!(fan.sensor, "/sys/class/thermal/thermal_zone0/temp");
+    assert_eq!(fan.curve, vec![(40.0, 30), (60.0, 100)]);
+    assert!(Config::parse("[fan]\npin = 17").is_err());
+    assert!(Config::parse("[fan]\npin = 18\ncurve = []").is_err());
+    assert!(Config::parse("[fan]\npin = 18\ncurve = [[60.0, 30], [40.0, 100]]").is_err());
+    assert!(Config::parse("[fan]\npin = 18\ncurve = [[40.0, 130]]").is_err());
+
+    let status = Status {
+        fan_duty: Some(40),
+        ..Default::default()
+    };
+    assert!(status.render().contains("\nstation: ok\nfan: 40%\n"));
+}
+
+#[test]
+fn test_ambient_light() {
+    assert_eq!(light::tsl2561_lux(0, 0), 0.0);
+    assert!((light::tsl2561_lux(100, 20) - 38.2).abs() < 0.1);
+    assert_eq!(light::tsl2561_lux(100, 200), 0.0);
+
+    let config = Config::parse("[ambient_light]\nsensor = \"bh1750\"\nmin_brightness = 20").unwrap();
+    let light = config.ambient_light.unwrap();
+    assert_eq!(light.address(), 0x23);
+    assert_eq!(light::brightness(0.0, &light), 20);
+    assert_eq!(light::brightness(1.0, &light), 20);
+    assert_eq!(light::brightness(300.0f64.sqrt(), &light), 60);
+    assert_eq!(light::brightness(10000.0, &light), 100);
+    assert!(Config::parse("[ambient_light]\nsensor = \"tsl2561\"\nmin_brightness = 60\nmax_brightness = 50").is_err());
+    assert!(Config::parse("[ambient_light]\nsensor = \"tsl2561\"\ndark_lux = 0.0").is_err());
+    assert!(Config::parse("[ambient_light]\nsensor = \"veml7700\"").is_err());
+
+    let config = Config::parse("[[outputs]]\npin = 12\nwhen = \"always\"\ndim_with_light = true").unwrap();
+    let state = RadioState {
+        ready: true,
+        playing: false,
+        volume: 50,
+        since_error: None,
+        buffering: false,
+        tuning_accuracy: 0,
+        wifi_quality: None,
+        idle: false,
+        brightness: 40,
+    };
+    assert_eq!(outputs::level(&config.outputs[0], &state), 0.4);
+}
+
+#[test]
+fn test_status() {
+    let status = Status {
+        uptime: Duration::from_secs(90),
+        threads: vec![("adc", Duration::from_millis(200))],
+        adc_errors: 2,
+        player: Some("play".into()),
+        streams_started: 3,
+        streams_skipped: 1,
+        stream_latency: Some(Duration::from_millis(1250)),
+        config_hash: Some(status::fnv1a(b"")),
+        ..Default::default()
+    };
+    assert_eq!(
+        status.render(),
+        "uptime: 90s\nthread adc: alive 0s ago\nadc last read: never\nadc errors: 2\nadc recoveries: 0\n\
+         player: play\nplaying since: never\nbitrate: unknown\nstreams started: 3\nstreams skipped: 1\n\
+         stream latency: 1250ms\nwifi: none\nnetwork: unknown\nstation: ok\nconfig hash: cbf29ce484222325\n"
+    );
+    assert_eq!(status::fnv1a(b"a"), 0xaf63dc4c8601ec8c);
+
+    let routes = "Iface\tDestination\tGateway\tFlags\n\
+                  wlan0\t00000000\t0100A8C0\t0003\n\
+                  wlan0\t0000A8C0\t00000000\t0001\n";
+    assert!(status::has_default_route(routes));
+
+    let wireless = "Inter-| sta-|   Quality        |   Discarded packets               | Missed | WE\n \
+                    face | tus | link level noise |  nwid  crypt   frag  retry   misc | beacon | 22\n \
+                    wlan0: 0000   57.  -53.  -256        0      0      0      0     13        0\n";
+    assert_eq!(wifi::parse_link_quality(wireless), Some(81));
+
+    assert_eq!(connectivity::classify(false, false, None), Connectivity::NoLink);
+    assert_eq!(connectivity::classify(true, false, None), Connectivity::NoDns);
+    assert_eq!(connectivity::classify(true, true, Some(302)), Connectivity::CaptivePortal);
+    assert_eq!(connectivity::classify(true, true, None), Connectivity::CaptivePortal);
+    assert_eq!(connectivity::classify(true, true, Some(204)), Connectivity::Online);
+    assert_eq!(Connectivity::CaptivePortal.to_string(), "captive portal");
+    assert_eq!(wifi::parse_link_quality(&wireless[..wireless.find(" wlan0").unwrap()]), None);
+    assert!(!status::has_default_route(&routes.replace("\t00000000\t0100", "\t0001A8C0\t0100")));
+}
+
+#[test]
+fn test_snapshot() {
+    let snapshot = Snapshot {
+        playlist: Some("Jazz: live".into()),
+        volume: 42,
+        muted: true,
+        line_out: true,
+        locked: true,
+        sleep_timer_until: Some(1_602_841_520),
+    };
+    let rendered = snapshot.render();
+    assert_eq!(
+        rendered,
+        "playlist: Jazz: live\nvolume: 42\nmuted: true\nline out: true\nlocked: true\nsleep timer until: 1602841520\n"
+    );
+    assert_eq!(Snapshot::parse(&rendered), Ok(snapshot));
+
+    // Unknown keys are ignored
+    let stopped = Snapshot::parse("volume: 30\nmuted: false\nbrightness: 3\n").unwrap();
+    assert_eq!(stopped.playlist, None);
+    assert_eq!(stopped.sleep_timer_until, None);
+    assert!(!stopped.line_out);
+    assert!(!stopped.locked);
+    assert_eq!(Snapshot::parse(&stopped.render()), Ok(stopped));
+
+    assert!(Snapshot::parse("muted: false\n").is_err());
+    assert!(Snapshot::parse("volume: loud\n").is_err());
+    assert!(Snapshot::parse("volume\n").is_err());
+}
+
+#[test]
+fn test_log_record() {
+    let record = log::record(
+        log::Level::Error,
+        "player",
+        &[("playlist", &"Jazz \"24\""), ("exit_status", &1)],
+        "Could not play\nplaylist",
+        1_602_841_520_123,
+    );
+    assert_eq!(
+        record,
+        "{\"timestamp\":\"2020-10-16T09:45:20.123Z\",\"level\":\"error\",\"subsystem\":\"player\",\
+         \"message\":\"Could not play\\nplaylist\",\"playlist\":\"Jazz \\\"24\\\"\",\"exit_status\":\"1\"}"
+    );
+    assert_eq!(
+        log::record(log::Level::Info, "adc", &[], "", 951_782_400_000),
+        "{\"timestamp\":\"2000-02-29T00:00:00.000Z\",\"level\":\"info\",\"subsystem\":\"adc\",\"message\":\"\"}"
+    );
+    assert_eq!("json".parse(), Ok(log::Format::Json));
+    assert!("xml".parse::<log::Format>().is_err());
+}
+
+#[test]
+fn test_crash_report() {
+    let report = crash::report(
+        "main",
+        "panicked at 'oops', src/main.rs:1:1",
+        "   0: inputd::main\n",
+        &["{\"message\":\"Started playlist jazz\"}".to_string()],
+        "Opts { i2c: \"/dev/i2c-1\" }",
+    );
+    let header = format!("inputd {} crashed in thread 'main'\n\npanicked at 'oops'", env!("CARGO_PKG_VERSION"));
+    assert!(report.starts_with(&header));
+    assert!(report.contains("## Backtrace\n\n   0: inputd::main\n\n## Recent events\n\n"));
+    assert!(report.contains("\n{\"message\":\"Started playlist jazz\"}\n"));
+    assert!(report.ends_with("## Configuration\n\nOpts { i2c: \"/dev/i2c-1\" }\n"));
+}
+
+#[test]
+fn test_persistent_file() {
+    // Shutting down in the scenario test flushes the files
+    let _lock = DRY_RUN_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let dir = std::env::temp_dir().join(format!("inputd-test-persist-{}", std::process::id()));
+    let path = dir.join("state");
+    let mut file = persist::PersistentFile::new("state", path.to_str().unwrap(), Duration::from_secs(60));
+    let start = Instant::now();
+
+    // The directory doesn't exist (yet), as if the filesystem was read-only
+    file.update("a".into());
+    assert!(!file.sync(start));
+
+    // The contents are kept until they can be written
+    fs::create_dir_all(&dir).unwrap();
+    assert!(file.sync(start));
+    assert_eq!(fs::read_to_string(&path).unwrap(), "a");
+
+    // Updates are written once the interval has passed
+    file.update("b".into());
+    file.update("c".into());
+    assert!(!file.sync(start + Duration::from_secs(59)));
+    assert_eq!(fs::read_to_string(&path).unwrap(), "a");
+    assert!(file.sync(start + Duration::from_secs(60)));
+    assert_eq!(fs::read_to_string(&path).unwrap(), "c");
+
+    // Or when flushing
+    file.update("d".into());
+    persist::flush();
+    assert_eq!(fs::read_to_string(&path).unwrap(), "d");
+    assert!(file.sync(start + Duration::from_secs(61)));
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_backup() {
+    let dir = std::env::temp_dir().join(format!("inputd-test-backup-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let path = |name: &str| dir.join(name).to_str().unwrap().to_string();
+    fs::write(path("config.toml"), "[idle]\nafter_minutes = 60\n").unwrap();
+    fs::write(path("state"), "playlist=jazz\n").unwrap();
+
+    // Missing files are skipped
+    let paths = [path("config.toml"), path("state"), path("history")];
+    let files = backup::export(&path("backup.tar.gz"), &paths).unwrap();
+    assert_eq!(files, vec![path("config.toml"), path("state")]);
+    assert!(backup::export(&path("empty.tar.gz"), &[path("history")]).is_err());
+
+    fs::remove_file(path("config.toml")).unwrap();
+    fs::write(path("state"), "playlist=mellow\n").unwrap();
+    let files = backup::import(&path("backup.tar.gz"), Some(&path("config.toml")), &paths).unwrap();
+    assert_eq!(files, vec![path("config.toml"), path("state")]);
+    assert_eq!(fs::read_to_string(path("config.toml")).unwrap(), "[idle]\nafter_minutes = 60\n");
+    assert_eq!(fs::read_to_string(path("state")).unwrap(), "playlist=jazz\n");
+
+    // An invalid configuration isn't imported
+    fs::write(path("config.toml"), "[idle]\nafter_minutes = 0\n").unwrap();
+    backup::export(&path("invalid.tar.gz"), &[path("config.toml")]).unwrap();
+    fs::remove_file(path("config.toml")).unwrap();
+    assert!(backup::import(&path("invalid.tar.gz"), Some(&path("config.toml")), &paths).is_err());
+    assert!(!dir.join("config.toml").exists());
+
+    // Archives with other files aren't imported at all
+    fs::write(path("config.toml"), "[idle]\nafter_minutes = 60\n").unwrap();
+    fs::write(path("sudoers"), "ALL ALL=(ALL) NOPASSWD: ALL\n").unwrap();
+    backup::export(&path("foreign.tar.gz"), &[path("config.toml"), path("sudoers")]).unwrap();
+    fs::remove_file(path("config.toml")).unwrap();
+    fs::remove_file(path("sudoers")).unwrap();
+    assert!(backup::import(&path("foreign.tar.gz"), Some(&path("config.toml")), &paths).is_err());
+    assert!(!dir.join("config.toml").exists());
+    assert!(!dir.join("sudoers").exists());
+    fs::remove_dir_all(&dir).unwrap();
+
+    assert_eq!(backup::parse_listing("/etc/\n/etc/inputd.toml\n\n"), vec!["/etc/inputd.toml".to_string()]);
+}
+
+#[test]
+fn test_detect() {
+    use detect::I2cDevice;
+
+    let devices: Vec<I2cDevice> = [0x27, 0x3c, 0x48, 0x4d, 0x60, 0x68, 0x70]
+        .iter()
+        .filter_map(|address| I2cDevice::identify(*address))
+        .collect();
+    assert_eq!(
+        devices,
+        vec![
+            I2cDevice::Pcf8574(0x27),
+            I2cDevice::Ssd1306(0x3c),
+            I2cDevice::Ads1115(0x48),
+            I2cDevice::Pcm512x(0x4d),
+            I2cDevice::Mcp4725(0x60),
+            I2cDevice::Ds3231(0x68),
+        ]
+    );
+
+    let config = detect::skeleton(&devices, Some(("sndrpihifiberry", "Digital")));
+    assert!(config.contains("\n[display]\ntype = \"hd44780\"\naddress = 0x27\n"));
+    assert!(config.contains("\n# Another display at 0x3c: only one is supported\n"));
+    let config = Config::parse(&config).unwrap();
+    assert_eq!(
+        config.display,
+        Some(DisplayConfig::Hd44780 {
+            address: 0x27,
+            columns: 16,
+            layout: vec![LcdLine::Station, LcdLine::Title],
+        })
+    );
+    assert_eq!(config.rtc.as_ref().unwrap().address, 0x68);
+    assert_eq!(config.magic_eye.as_ref().unwrap().mcp4725_address, Some(0x60));
+    assert_eq!(config.alsa.as_ref().unwrap().mixer, "Digital");
+    assert_eq!(config.analog_controls(false).len(), 1);
+
+    let config = Config::parse(&detect::skeleton(&[I2cDevice::Ads1115(0x49)], None)).unwrap();
+    assert_eq!(config.adcs[0].name, "adc49");
+    assert_eq!(config.adcs[0].address, 0x49);
+}
+
+#[test]
+fn test_adcs() {
+    let config = Config::parse(
+        r#"
+        [[adcs]]
+        name = "power"
+        address = 0x49
+
+        [[analog]]
+        channel = "A0"
+        role = "volume"
+
+        [[analog]]
+        adc = "power"
+        channel = "A0"
+        role = "monitor"
+
+        [battery]
+        adc = "power"
+        channel = "A3"
+        warn_voltage = 6.2
+        shutdown_voltage = 5.8
+        "#,
+    )
+    .unwrap();
+    assert_eq!(config.adcs[0].i2c, None);
+
+    // The same channel of another ADC is a different input
+    let records: Vec<Record> = ["100 adc power/A0 26000", "110 adc A0 0"]
+        .iter()
+        .map(|line| line.parse().unwrap())
+        .collect();
+    assert_eq!(records[0].to_string(), "100 adc power/A0 26000");
+    assert_eq!(
+        recording::replay(&records, &config, false, (0, 0)),
+        vec!["100 power/A0 (Monitor) 13", "110 A0 (Volume) 100"]
+    );
+
+    let front = FakeAdc::default();
+    let power = FakeAdc::default();
+    front.set(Channel::A0, 100);
+    power.set(Channel::A0, 200);
+    let mut adcs = AdcBank::default();
+    adcs.add(None, front);
+    adcs.add(Some("power"), power);
+    assert_eq!(adcs.read_raw(None, Channel::A0), Ok(100));
+    assert_eq!(adcs.read_raw(Some("power"), Channel::A0), Ok(200));
+    assert!(adcs.read_raw(Some("power"), Channel::A1).is_err());
+    assert!(adcs.read_raw(Some("front"), Channel::A0).is_err());
+
+    // An ADC that keeps failing is re-initialized
+    for _ in 0..8 {
+        assert!(adcs.read_raw(Some("power"), Channel::A1).is_err());
+    }
+    assert_eq!(adcs.recoveries(), 1);
+    assert!(adcs.read_raw(Some("power"), Channel::A1).is_err());
+    assert_eq!(adcs.recoveries(), 2);
+    assert_eq!(adcs.read_raw(Some("power"), Channel::A0), Ok(200));
+    for _ in 0..4 {
+        assert!(adcs.read_raw(Some("power"), Channel::A1).is_err());
+    }
+    assert_eq!(adcs.recoveries(), 2);
+
+    let invalid = [
+        // Unknown ADC
+        "[[analog]]\nadc = \"front\"\nchannel = \"A0\"\nrole = \"volume\"",
+        // Duplicate name
+        "[[adcs]]\nname = \"power\"\naddress = 0x49\n[[adcs]]\nname = \"power\"\naddress = 0x4a",
+        // Duplicate address
+        "[[adcs]]\nname = \"a\"\naddress = 0x49\n[[adcs]]\nname = \"b\"\naddress = 0x49",
+        // Not an ADS1115 address
+        "[[adcs]]\nname = \"power\"\naddress = 0x50",
+        // Battery on a channel of an analog control
+        "[[adcs]]\nname = \"power\"\naddress = 0x49\n[[analog]]\nadc = \"power\"\nchannel = \"A3\"\n\
+         role = \"monitor\"\n[battery]\nadc = \"power\"\nchannel = \"A3\"\nwarn_voltage = 6.2\n\
+         shutdown_voltage = 5.8",
+        // The default ADC is replaced
+        "[[adcs]]\nname = \"front\"\n[[analog]]\nchannel = \"A0\"\nrole = \"volume\"",
+    ];
+    for config in &invalid {
+        assert!(Config::parse(config).is_err(), "{}", config);
+    }
+    // The same address on another bus is fine
+    assert!(Config::parse(
+        "[[adcs]]\nname = \"a\"\naddress = 0x49\n[[adcs]]\nname = \"b\"\naddress = 0x49\ni2c = \"/dev/i2c-3\""
+    )
+    .is_ok());
+}
+
+#[test]
+fn test_update() {
+    assert_eq!(update::target("arm"), Some("arm-unknown-linux-musleabihf"));
+    assert_eq!(update::target("x86_64"), None);
+
+    let hash = "9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08";
+    assert_eq!(update::parse_checksum(&format!("{}  inputd-arm-unknown-linux-musleabihf\n", hash)), Some(hash));
+    assert_eq!(update::parse_checksum("Not Found"), None);
+    assert_eq!(update::parse_checksum(""), None);
+}
+
+#[test]
+fn test_setup() {
+    let table = setup::scale_lookup_table(&[(0, 100), (140, 1100), (280, 2100)], 50, 1050).unwrap();
+    assert_eq!(table, vec![(0, 50), (140, 550), (280, 1050)]);
+    assert!(setup::scale_lookup_table(&LOOKUP_TABLE_VOL, 100, 110).is_none());
+    assert!(setup::scale_lookup_table(&LOOKUP_TABLE_VOL, 500, 400).is_none());
+
+    let baseline = [(4, true), (5, false), (6, true)];
+    assert_eq!(setup::changed_pin(&baseline, &[(4, true), (5, false), (6, false)], &[]), Some((6, false)));
+    assert_eq!(setup::changed_pin(&baseline, &[(4, true), (5, true), (6, false)], &[5]), Some((6, false)));
+    assert_eq!(setup::changed_pin(&baseline, &baseline, &[]), None);
+
+    let setup = setup::Setup {
+        buttons: vec![
+            setup::DetectedButton {
+                name: "aus".into(),
+                pin: 17,
+                inverted: true,
+                action: ButtonAction::Shutdown,
+            },
+            setup::DetectedButton {
+                name: "ukw".into(),
+                pin: 22,
+                inverted: false,
+                action: ButtonAction::Playlist("mellow".into()),
+            },
+        ],
+        volume_table: Some(table),
+        alsa: Some(("0".into(), "Digital".into())),
+    };
+    let config = Config::parse(&setup.render()).unwrap();
+    assert_eq!(config.buttons.len(), 2);
+    assert!(config.buttons[0].inverted);
+    assert_eq!(config.buttons[1].playlist(), Some("mellow"));
+    assert_eq!(config.analog_controls(false)[0].lookup_table[1], (140, 550));
+    assert_eq!(config.alsa.unwrap().mixer, "Digital");
+}
+
+#[test]
+fn test_inspect_timestamp() {
+    assert_eq!(inspect::timestamp(Duration::from_millis(12_345)), "[   12.345]");
+    assert_eq!(inspect::timestamp(Duration::from_millis(5)), "[    0.005]");
+}
+
+/// Serializes the tests that change the dry-run mode.
+static DRY_RUN_LOCK: Mutex<()> = Mutex::new(());
+
+#[test]
+fn test_dry_run() {
+    let _lock = DRY_RUN_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    DRY_RUN.store(true, Ordering::SeqCst);
+    let status = Command::new("/nonexistent/shutdown").arg("now").execute();
+    DRY_RUN.store(false, Ordering::SeqCst);
+    assert!(status.unwrap().success());
+    assert!(Command::new("/nonexistent/shutdown").execute().is_err());
+}
+
+#[test]
+fn test_recording() {
+    let record: Record = "1250 gpio ukw 1".parse().unwrap();
+    assert_eq!(
+        record,
+        Record {
+            time_ms: 1250,
+            sample: Sample::Gpio {
+                button: "ukw".into(),
+                low: true
+            },
+        }
+    );
+    assert_eq!(record.to_string(), "1250 gpio ukw 1");
+    let record: Record = "1300 adc A0-A1 -12".parse().unwrap();
+    assert_eq!(record.to_string(), "1300 adc A0-A1 -12");
+    assert!("1300 adc A5 12".parse::<Record>().is_err());
+    assert!("gpio ukw 1".parse::<Record>().is_err());
+
+    // A bouncing contact only triggers a press once it is stable
+    let config = Config::parse("[debounce]\nsamples = 3").unwrap();
+    let mut records = vec![];
+    for (i, low) in [true, false, true, true, true, true, false, false, false].iter().enumerate() {
+        records.push(Record {
+            time_ms: i as u64 * 10,
+            sample: Sample::Gpio {
+                button: "ukw".into(),
+                low: *low,
+            },
+        });
+    }
+    records.push("100 adc A0 0".parse().unwrap());
+    records.push("110 adc A0 5".parse().unwrap());
+    records.push("120 adc A0 26000".parse().unwrap());
+    assert_eq!(
+        recording::replay(&records, &config, false, (0, 0)),
+        vec!["40 pressed ukw", "80 released ukw", "100 A0 (Volume) 100", "120 A0 (Volume) 13"]
+    );
+}
+
+/// Strictly increasing lookup tables with 2-30 entries.
+fn lookup_tables() -> impl Strategy<Value = Vec<(u16, u16)>> {
+    proptest::collection::vec((1u16..1000, 1u16..1000), 2..30).prop_map(|steps| {
+        let (mut angle, mut value) = (0, 0);
+        steps
+            .into_iter()
+            .map(|(angle_step, value_step)| {
+                angle += angle_step;
+                value += value_step;
+                (angle, value)
+            })
+            .collect()
+    })
+}
+
+proptest! {
+    #[test]
+    fn prop_measurement_to_angle_is_monotonic(table in lookup_tables(), a: u16, b: u16) {
+        let (low, high) = (a.min(b), a.max(b));
+        let (low_angle, high_angle) = (measurement_to_angle(&table, low), measurement_to_angle(&table, high));
+        prop_assert!(low_angle <= high_angle);
+        prop_assert!(low_angle >= table[0].0 && high_angle <= table[table.len() - 1].0);
+    }
+
+    #[test]
+    fn prop_potentiometer_value_is_percentage(table in lookup_tables(), val: u16) {
+        prop_assert!(map_potentiometer_value(&table, val) <= 100);
+    }
+
+    #[test]
+    fn prop_validated_lookup_tables_dont_panic(table: Vec<(u16, u16)>, val: u16) {
+        if config::validate_lookup_table(&table).is_ok() {
+            prop_assert!(map_potentiometer_value(&table, val) <= 100);
+        }
+    }
+
+    #[test]
+    fn prop_parsing_lookup_tables_doesnt_panic(table: Vec<(u16, u16)>) {
+        let entries: Vec<String> = table.iter().map(|(angle, value)| format!("[{}, {}]", angle, value)).collect();
+        let config = format!("[[analog]]\nchannel = \"A0\"\nrole = \"volume\"\nlookup_table = [{}]", entries.join(", "));
+        let _ = Config::parse(&config);
+    }
+}
+
+/// Wait until a command containing all of the specified parts was skipped in
+/// dry-run mode.
+fn wait_for_command(parts: &[&str]) -> bool {
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while Instant::now() < deadline {
+        let skipped = SKIPPED_COMMANDS.lock().unwrap();
+        if skipped.iter().any(|command| parts.iter().all(|part| command.contains(part))) {
+            return true;
+        }
+        drop(skipped);
+        thread::sleep(Duration::from_millis(5));
+    }
+    false
+}
+
+/// Run the button and ADC threads with fake hardware in dry-run mode.
+#[test]
+fn test_scenario() {
+    let _lock = DRY_RUN_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    DRY_RUN.store(true, Ordering::SeqCst);
+
+    let config = Config::parse(
+        r#"
+        [[buttons]]
+        name = "aus"
+        pin = 17
+        action = "shutdown"
+
+        [[buttons]]
+        name = "ukw"
+        pin = 22
+        action = { playlist = "mellow" }
+
+        [debounce]
+        samples = 2
+        interval_ms = 5
+
+        [shutdown]
+        min_on_s = 0
+
+        [[analog]]
+        channel = "A0"
+        role = "volume"
+        "#,
+    )
+    .unwrap();
+    let opts = Opts::parse_from(["inputd", "--volume-ramp-ms", "0", "/dev/null", "/scenario/volumio"]);
+    let shared = Arc::new(SharedState::default());
+
+    // Buttons
+    let (aus, ukw) = (FakePin::new(), FakePin::new());
+    let now = Instant::now();
+    let inputs = [("aus", 17, &aus), ("ukw", 22, &ukw)]
+        .iter()
+        .map(|(name, gpio, pin)| GpioInput {
+            name: name.to_string(),
+            gpio: *gpio,
+            pin: Box::new((*pin).clone()),
+            inverted: false,
+            debouncer: Debouncer::new(2),
+            wear: SwitchWear::default(),
+            interval: Duration::from_millis(5),
+            next_sample: now,
+        })
+        .collect();
+    let (_emulated_buttons_tx, emulated_buttons_rx) = mpsc::channel();
+    {
+        let (opts, config, shared) = (opts.clone(), Arc::new(config.clone()), shared.clone());
+        thread::spawn(move || gpio_loop(GpioPinState::new(inputs), opts, config, shared, emulated_buttons_rx));
+    }
+
+    // Volume knob
+    let adc = FakeAdc::default();
+    adc.set(Channel::A0, 26227);
+    {
+        let (opts, shared) = (opts.clone(), shared.clone());
+        let mut adcs = AdcBank::default();
+        adcs.add(None, adc.clone());
+        let controls = config.analog_controls(false);
+        thread::spawn(move || adc_loop(adcs, opts, controls, Tuning::default(), None, shared));
+    }
+    assert!(wait_for_command(&["/scenario/volumio", "\"volume\" \"0\""]));
+
+    // Press UKW
+    ukw.set_low(true);
+    assert!(wait_for_command(&["playplaylist&name=mellow"]));
+
+    // Wiggle the volume knob
+    adc.set(Channel::A0, 0);
+    assert!(wait_for_command(&["/scenario/volumio", "\"volume\" \"100\""]));
+    adc.set(Channel::A0, 26000);
+    assert!(wait_for_command(&["/scenario/volumio", "\"volume\" \"13\""]));
+
+    // Press Aus
+    aus.set_low(true);
+    assert!(wait_for_command(&["\"shutdown\" \"now\""]));
+}
+
+#[test]
+fn test_schedule() {
+    assert_eq!(parse_weekday_time("1 06:45\n"), Some((Weekday::Mon, 405)));
+    assert_eq!(parse_weekday_time("7 23:59"), Some((Weekday::Sun, 1439)));
+    assert_eq!(parse_weekday_time("8 06:45"), None);
+    assert_eq!(parse_weekday_time(""), None);
+
+    let config = Config::parse(
+        "[[schedule]]\ndays = [\"mon\", \"tue\", \"wed\", \"thu\", \"fri\"]\ntime = \"06:45\"\n\
+         action = \"play\"\nplaylist = \"mellow\"\nvolume = 30\n\n\
+         [[schedule]]\ntime = \"22:00\"\naction = \"shutdown\"",
+    )
+    .unwrap();
+    let [weekdays, nightly] = &config.schedule[..] else {
+        panic!("Expected two schedule entries");
+    };
+    assert_eq!(next_alarm(&config.schedule, Weekday::Mon, 400).map(|entry| &entry.time[..]), Some("06:45"));
+    assert_eq!(next_alarm(&config.schedule, Weekday::Fri, 405).map(|entry| &entry.time[..]), Some("06:45"));
+    assert!(next_alarm(&config.schedule[1..], Weekday::Mon, 400).is_none());
+    assert!(weekdays.is_due(Weekday::Mon, 405));
+    assert!(!weekdays.is_due(Weekday::Sat, 405));
+    assert!(!weekdays.is_due(Weekday::Mon, 406));
+    assert!(nightly.is_due(Weekday::Sat, 1320));
+    assert!(nightly.is_due(Weekday::Wed, 1320));
+
+    assert!(Config::parse("[[schedule]]\ntime = \"06:45\"\naction = \"play\"\nplaylist = \"mellow\"").is_ok());
+    assert!(Config::parse("[[schedule]]\ntime = \"06:45\"\naction = \"play\"").is_err());
+    assert!(Config::parse("[[schedule]]\ntime = \"24:00\"\naction = \"stop\"").is_err());
+}
+
+#[test]
+fn test_rtc() {
+    // 2024-02-29 13:37:42 UTC, a Thursday
+    let registers = [0x42, 0x37, 0x13, 0x04, 0x29, 0x02, 0x24];
+    assert_eq!(rtc::decode(&registers), Some(1_709_213_862));
+    assert_eq!(rtc::encode(1_709_213_862), registers);
+    // 12 hour mode, 1 PM
+    assert_eq!(rtc::decode(&[0x42, 0x37, 0x61, 0x04, 0x29, 0x02, 0x24]), Some(1_709_213_862));
+    assert_eq!(rtc::decode(&[0x00, 0x00, 0x00, 0x01, 0x00, 0x01, 0x00]), None);
+    assert_eq!(rtc::decode(&[0x00, 0x00, 0x00, 0x01, 0x01, 0x01, 0x00]), Some(946_684_800));
+}
+
+proptest! {
+    #[test]
+    fn rtc_roundtrip(timestamp in 946_684_800u64..7_258_118_400) {
+        prop_assert_eq!(rtc::decode(&rtc::encode(timestamp)), Some(timestamp));
+    }
+}
+
+#[test]
+fn test_weather() {
+    let response = concat!(
+        r#"{"latitude":47.38,"longitude":8.54,"current_units":{"time":"iso8601","temperature_2m":"°C","#,
+        r#""weather_code":"wmo code"},"current":{"time":"2026-10-16T07:00","interval":900,"temperature_2m":6.8,"#,
+        r#""weather_code":3},"daily_units":{"precipitation_probability_max":"%"},"daily":{"time":["2026-10-16"],"#,
+        r#""precipitation_probability_max":[70]}}"#,
+    );
+    let weather = weather::parse(response).unwrap();
+    assert_eq!(weather.temperature, 6.8);
+    assert_eq!(weather.code, 3);
+    assert_eq!(weather.precipitation_probability, Some(70));
+    assert_eq!(weather.to_string(), "7 degrees, overcast, rain expected");
+    assert_eq!(weather.summary(), "7C overcast");
+
+    let response = r#"{"current":{"temperature_2m":-2.4,"weather_code":73}}"#;
+    let weather = weather::parse(response).unwrap();
+    assert_eq!(weather.to_string(), "-2 degrees, snow");
+    assert_eq!(weather::parse("{}"), None);
+
+    assert!(Config::parse("[weather]\nlatitude = 47.38\nlongitude = 8.54").is_ok());
+    assert!(Config::parse("[weather]\nlatitude = 147.38\nlongitude = 8.54").is_err());
+}
+
+#[test]
+fn test_i18n() {
+    assert_eq!(Message::NoNetwork.text(Language::En), "No network");
+    assert_eq!(Message::NoNetwork.text(Language::De), "Kein Netzwerk");
+    assert_eq!(Message::Weekday(Weekday::Thu).text(Language::De), "Do");
+    assert_eq!(display::to_ascii(Message::StationDown.text(Language::De)), "Sender gestoert");
+    assert_eq!(format_date("5 16.10.\n"), "Fri 16.10.");
+    assert_eq!(format_date("16.10."), "16.10.");
+
+    assert_eq!(Config::parse("language = \"de\"").unwrap().language, Language::De);
+    assert_eq!(Config::default().language, Language::En);
+    assert!(Config::parse("language = \"fr\"").is_err());
+}
+
+#[test]
+fn test_notifier() {
+    let config = Config::parse("[notify.target]\ntype = \"ntfy\"\ntopic = \"radio\"").unwrap();
+    let notify = config.notify.unwrap();
+    assert_eq!(notify.station_down_minutes, 10);
+    assert_eq!(
+        notifier::curl_args(&notify.target, "Battery low"),
+        vec!["-sf", "--max-time", "10", "-d", "Battery low", "https://ntfy.sh/radio"]
+    );
+
+    let webhook = NotifyTarget::Webhook {
+        url: "http://example.com/hook".into(),
+    };
+    assert_eq!(
+        notifier::curl_args(&webhook, "Thread \"adc\" crashed\n")[6],
+        r#"{"text":"Thread \"adc\" crashed\n"}"#
+    );
+
+    let telegram = NotifyTarget::Telegram {
+        bot_token: "123:abc".into(),
+        chat_id: "42".into(),
+    };
+    let args = notifier::curl_args(&telegram, "Station down");
+    assert_eq!(args[4], "chat_id=42");
+    assert_eq!(args[6], "text=Station down");
+    assert_eq!(args[7], "https://api.telegram.org/bot123:abc/sendMessage");
+
+    assert!(Config::parse("[notify.target]\ntype = \"email\"").is_err());
+}
+
+#[test]
+fn test_config_overrides() {
+    let local = r#"
+        [[analog]]
+        channel = "A2"
+        role = "tuning"
+
+        [tuning]
+        bands.ukw = ["srf1"]
+
+        [idle]
+        after_minutes = 60
+        "#;
+    let overrides = "[tuning]\nbands.ukw = [\"srf1\", \"srf2\"]";
+    let config = Config::parse_with_overrides(local, overrides).unwrap();
+    assert_eq!(config.tuning.stations("ukw"), &["srf1".to_string(), "srf2".to_string()]);
+    // Sections that aren't overridden are kept
+    assert_eq!(config.idle.unwrap().after_minutes, 60);
+
+    let config = Config::parse_with_overrides("", "language = \"de\"").unwrap();
+    assert_eq!(config.language, Language::De);
+    assert!(Config::parse_with_overrides(local, "[idle]\nafter_minutes = 0").is_err());
+    assert!(Config::parse_with_overrides(local, "[idle").is_err());
+}
+
+#[test]
+fn test_remote_config_cache() {
+    let cache = std::env::temp_dir().join(format!("inputd-test-{}.overrides", std::process::id()));
+    let cache = cache.to_str().unwrap();
+    // Nothing listens on the port, so the cached overrides are used
+    let url = "http://127.0.0.1:1/overrides.toml";
+    fs::write(cache, "language = \"de\"").unwrap();
+    assert_eq!(remote_config::load("", url, cache), "language = \"de\"");
+
+    // Unless they're invalid with the local configuration
+    fs::write(cache, "[idle]\nafter_minutes = 0").unwrap();
+    assert_eq!(remote_config::load("", url, cache), "");
+    let _ = fs::remove_file(cache);
+}
+
+#[test]
+fn test_config_profiles() {
+    let contents = r#"
+        [idle]
+        after_minutes = 60
+
+        [profile_jumpers]
+        travel = 12
+
+        [profiles.travel]
+        language = "de"
+
+        [profiles.travel.idle]
+        after_minutes = 15
+        shutdown = true
+
+        [profiles.workshop]
+        "#;
+    let config = Config::parse(contents).unwrap();
+    assert_eq!(config.idle.as_ref().unwrap().after_minutes, 60);
+    assert_eq!(config.profile_jumpers.get("travel"), Some(&12));
+
+    let config = Config::parse_with_profile(contents, "", "travel").unwrap();
+    assert_eq!(config.language, Language::De);
+    assert_eq!(config.idle.as_ref().map(|idle| (idle.after_minutes, idle.shutdown)), Some((15, true)));
+    let config = Config::parse_with_profile(contents, "[idle]
+after_minutes = 30", "workshop").unwrap();
+    assert_eq!(config.idle.unwrap().after_minutes, 30);
+    assert!(Config::parse_with_profile(contents, "", "home").is_err());
+
+    // Every profile must be valid
+    let error = Config::parse("[profiles.travel.idle]\nafter_minutes = 0").unwrap_err();
+    assert!(error.starts_with("Profile \"travel\": "));
+    assert!(Config::parse("[profile_jumpers]\ntravel = 12").is_err());
+    assert!(Config::parse("[profile_jumpers]\ntravel = 17\n[profiles.travel]").is_err());
+}
+
+#[test]
+fn test_control_logs() {
+    let path = std::env::temp_dir().join(format!("inputd-test-{}.sock", std::process::id()));
+    let path = path.to_str().unwrap();
+    let shared = Arc::new(SharedState::default());
+    control::serve(path, "true", &LockConfig::default(), shared.clone()).unwrap();
+    info!("test", "Before the request");
+
+    let mut out = vec![];
+    control::request(path, "logs", &mut out).unwrap();
+    assert!(String::from_utf8(out).unwrap().contains("Before the request"));
+
+    let (_, receiver) = log::follow();
+    info!("test", "While following");
+    assert!(receiver.iter().any(|record| record.contains("While following")));
+
+    events::record(events::EventKind::Press, "ukw");
+    let mut out = vec![];
+    control::request(path, "events", &mut out).unwrap();
+    let out = String::from_utf8(out).unwrap();
+    assert!(out.lines().any(|line| line.ends_with(" press ukw")));
+
+    // The status report is collected on demand
+    shared.heartbeat("gpio");
+    let mut out = vec![];
+    control::request(path, "status", &mut out).unwrap();
+    let out = String::from_utf8(out).unwrap();
+    assert!(out.starts_with("uptime: 0s\nthread gpio: alive 0s ago\n"));
+    assert!(out.ends_with("config hash: none\n"));
+
+    let mut out = vec![];
+    control::request(path, "stats", &mut out).unwrap();
+    assert_eq!(String::from_utf8(out).unwrap(), "Error: Unknown command \"stats\"\n");
+
+    // The brightness is set manually until it follows the ambient light
+    // again
+    *shared.ambient_brightness.lock().unwrap() = Some(30);
+    let send = |command| {
+        let mut out = vec![];
+        control::request(path, command, &mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    };
+    assert_eq!(send("brightness"), "brightness: 30% (ambient light)\n");
+    assert_eq!(send("brightness 80"), "brightness: 80% (manual)\n");
+    assert_eq!(shared.radio_state().brightness, 80);
+    assert!(send("brightness 180").starts_with("Error: Invalid brightness"));
+    assert_eq!(send("brightness auto"), "brightness: 30% (ambient light)\n");
+
+    // The lock limits the volume
+    shared.volume.store(80, Ordering::SeqCst);
+    assert_eq!(send("lock"), "lock: off\n");
+    assert_eq!(send("lock on"), "lock: on (band buttons ignored, volume limited to 30%)\n");
+    assert!(shared.is_locked());
+    assert_eq!(shared.output_volume(80), 30);
+    // Also for the volume keys of input devices
+    shared.step_volume("true", true);
+    assert_eq!(shared.volume.load(Ordering::SeqCst), 85);
+    assert_eq!(shared.output_volume(85), 30);
+    assert_eq!(send("lock off"), "lock: off\n");
+    assert_eq!(shared.output_volume(85), 85);
+    let _ = fs::remove_file(path);
+}
+
+#[test]
+fn test_events() {
+    let event = events::Event {
+        timestamp_ms: 1_792_161_125_123,
+        kind: events::EventKind::Stop,
+        detail: String::new(),
+    };
+    assert_eq!(event.to_string(), "2026-10-16T14:32:05.123Z stop");
+    let event = events::Event {
+        kind: events::EventKind::Volume,
+        detail: "30%".into(),
+        ..event
+    };
+    assert_eq!(event.to_string(), "2026-10-16T14:32:05.123Z volume 30%");
+
+    error!("test", "Something failed");
+    assert!(events::recent()
+        .iter()
+        .any(|event| event.kind == events::EventKind::Error && event.detail == "test: Something failed"));
+}
+
+#[test]
+fn test_history() {
+    let mut history = History::default();
+    history.add("2026-10-15", "jazz", Duration::from_secs(3600));
+    history.add("2026-10-16", "jazz", Duration::from_secs(600));
+    history.add("2026-10-16", "rock blues", Duration::from_secs(1800));
+    history.add("2026-10-16", "jazz", Duration::from_secs(60));
+    let rendered = history.render();
+    assert_eq!(rendered, "2026-10-15 3600 jazz\n2026-10-16 660 jazz\n2026-10-16 1800 rock blues\n");
+    assert_eq!(History::parse(&rendered), Ok(history.clone()));
+    assert!(History::parse("2026-10-16 jazz").is_err());
+
+    let today = history.summary(&["2026-10-16".to_string()]);
+    assert_eq!(
+        today,
+        vec![
+            ("rock blues".to_string(), Duration::from_secs(1800)),
+            ("jazz".to_string(), Duration::from_secs(660))
+        ]
+    );
+    assert_eq!(
+        history::render_summary("Today", &today),
+        "Today\n    0h 30m  rock blues\n    0h 11m  jazz\n"
+    );
+    let week = history.summary(&["2026-10-16".to_string(), "2026-10-15".to_string()]);
+    assert_eq!(week[0], ("jazz".to_string(), Duration::from_secs(4260)));
+    assert_eq!(history::render_summary("Today", &[]), "Today\n  nothing played\n");
 }
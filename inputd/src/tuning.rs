@@ -0,0 +1,206 @@
+use std::{
+    ops::RangeInclusive,
+    time::{Duration, Instant},
+};
+
+/// Distance in percent of the dial range from the position of a station at
+/// which the accuracy of the seek mode drops to 0%.
+const SEEK_ACCURACY_RANGE: f64 = 10.0;
+
+/// Position of the tuning dial.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DialPosition {
+    /// The dial points at the station with the specified index.
+    Station(usize),
+    /// The dial is between two stations.
+    Between,
+}
+
+/// Maps the value of the tuning dial to a station.
+///
+/// The dial range (0-100) is divided into equally wide slots, one per station.
+/// Between two neighbouring slots there's a gap of `gap` percent in which no
+/// station is received. To prevent flapping, the current position is only
+/// left once the dial has moved `hysteresis` percent past its boundary.
+pub struct Tuner {
+    gap: u8,
+    hysteresis: u8,
+    position: Option<DialPosition>,
+}
+
+impl Tuner {
+    pub fn new(gap: u8, hysteresis: u8) -> Self {
+        Self {
+            gap,
+            hysteresis,
+            position: None,
+        }
+    }
+
+    /// Forget the current position, e.g. after the band has changed.
+    pub fn reset(&mut self) {
+        self.position = None;
+    }
+
+    /// Update the tuner with the current dial value and the number of
+    /// stations on the current band.
+    ///
+    /// Returns the new position if it changed.
+    pub fn update(&mut self, value: u8, stations: usize) -> Option<DialPosition> {
+        if stations == 0 {
+            self.position = None;
+            return None;
+        }
+
+        let value = value as i32;
+        let hysteresis = self.hysteresis as i32;
+        let find_station =
+            |margin: i32| (0..stations).find(|&i| self.station_range(i, stations, margin).contains(&value));
+        let position = match self.position {
+            Some(DialPosition::Station(i)) if self.station_range(i, stations, hysteresis).contains(&value) => {
+                DialPosition::Station(i)
+            },
+            Some(DialPosition::Between) => match find_station(-hysteresis) {
+                Some(i) => DialPosition::Station(i),
+                None => DialPosition::Between,
+            },
+            _ => match find_station(0) {
+                Some(i) => DialPosition::Station(i),
+                None => DialPosition::Between,
+            },
+        };
+
+        if self.position == Some(position) {
+            None
+        } else {
+            self.position = Some(position);
+            Some(position)
+        }
+    }
+
+    /// Return the dial values belonging to a station, widened by `margin`.
+    fn station_range(&self, index: usize, stations: usize, margin: i32) -> RangeInclusive<i32> {
+        let half_gap = self.gap as i32 / 2;
+        let start = if index == 0 {
+            0
+        } else {
+            index as i32 * 100 / stations as i32 + half_gap
+        };
+        let end = if index == stations - 1 {
+            100
+        } else {
+            (index as i32 + 1) * 100 / stations as i32 - half_gap
+        };
+        (start - margin)..=(end + margin)
+    }
+}
+
+/// Return how accurately the dial is tuned to the nearest station, in percent.
+///
+/// The accuracy is 100% at the centre of a station's slot and falls off
+/// linearly to 0% at its boundaries.
+pub fn accuracy(value: u8, stations: usize) -> u8 {
+    if stations == 0 {
+        return 0;
+    }
+    let slot_width = 100.0 / stations as f64;
+    let index = ((value as f64 / slot_width) as usize).min(stations - 1);
+    let centre = (index as f64 + 0.5) * slot_width;
+    let distance = (value as f64 - centre).abs();
+    (100.0 * (1.0 - distance / (slot_width / 2.0))).max(0.0).round() as u8
+}
+
+/// Maps the value of the tuning dial to a station in the seek mode.
+///
+/// Each station has a configured position on the dial. While the dial is
+/// turned, static is received. Once it has rested (moved no more than
+/// `hysteresis` percent) for `settle`, the tuner locks onto the station whose
+/// position is nearest to the dial.
+pub struct Seeker {
+    settle: Duration,
+    hysteresis: u8,
+    /// The dial value at which the dial last moved or was locked.
+    anchor: Option<u8>,
+    /// The time at which the dial last moved, while it hasn't settled.
+    moved: Option<Instant>,
+    position: Option<DialPosition>,
+}
+
+impl Seeker {
+    pub fn new(settle: Duration, hysteresis: u8) -> Self {
+        Self {
+            settle,
+            hysteresis,
+            anchor: None,
+            moved: None,
+            position: None,
+        }
+    }
+
+    /// Forget the current position, e.g. after the band has changed.
+    pub fn reset(&mut self) {
+        self.anchor = None;
+        self.moved = None;
+        self.position = None;
+    }
+
+    /// Update the tuner with the current dial value and the dial positions
+    /// of the stations on the current band.
+    ///
+    /// Returns the new position if it changed.
+    pub fn update(&mut self, value: u8, positions: &[u8], now: Instant) -> Option<DialPosition> {
+        if positions.is_empty() {
+            self.reset();
+            return None;
+        }
+
+        let position = match self.anchor {
+            // Lock on right away at startup and after a band change
+            None => {
+                self.anchor = Some(value);
+                DialPosition::Station(nearest(value, positions))
+            },
+            Some(anchor) if (value as i32 - anchor as i32).abs() > self.hysteresis as i32 => {
+                self.anchor = Some(value);
+                self.moved = Some(now);
+                DialPosition::Between
+            },
+            Some(_) => match self.moved {
+                Some(moved) if now.saturating_duration_since(moved) < self.settle => DialPosition::Between,
+                Some(_) => {
+                    self.anchor = Some(value);
+                    self.moved = None;
+                    DialPosition::Station(nearest(value, positions))
+                },
+                None => return None,
+            },
+        };
+
+        if self.position == Some(position) {
+            None
+        } else {
+            self.position = Some(position);
+            Some(position)
+        }
+    }
+}
+
+/// Return the index of the station whose position is nearest to the dial.
+fn nearest(value: u8, positions: &[u8]) -> usize {
+    (0..positions.len())
+        .min_by_key(|&i| (positions[i] as i32 - value as i32).abs())
+        .unwrap_or(0)
+}
+
+/// Return how accurately the dial is tuned to the nearest station in the seek
+/// mode, in percent.
+///
+/// The accuracy is 100% at the position of a station and falls off linearly
+/// to 0% at a distance of 10% of the dial range.
+pub fn seek_accuracy(value: u8, positions: &[u8]) -> u8 {
+    if positions.is_empty() {
+        return 0;
+    }
+    let distance = (positions[nearest(value, positions)] as f64 - value as f64).abs();
+    (100.0 * (1.0 - distance / SEEK_ACCURACY_RANGE)).max(0.0).round() as u8
+}
@@ -0,0 +1,106 @@
+use std::{
+    env, fs,
+    os::unix::fs::PermissionsExt,
+    path::Path,
+    process::{Command, Stdio},
+};
+
+/// The release assets are downloaded from the latest GitHub release.
+const RELEASE_URL: &str = "https://github.com/dbrgn/weltempfaenger/releases/latest/download";
+
+/// Return the build target of the release binary for this architecture.
+pub fn target(arch: &str) -> Option<&'static str> {
+    match arch {
+        "arm" => Some("arm-unknown-linux-musleabihf"),
+        "aarch64" => Some("aarch64-unknown-linux-musl"),
+        _ => None,
+    }
+}
+
+/// Extract the hash from the output of `sha256sum`.
+pub fn parse_checksum(contents: &str) -> Option<&str> {
+    contents
+        .split_whitespace()
+        .next()
+        .filter(|hash| hash.len() == 64 && hash.chars().all(|c| c.is_ascii_hexdigit()))
+}
+
+/// Replace the running binary with the latest release and restart the
+/// service.
+pub fn self_update() -> Result<(), String> {
+    let target = target(env::consts::ARCH)
+        .ok_or_else(|| format!("No release binary for architecture {}", env::consts::ARCH))?;
+    let exe = env::current_exe().map_err(|e| format!("Could not locate the running binary: {}", e))?;
+    let url = format!("{}/inputd-{}", RELEASE_URL, target);
+
+    // Download next to the running binary, so that it can be renamed
+    // atomically
+    let new_exe = exe.with_extension("new");
+    let result = download(&url, &new_exe).and_then(|_| {
+        let checksum = fetch(&format!("{}.sha256", url))?;
+        let expected = parse_checksum(&checksum).ok_or("Invalid checksum file")?;
+        let actual = sha256(&new_exe)?;
+        if !actual.eq_ignore_ascii_case(expected) {
+            return Err(format!("Checksum mismatch (expected {}, got {})", expected, actual));
+        }
+        fs::set_permissions(&new_exe, fs::Permissions::from_mode(0o755))
+            .map_err(|e| format!("Could not make {} executable: {}", new_exe.display(), e))?;
+        fs::rename(&new_exe, &exe).map_err(|e| format!("Could not replace {}: {}", exe.display(), e))
+    });
+    if result.is_err() {
+        let _ = fs::remove_file(&new_exe);
+    }
+    result?;
+    info!("update", "Updated {}", exe.display());
+
+    restart_service()
+}
+
+/// Download a file with curl.
+fn download(url: &str, path: &Path) -> Result<(), String> {
+    let status_res = Command::new("/usr/bin/curl")
+        .args(["-sfL", "-o"])
+        .arg(path)
+        .arg(url)
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status();
+    match status_res {
+        Ok(status) if status.success() => Ok(()),
+        Ok(status) => Err(format!("Exit status {} when downloading {}", status, url)),
+        Err(e) => Err(format!("Could not download {}: {}", url, e)),
+    }
+}
+
+/// Download a small text file with curl.
+fn fetch(url: &str) -> Result<String, String> {
+    match Command::new("/usr/bin/curl").args(["-sfL", url]).stderr(Stdio::null()).output() {
+        Ok(output) if output.status.success() => Ok(String::from_utf8_lossy(&output.stdout).into_owned()),
+        Ok(output) => Err(format!("Exit status {} when downloading {}", output.status, url)),
+        Err(e) => Err(format!("Could not download {}: {}", url, e)),
+    }
+}
+
+/// Hash a file with `sha256sum`.
+fn sha256(path: &Path) -> Result<String, String> {
+    match Command::new("/usr/bin/sha256sum").arg(path).stderr(Stdio::null()).output() {
+        Ok(output) if output.status.success() => parse_checksum(&String::from_utf8_lossy(&output.stdout))
+            .map(str::to_string)
+            .ok_or_else(|| "Unexpected output of sha256sum".into()),
+        Ok(output) => Err(format!("Exit status {} when hashing {}", output.status, path.display())),
+        Err(e) => Err(format!("Could not hash {}: {}", path.display(), e)),
+    }
+}
+
+pub fn restart_service() -> Result<(), String> {
+    let status_res = Command::new("/usr/bin/sudo")
+        .args(["systemctl", "restart", "inputd"])
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status();
+    match status_res {
+        Ok(status) if status.success() => Ok(()),
+        Ok(status) => Err(format!("Exit status {} when restarting the service", status)),
+        Err(e) => Err(format!("Could not restart the service: {}", e)),
+    }
+}
@@ -0,0 +1,32 @@
+/// Something that plays audio and whose volume can be set, e.g. the ALSA
+/// mixer or volumio.
+pub trait VolumeSink {
+    /// Set the volume in percent, returning whether it was set.
+    fn set_volume(&self, volume: u8) -> bool;
+
+    /// Set the volumes of the left and the right channel in percent.
+    ///
+    /// Sinks without separate channel volumes use the louder channel.
+    fn set_channel_volumes(&self, left: u8, right: u8) -> bool {
+        self.set_volume(left.max(right))
+    }
+
+    /// Mute or unmute the output.
+    fn set_mute(&self, muted: bool);
+}
+
+/// Volumio, controlled with the volumio command with the specified name.
+///
+/// Volumio applies the volume to whatever source is playing, including its
+/// plugins for Spotify, AirPlay and Bluetooth.
+pub struct Volumio<'a>(pub &'a str);
+
+impl VolumeSink for Volumio<'_> {
+    fn set_volume(&self, volume: u8) -> bool {
+        crate::set_volume(self.0, volume)
+    }
+
+    fn set_mute(&self, muted: bool) {
+        crate::set_mute(self.0, muted)
+    }
+}
@@ -0,0 +1,106 @@
+use std::{
+    fmt,
+    process::{Command, Stdio},
+};
+
+use crate::i18n::{tr, Message};
+
+/// Forecast API of Open-Meteo, which doesn't need an API key.
+const OPEN_METEO_URL: &str = "https://api.open-meteo.com/v1/forecast";
+
+/// The current weather and today's forecast.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Weather {
+    /// Temperature in degrees Celsius.
+    pub temperature: f64,
+    /// WMO weather interpretation code of the current weather.
+    pub code: u8,
+    /// Probability of precipitation later today, in percent.
+    pub precipitation_probability: Option<u8>,
+}
+
+impl Weather {
+    /// Return a short description of the WMO weather code.
+    pub fn description(&self) -> Message {
+        match self.code {
+            0 => Message::Clear,
+            1 | 2 => Message::PartlyCloudy,
+            3 => Message::Overcast,
+            45 | 48 => Message::Fog,
+            51..=57 => Message::Drizzle,
+            61..=67 | 80..=82 => Message::Rain,
+            71..=77 | 85 | 86 => Message::Snow,
+            95..=99 => Message::Thunderstorm,
+            _ => Message::UnknownWeather,
+        }
+    }
+
+    /// Return a short summary for displays, e.g. "7C rain".
+    pub fn summary(&self) -> String {
+        format!("{:.0}C {}", self.temperature, tr(self.description()))
+    }
+
+    /// Return whether the code describes precipitation.
+    fn precipitation(&self) -> bool {
+        self.code >= 51
+    }
+}
+
+impl fmt::Display for Weather {
+    /// Format the weather for announcements, e.g. "7 degrees, rain expected".
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:.0} {}, {}", self.temperature, tr(Message::Degrees), tr(self.description()))?;
+        if !self.precipitation() && self.precipitation_probability.is_some_and(|probability| probability >= 50) {
+            write!(f, ", {}", tr(Message::RainExpected))?;
+        }
+        Ok(())
+    }
+}
+
+/// Fetch the weather at a location.
+pub fn fetch(latitude: f64, longitude: f64) -> Result<Weather, String> {
+    let url = format!(
+        "{}?latitude={}&longitude={}&current=temperature_2m,weather_code\
+         &daily=precipitation_probability_max&forecast_days=1&timezone=auto",
+        OPEN_METEO_URL, latitude, longitude
+    );
+    let output = Command::new("/usr/bin/curl")
+        .args(["-sf", "--max-time", "10", &url])
+        .stderr(Stdio::null())
+        .output()
+        .map_err(|e| format!("Could not run curl: {}", e))?;
+    if !output.status.success() {
+        return Err(format!("Exit status {} when fetching the weather", output.status));
+    }
+    parse(&String::from_utf8_lossy(&output.stdout)).ok_or_else(|| "Invalid weather response".into())
+}
+
+/// Parse a response of the forecast API.
+pub fn parse(json: &str) -> Option<Weather> {
+    // The units are listed with the same keys, so only look at the values
+    let current = section(json, "current")?;
+    let probability = section(json, "daily")
+        .and_then(|daily| daily.split_once("\"precipitation_probability_max\":["))
+        .and_then(|(_, rest)| number(rest))
+        .map(|probability| probability.clamp(0.0, 100.0) as u8);
+    Some(Weather {
+        temperature: number(current.split_once("\"temperature_2m\":")?.1)?,
+        code: number(current.split_once("\"weather_code\":")?.1)? as u8,
+        precipitation_probability: probability,
+    })
+}
+
+/// Return the contents of the JSON object with the key.
+fn section<'a>(json: &'a str, key: &str) -> Option<&'a str> {
+    let (_, rest) = json.split_once(&format!("\"{}\":{{", key))?;
+    rest.split_once('}').map(|(section, _)| section)
+}
+
+/// Parse the number at the start of a JSON value.
+fn number(value: &str) -> Option<f64> {
+    let value = value.trim_start();
+    let end = value
+        .find(|c: char| !(c.is_ascii_digit() || c == '-' || c == '.'))
+        .unwrap_or(value.len());
+    value[..end].parse().ok()
+}
@@ -0,0 +1,24 @@
+use std::fs;
+
+/// Link quality of the wireless interfaces.
+const WIRELESS: &str = "/proc/net/wireless";
+
+/// Maximum link quality reported by most drivers, including the one of the
+/// Raspberry Pi.
+const MAX_LINK_QUALITY: f64 = 70.0;
+
+/// Read the link quality of the first wireless interface in percent.
+pub fn read_link_quality() -> Option<u8> {
+    fs::read_to_string(WIRELESS).ok().and_then(|contents| parse_link_quality(&contents))
+}
+
+/// Parse the contents of `/proc/net/wireless`, returning the link quality of
+/// the first interface in percent.
+pub fn parse_link_quality(contents: &str) -> Option<u8> {
+    // Two header lines, then one line per interface, e.g.
+    // " wlan0: 0000   57.  -53.  -256        0      0      0      0     13        0"
+    let line = contents.lines().nth(2)?;
+    let (_interface, values) = line.split_once(':')?;
+    let link: f64 = values.split_whitespace().nth(1)?.trim_end_matches('.').parse().ok()?;
+    Some((link / MAX_LINK_QUALITY * 100.0).round().clamp(0.0, 100.0) as u8)
+}